@@ -1,4 +1,4 @@
-use crate::value::{Value, XFn};
+use crate::value::Value;
 
 use super::AsmStatement;
 
@@ -7,7 +7,7 @@ use super::AsmStatement;
 pub struct Asm {
     pub consts: Vec<Value>, // The consts.
     pub ifns: Vec<AsmFn>, // The inner functions.
-    pub xfns: Vec<XFn>, // The extend functions.
+    pub xfns: Vec<String>, // The names of the host functions.
 }
 
 impl Asm {
@@ -28,6 +28,7 @@ impl Asm {
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct AsmFn {
+    pub name: String, // The name, empty for the main function.
     pub locals: u32, // The number of local variables.
     pub statements: Vec<AsmStatement>,
 }
@@ -35,7 +36,7 @@ pub struct AsmFn {
 impl AsmFn {
     /// Build an empty [AsmFn].
     pub fn new(locals: u32, statements: Vec<AsmStatement>) -> Self {
-        Self { locals, statements }
+        Self { name: String::new(), locals, statements }
     }
 
     /// Push a statements.
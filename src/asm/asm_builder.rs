@@ -1,8 +1,8 @@
-use std::collections::HashMap;
+use std::{collections::{HashMap, HashSet}, mem::{replace, take}, path::{Path, PathBuf}};
 
-use crate::{asm::asm_statement::AsmLabel, ast::{Ast, SExp}, value::{Value, XFn}};
+use crate::{asm::asm_statement::AsmLabel, ast::{Ast, AstBuilder, SExp}, bytecode::lookup_builtin, token_stream::{TokenPos, TokenStream}, value::{Regex, Value}};
 
-use super::{asm::AsmFn, Asm, AsmStatement};
+use super::{asm::AsmFn, module, Asm, AsmError, AsmStatement, FsLoader, ImportError, ModuleLoader};
 
 pub struct AsmBuilder {
     ast: Ast,
@@ -10,9 +10,21 @@ pub struct AsmBuilder {
     consts_index: HashMap<Value, u32>,
     consts: Vec<Value>,
 
-    fns_index: HashMap<String, u32>,
+    fns_index: HashMap<String, u32>, // The functions of the module being built, by name.
     ifns: Vec<AsmFn>,
-    xfns: Vec<XFn>,
+    xfn_names: HashSet<String>, // The declared host functions.
+    packages: HashMap<String, Vec<String>>, // The host functions of the declared packages, by the path.
+    xfns_index: HashMap<String, u32>, // The host functions used, by name.
+    xfns: Vec<String>, // The host functions used, by the index of Value::XFn.
+
+    params: Vec<String>, // The parameters of the main function.
+
+    path: Option<PathBuf>, // The file of the module being built.
+    loader: Box<dyn ModuleLoader>,
+    sources: HashMap<PathBuf, Ast>, // The loaded modules, checked before being built.
+    modules: HashMap<PathBuf, HashMap<String, u32>>, // The exported functions of the built modules.
+    loading: Vec<PathBuf>, // The modules being loaded, the innermost is the last.
+    exports: Option<Vec<String>>, // The names exported by the module being built, if not the main one.
 }
 
 impl AsmBuilder {
@@ -25,26 +37,186 @@ impl AsmBuilder {
 
             fns_index: HashMap::new(),
             ifns: vec![],
+            xfn_names: HashSet::new(),
+            packages: HashMap::new(),
+            xfns_index: HashMap::new(),
             xfns: vec![],
+
+            params: vec![],
+
+            path: None,
+            loader: Box::new(FsLoader),
+            sources: HashMap::new(),
+            modules: HashMap::new(),
+            loading: vec![],
+            exports: None,
+        }
+    }
+
+    /// Set the file of the source, the imports are relative to it.
+    pub fn set_path(&mut self, path: PathBuf) {
+        self.path = Some(path);
+    }
+
+    /// Load the imported modules by the [ModuleLoader] instead of the [FsLoader].
+    pub fn set_loader<L: ModuleLoader + 'static>(&mut self, loader: L) {
+        self.loader = Box::new(loader);
+    }
+
+    /// Declare a parameter of the main function, the value of it is given by
+    /// [Runner::run_with](crate::bytecode::Runner::run_with) in order.
+    pub fn declare_param(&mut self, name: String) {
+        self.params.push(name);
+    }
+
+    /// Declare a host function, which is bound by the name when the
+    /// [Runner](crate::bytecode::Runner) is created by the [Engine](crate::engine::Engine).
+    /// Only the used ones are kept in the [Asm].
+    pub fn declare_xfn(&mut self, name: String) {
+        self.xfn_names.insert(name);
+    }
+
+    /// Declare a package of host functions, like `std/math` of `math/sqrt`,
+    /// which are not seen until `(import "std/math")`.
+    pub fn declare_package(&mut self, path: String, names: Vec<String>) {
+        self.packages.insert(path, names);
+    }
+
+    /// The const of the jisp or host function with the name.
+    fn fn_const(&mut self, name: &str) -> Option<u32> {
+        if let Some(index) = self.fns_index.get(name) {
+            return Some(*index);
+        }
+        match self.xfn_names.contains(name) {
+            true => Some(self.xfn_const(name)),
+            false => None,
+        }
+    }
+
+    /// The const of the host function, added when it is used first.
+    fn xfn_const(&mut self, name: &str) -> u32 {
+        if let Some(index) = self.xfns_index.get(name) {
+            return *index;
+        }
+        self.xfns.push(name.to_string());
+        self.consts.push(Value::XFn(self.xfns.len() as u32 - 1));
+        self.xfns_index.insert(name.to_string(), self.consts.len() as u32 - 1);
+        self.consts.len() as u32 - 1
+    }
+
+    /// Build the module loaded by [AsmBuilder::load_modules] once, and return the consts of the exported
+    /// functions by name. The module has its own names of functions, so the
+    /// ones not exported do not collide with the importing one.
+    fn load_module(&mut self, path: &str) -> Result<HashMap<String, u32>, String> {
+        let path = module::resolve(self.path.as_deref(), path);
+        if let Some(exports) = self.modules.get(&path) {
+            return Ok(exports.clone());
+        }
+        let ast = match self.sources.get(&path) {
+            Some(ast) => ast.clone(),
+            None => return Err(format!("module {} is not loaded", path.display())),
+        };
+
+        let outer_path = self.path.replace(path.clone());
+        let outer_fns = take(&mut self.fns_index);
+        let outer_exports = self.exports.replace(vec![]);
+
+        // Only the functions are kept, the module has no code to run.
+        let mut module_builder = AsmFnBuilder::new(self);
+        for s_exp in ast.s_exps() {
+            module_builder.build_value(s_exp)?;
+        }
+
+        let names = replace(&mut self.exports, outer_exports).unwrap_or_default();
+        let module_fns = replace(&mut self.fns_index, outer_fns);
+        self.path = outer_path;
+
+        let mut exports = HashMap::new();
+        for name in names {
+            match module_fns.get(&name) {
+                Some(index) => exports.insert(name, *index),
+                None => return Err(format!("module {} exports {} which is not a fn", path.display(), name)),
+            };
         }
+        self.modules.insert(path, exports.clone());
+        Ok(exports)
     }
 
-    pub fn register_xfn<F>(&mut self, name: String, xfn: F) where F: Fn(Vec<Value>) -> Value + 'static {
-        let xfn = XFn::new(name.clone(), xfn);
-        let xfn_value = Value::XFn(self.xfns.len() as u32);
+    /// Load the modules imported by the AST, and the ones imported by them,
+    /// and check them before anything is built.
+    fn load_modules(&mut self, from: Option<&Path>, ast: &Ast) -> Result<(), ImportError> {
+        let mut imports = vec![];
+        for s_exp in ast.s_exps() {
+            find_imports(s_exp, &mut imports);
+        }
+        for (path, names) in imports {
+            let unexported = |exports: &[String]| names.iter().find(|name| !exports.contains(name)).cloned();
+            if let Some(package) = self.packages.get(&path) {
+                let exports: Vec<String> = package.iter().filter_map(|full| full.rsplit('/').next()).map(String::from).collect();
+                if let Some(name) = unexported(&exports) {
+                    return Err(ImportError { path: PathBuf::from(path), msg: format!("does not export {}", name) });
+                }
+                continue;
+            }
+            if path.starts_with("std/") {
+                return Err(ImportError { path: PathBuf::from(path), msg: "no such package".to_string() });
+            }
+
+            let path = module::resolve(from, &path);
+            let error = |msg: String| ImportError { path: path.clone(), msg };
+            if let Some(module) = self.sources.get(&path) {
+                if let Some(name) = unexported(&module_exports(module).map_err(error)?) {
+                    return Err(error(format!("does not export {}", name)));
+                }
+                continue;
+            }
+            if self.loading.contains(&path) {
+                let cycle: Vec<String> = self.loading.iter().chain([&path]).map(|path| path.display().to_string()).collect();
+                return Err(error(format!("import cycle: {}", cycle.join(" -> "))));
+            }
+            let source = self.loader.load(&path).map_err(error)?;
+            let module = AstBuilder::new(TokenStream::new(&source)).try_build().map_err(error)?;
+            if let Some(name) = unexported(&module_exports(&module).map_err(error)?) {
+                return Err(error(format!("does not export {}", name)));
+            }
+
+            self.loading.push(path.clone());
+            let result = self.load_modules(Some(&path), &module);
+            self.loading.pop();
+            result?;
+            self.sources.insert(path, module);
+        }
+        Ok(())
+    }
+
+    /// Build the [Asm], or return the error of loading the imported modules
+    /// or of the invalid AST.
+    pub fn try_build(mut self) -> Result<Asm, AsmError> {
+        let ast = self.ast.clone();
+        let path = self.path.clone();
+        self.load_modules(path.as_deref(), &ast)?;
+        self.build_loaded().map_err(AsmError::Invalid)
+    }
 
-        self.xfns.push(xfn);
-        self.consts.push(xfn_value.clone());
-        self.consts_index.insert(xfn_value, self.consts.len() as u32 - 1);
-        self.fns_index.insert(name, self.consts.len() as u32 - 1);
+    /// Build the [Asm], panic if it can not be built.
+    pub fn build(self) -> Asm {
+        match self.try_build() {
+            Ok(asm) => asm,
+            Err(err) => panic!("{}", err),
+        }
     }
 
-    pub fn build(mut self) -> Asm {
+    fn build_loaded(mut self) -> Result<Asm, String> {
         let mut asm = Asm::new();
 
         let ast = self.ast.clone();
-        let main_fn = AsmFnBuilder::new(&mut self)
-            .build(ast);
+        let params = self.params.clone();
+        let mut main_fn_builder = AsmFnBuilder::new(&mut self);
+        for param in params {
+            let index = main_fn_builder.new_local();
+            main_fn_builder.locals_index.insert(param, index);
+        }
+        let main_fn = main_fn_builder.build(ast)?;
 
         asm.consts = self.consts;
         asm.xfns = self.xfns;
@@ -52,7 +224,75 @@ impl AsmBuilder {
         for func in self.ifns {
             asm.push_fn(func);
         }
-        asm
+        Ok(asm)
+    }
+}
+
+/// The paths and the names of `(import "path" [name ...])`, the names are
+/// empty if all are imported.
+fn find_imports(s_exp: &SExp, imports: &mut Vec<(String, Vec<String>)>) {
+    match s_exp {
+        SExp::List(lst, _) => {
+            if let [SExp::Sym(sym), SExp::Str(path), rest @ ..] = &lst[..] {
+                if sym == "import" {
+                    let names = match rest {
+                        [SExp::Array(names)] => names.iter().filter_map(|name| match name {
+                            SExp::Sym(name) => Some(name.clone()),
+                            _ => None,
+                        }).collect(),
+                        _ => vec![],
+                    };
+                    imports.push((path.clone(), names));
+                }
+            }
+            lst.iter().for_each(|s_exp| find_imports(s_exp, imports));
+        }
+        SExp::Array(arr) => arr.iter().for_each(|s_exp| find_imports(s_exp, imports)),
+        SExp::Map(map) => map.iter().for_each(|(key, val)| {
+            find_imports(key, imports);
+            find_imports(val, imports);
+        }),
+        _ => (),
+    }
+}
+
+/// The names exported by the module, which should only have fn, import and
+/// export, and export only the fns.
+fn module_exports(module: &Ast) -> Result<Vec<String>, String> {
+    let mut fns = HashSet::new();
+    let mut exports = vec![];
+    for s_exp in module.s_exps() {
+        match s_exp {
+            SExp::List(lst, _) if lst.first() == Some(&SExp::Sym("fn".to_string())) => {
+                if let Some(SExp::Sym(name)) = lst.get(1) {
+                    fns.insert(name);
+                }
+            }
+            SExp::List(lst, _) if lst.first() == Some(&SExp::Sym("export".to_string())) => {
+                for name in &lst[1..] {
+                    match name {
+                        SExp::Sym(name) => exports.push(name.clone()),
+                        _ => return Err("`export` wants names to be SYM".to_string()),
+                    }
+                }
+            }
+            SExp::List(lst, _) if lst.first() == Some(&SExp::Sym("import".to_string())) => (),
+            _ => return Err("should only have fn, import and export".to_string()),
+        }
+    }
+    match exports.iter().find(|name| !fns.contains(name)) {
+        Some(name) => Err(format!("exports {} which is not a fn", name)),
+        None => Ok(exports),
+    }
+}
+
+/// The value of the literal symbol, which can not be a name.
+fn literal(sym: &str) -> Option<Value> {
+    match sym {
+        "null" => Some(Value::Null),
+        "true" => Some(Value::Bool(true)),
+        "false" => Some(Value::Bool(false)),
+        _ => None,
     }
 }
 
@@ -61,10 +301,40 @@ pub struct AsmFnBuilder<'a> {
 
     locals_index: HashMap<String, u32>,
     label_cnt: u32,
+    height: i32, // The height of the stack, known at compile time.
+    loops: Vec<AsmLoop>, // The loops we are in, the innermost is the last.
+    tries: u32, // The number of `try` we are in.
 
     func: AsmFn,
 }
 
+/// The loop being built, to know where `break`, `continue` and `recur` go.
+struct AsmLoop {
+    kind: AsmLoopKind,
+    continue_label: AsmLabel, // Where to go for `continue` and `recur`.
+    break_label: AsmLabel, // Where to go for `break`, with the value pushed.
+    height: i32, // The height of the stack before the loop.
+    bindings: Vec<u32>, // The locals rebound by `recur`.
+    tries: u32, // The number of `try` we were in before the loop.
+}
+
+#[derive(PartialEq, Eq)]
+enum AsmLoopKind {
+    While,
+    For,
+    Loop,
+}
+
+/// How the names in a pattern are bound.
+enum AsmBind {
+    /// Assign to the local with the same name, or a new local, like `let`.
+    Assign,
+
+    /// Hide the locals with the same name, and remember the old ones to
+    /// restore them by [AsmFnBuilder::unbind_local].
+    Scoped(Vec<(String, Option<u32>)>),
+}
+
 impl<'a> AsmFnBuilder<'a> {
     fn new(ab: &'a mut AsmBuilder) -> Self {
         Self {
@@ -72,20 +342,258 @@ impl<'a> AsmFnBuilder<'a> {
 
             locals_index: HashMap::new(),
             label_cnt: 1,
+            height: 0,
+            loops: vec![],
+            tries: 0,
 
             func: AsmFn::new(0, vec![]),
         }
     }
 
-    fn build(mut self, ast: Ast) -> AsmFn {
+    fn build(mut self, ast: Ast) -> Result<AsmFn, String> {
         for s_exp in ast.s_exps() {
-            self.build_value(s_exp);
+            self.build_value(s_exp)?;
+        }
+        self.push_statement(AsmStatement::Ret);
+        Ok(self.func)
+    }
+
+    /// Push a statement, and track the height of the stack.
+    fn push_statement(&mut self, statement: AsmStatement) {
+        type AS = AsmStatement;
+        self.height += match &statement {
+            AS::Label { label: _ } | AS::Jump { label: _ } | AS::Dbg { index: _ } => 0,
+            AS::TryBegin { label: _ } | AS::TryEnd => 0,
+            AS::PushI64 { val: _ } | AS::PushConst { index: _ } | AS::Load { index: _ } => 1,
+            AS::Ret | AS::Pop | AS::Store { index: _ } | AS::JumpFalse { label: _ } => -1,
+            AS::Add | AS::Sub | AS::Mul | AS::Div |
+            AS::Eq | AS::Ne | AS::Lt | AS::Le | AS::Gt | AS::Ge => -1,
+            AS::Call { args } => -(*args as i32),
+            AS::MakeArray { len } => 1 - *len as i32,
+            AS::MakeMap { len } => 1 - 2 * *len as i32,
+        };
+        self.func.push_statement(statement);
+    }
+
+    /// The index of the const, the const is added if it is new.
+    fn const_index(&mut self, ac: Value) -> u32 {
+        match self.ab.consts_index.get(&ac) {
+            None => {
+                self.ab.consts.push(ac.clone());
+                let idx = self.ab.consts.len() as u32 - 1;
+                self.ab.consts_index.insert(ac, idx);
+                idx
+            }
+            Some(idx) => *idx,
+        }
+    }
+
+    /// Push the const to the stack, the const is added if it is new.
+    fn push_const(&mut self, ac: Value) {
+        let index = self.const_index(ac);
+        self.push_statement(AsmStatement::PushConst { index });
+    }
+
+    /// Push the builtin function to the stack.
+    fn push_builtin(&mut self, name: &str) -> Result<(), String> {
+        let index = match lookup_builtin(name) {
+            Some(index) => index,
+            None => return Err(format!("no builtin function `{}`", name)),
+        };
+        self.push_const(Value::Builtin(index));
+        Ok(())
+    }
+
+    /// Pop the values until the stack is in the height.
+    fn pop_to(&mut self, height: i32) {
+        while self.height > height {
+            self.push_statement(AsmStatement::Pop);
+        }
+    }
+
+    fn new_label(&mut self) -> AsmLabel {
+        let label = AsmLabel::new(format!(".L{}", self.label_cnt));
+        self.label_cnt += 1;
+        label
+    }
+
+    /// Add a local variable without name.
+    fn new_local(&mut self) -> u32 {
+        let index = self.func.locals;
+        self.func.locals += 1;
+        index
+    }
+
+    /// Add a local variable which hides the old one with the same name until
+    /// [AsmFnBuilder::unbind_local] is called. Return the old one.
+    fn bind_local(&mut self, name: &SExp) -> Result<(String, u32, Option<u32>), String> {
+        let name = match name {
+            SExp::Sym(sym) => sym.clone(),
+            _ => return Err("binding name should be a SYM".to_string()),
+        };
+        let index = self.new_local();
+        let old = self.locals_index.insert(name.clone(), index);
+        Ok((name, index, old))
+    }
+
+    fn unbind_local(&mut self, name: String, old: Option<u32>) {
+        match old {
+            Some(index) => self.locals_index.insert(name, index),
+            None => self.locals_index.remove(&name),
+        };
+    }
+
+    /// Get the local variable with the name, or add it if there is not.
+    fn assign_local(&mut self, name: &str) -> u32 {
+        match self.locals_index.get(name) {
+            Some(idx) => *idx,
+            None => {
+                let ret = self.new_local();
+                self.locals_index.insert(name.to_string(), ret);
+                ret
+            }
+        }
+    }
+
+    /// Build the pattern against the value in the local (by index), and bind
+    /// the names in the pattern.
+    ///
+    /// Jump to the fail label if the value does not match. If there is no
+    /// fail label, the pattern should always match.
+    fn build_pattern(&mut self, pattern: &SExp, index: u32, fail_label: Option<&AsmLabel>, bind: &mut AsmBind) -> Result<(), String> {
+        match pattern {
+            SExp::Sym(name) if name == "_" => (),
+            SExp::Sym(name) if literal(name).is_none() => {
+                let target = match bind {
+                    AsmBind::Assign => self.assign_local(name),
+                    AsmBind::Scoped(olds) => {
+                        let (name, target, old) = self.bind_local(pattern)?;
+                        olds.push((name, old));
+                        target
+                    }
+                };
+                self.push_statement(AsmStatement::Load { index });
+                self.push_statement(AsmStatement::Store { index: target });
+            }
+            SExp::I64(_) | SExp::F64(_) | SExp::Str(_) | SExp::Sym(_) => {
+                let fail_label = match fail_label {
+                    Some(label) => label.clone(),
+                    None => return Err("the pattern may not match, use `match`".to_string()),
+                };
+                self.push_statement(AsmStatement::Load { index });
+                self.build_value(pattern)?;
+                self.push_statement(AsmStatement::Eq);
+                self.push_statement(AsmStatement::JumpFalse { label: fail_label });
+            }
+            SExp::Array(items) => {
+                // [pattern ... & rest]
+                let (items, rest) = match items.iter().position(|i| i == &SExp::Sym("&".to_string())) {
+                    Some(pos) if pos + 2 == items.len() => (&items[..pos], Some(&items[pos + 1])),
+                    Some(_) => return Err("`&` wants one pattern after it".to_string()),
+                    None => (&items[..], None),
+                };
+
+                if let Some(fail_label) = fail_label {
+                    self.push_builtin("array?")?;
+                    self.push_statement(AsmStatement::Load { index });
+                    self.push_statement(AsmStatement::Call { args: 1 });
+                    self.push_statement(AsmStatement::JumpFalse { label: fail_label.clone() });
+
+                    self.push_builtin("len")?;
+                    self.push_statement(AsmStatement::Load { index });
+                    self.push_statement(AsmStatement::Call { args: 1 });
+                    self.push_statement(AsmStatement::PushI64 { val: items.len() as i64 });
+                    self.push_statement(match rest {
+                        Some(_) => AsmStatement::Ge,
+                        None => AsmStatement::Eq,
+                    });
+                    self.push_statement(AsmStatement::JumpFalse { label: fail_label.clone() });
+                }
+
+                for (i, item) in items.iter().enumerate() {
+                    if item == &SExp::Sym("_".to_string()) {
+                        continue;
+                    }
+                    self.push_builtin("get")?;
+                    self.push_statement(AsmStatement::Load { index });
+                    self.push_statement(AsmStatement::PushI64 { val: i as i64 });
+                    self.push_statement(AsmStatement::Call { args: 2 });
+                    let item_index = self.new_local();
+                    self.push_statement(AsmStatement::Store { index: item_index });
+                    self.build_pattern(item, item_index, fail_label, bind)?;
+                }
+
+                if let Some(rest) = rest {
+                    self.push_builtin("slice")?;
+                    self.push_statement(AsmStatement::Load { index });
+                    self.push_statement(AsmStatement::PushI64 { val: items.len() as i64 });
+                    self.push_statement(AsmStatement::Call { args: 2 });
+                    let rest_index = self.new_local();
+                    self.push_statement(AsmStatement::Store { index: rest_index });
+                    self.build_pattern(rest, rest_index, fail_label, bind)?;
+                }
+            }
+            SExp::Map(entries) => {
+                // {key pattern ...}
+                if let Some(fail_label) = fail_label {
+                    self.push_builtin("map?")?;
+                    self.push_statement(AsmStatement::Load { index });
+                    self.push_statement(AsmStatement::Call { args: 1 });
+                    self.push_statement(AsmStatement::JumpFalse { label: fail_label.clone() });
+                }
+
+                for (key, item) in entries {
+                    if !matches!(key, SExp::Str(_)) {
+                        return Err("keys of the map pattern should be STR".to_string());
+                    }
+                    if let Some(fail_label) = fail_label {
+                        self.push_builtin("has?")?;
+                        self.push_statement(AsmStatement::Load { index });
+                        self.build_value(key)?;
+                        self.push_statement(AsmStatement::Call { args: 2 });
+                        self.push_statement(AsmStatement::JumpFalse { label: fail_label.clone() });
+                    }
+                    if item == &SExp::Sym("_".to_string()) {
+                        continue;
+                    }
+                    self.push_builtin("get")?;
+                    self.push_statement(AsmStatement::Load { index });
+                    self.build_value(key)?;
+                    self.push_statement(AsmStatement::Call { args: 2 });
+                    let item_index = self.new_local();
+                    self.push_statement(AsmStatement::Store { index: item_index });
+                    self.build_pattern(item, item_index, fail_label, bind)?;
+                }
+            }
+            _ => return Err("unsupported pattern".to_string()),
+        }
+        Ok(())
+    }
+
+    /// Drop the handlers of the `try` we are leaving by jumping out, until
+    /// there are `tries` left.
+    fn end_tries(&mut self, tries: u32) {
+        for _ in tries..self.tries {
+            self.push_statement(AsmStatement::TryEnd);
+        }
+    }
+
+    /// Build the values one by one, and keep the last one only.
+    fn build_body(&mut self, body: &[SExp]) -> Result<(), String> {
+        let height = self.height;
+        if body.is_empty() {
+            self.push_const(Value::Null);
+        }
+        for (i, val) in body.iter().enumerate() {
+            if i != 0 {
+                self.pop_to(height);
+            }
+            self.build_value(val)?;
         }
-        self.func.push_statement(AsmStatement::Ret);
-        self.func
+        Ok(())
     }
 
-    fn build_list(&mut self, lst: &Vec<SExp>) {
+    fn build_list(&mut self, lst: &[SExp], pos: TokenPos) -> Result<(), String> {
         enum Op {
             Add, Sub, Mul, Div,
             Eq, Ne, Lt, Le, Gt, Ge,
@@ -93,168 +601,275 @@ impl<'a> AsmFnBuilder<'a> {
             If,
             Fn, Call,
             Do,
+            While, For, Loop, Recur, Break, Continue,
+            Cond, Case, When, Unless,
+            Match,
+            Try,
+            Method,
+            Import, Export,
         }
 
-        let op = match &lst[0] {
-            SExp::Sym(sym) if sym == &"+".to_string() => Op::Add,
-            SExp::Sym(sym) if sym == &"-".to_string() => Op::Sub,
-            SExp::Sym(sym) if sym == &"*".to_string() => Op::Mul,
-            SExp::Sym(sym) if sym == &"/".to_string() => Op::Div,
-            SExp::Sym(sym) if sym == &"==".to_string() => Op::Eq,
-            SExp::Sym(sym) if sym == &"!=".to_string() => Op::Ne,
-            SExp::Sym(sym) if sym == &"<".to_string() => Op::Lt,
-            SExp::Sym(sym) if sym == &"<=".to_string() => Op::Le,
-            SExp::Sym(sym) if sym == &">".to_string() => Op::Gt,
-            SExp::Sym(sym) if sym == &">=".to_string() => Op::Ge,
-            SExp::Sym(sym) if sym == &"let".to_string() => Op::Let,
-            SExp::Sym(sym) if sym == &"if".to_string() => Op::If,
-            SExp::Sym(sym) if sym == &"fn".to_string() => Op::Fn,
-            SExp::Sym(sym) if sym == &"do".to_string() => Op::Do,
+        let op = match lst.first() {
+            None => return Err("`()` is not a call".to_string()),
+            Some(SExp::Sym(sym)) if sym == &"+".to_string() => Op::Add,
+            Some(SExp::Sym(sym)) if sym == &"-".to_string() => Op::Sub,
+            Some(SExp::Sym(sym)) if sym == &"*".to_string() => Op::Mul,
+            Some(SExp::Sym(sym)) if sym == &"/".to_string() => Op::Div,
+            Some(SExp::Sym(sym)) if sym == &"==".to_string() => Op::Eq,
+            Some(SExp::Sym(sym)) if sym == &"!=".to_string() => Op::Ne,
+            Some(SExp::Sym(sym)) if sym == &"<".to_string() => Op::Lt,
+            Some(SExp::Sym(sym)) if sym == &"<=".to_string() => Op::Le,
+            Some(SExp::Sym(sym)) if sym == &">".to_string() => Op::Gt,
+            Some(SExp::Sym(sym)) if sym == &">=".to_string() => Op::Ge,
+            Some(SExp::Sym(sym)) if sym == &"let".to_string() => Op::Let,
+            Some(SExp::Sym(sym)) if sym == &"if".to_string() => Op::If,
+            Some(SExp::Sym(sym)) if sym == &"fn".to_string() => Op::Fn,
+            Some(SExp::Sym(sym)) if sym == &"do".to_string() => Op::Do,
+            Some(SExp::Sym(sym)) if sym == &"while".to_string() => Op::While,
+            Some(SExp::Sym(sym)) if sym == &"for".to_string() => Op::For,
+            Some(SExp::Sym(sym)) if sym == &"loop".to_string() => Op::Loop,
+            Some(SExp::Sym(sym)) if sym == &"recur".to_string() => Op::Recur,
+            Some(SExp::Sym(sym)) if sym == &"break".to_string() => Op::Break,
+            Some(SExp::Sym(sym)) if sym == &"continue".to_string() => Op::Continue,
+            Some(SExp::Sym(sym)) if sym == &"cond".to_string() => Op::Cond,
+            Some(SExp::Sym(sym)) if sym == &"case".to_string() => Op::Case,
+            Some(SExp::Sym(sym)) if sym == &"when".to_string() => Op::When,
+            Some(SExp::Sym(sym)) if sym == &"unless".to_string() => Op::Unless,
+            Some(SExp::Sym(sym)) if sym == &"match".to_string() => Op::Match,
+            Some(SExp::Sym(sym)) if sym == &"try".to_string() => Op::Try,
+            Some(SExp::Sym(sym)) if sym == &"import".to_string() => Op::Import,
+            Some(SExp::Sym(sym)) if sym == &"export".to_string() => Op::Export,
+            Some(SExp::Sym(sym)) if sym.len() > 1 && sym.starts_with('.') => Op::Method,
             _ => Op::Call,
         };
 
+        // The forms take their values by index, so they should have enough.
+        let want = match op {
+            Op::Add | Op::Sub | Op::Mul | Op::Div => 1,
+            Op::While | Op::For | Op::Loop | Op::When | Op::Unless => 1,
+            Op::Eq | Op::Ne | Op::Lt | Op::Le | Op::Gt | Op::Ge => 2,
+            Op::Let | Op::If | Op::Fn => 2,
+            _ => 0,
+        };
+        if let SExp::Sym(sym) = &lst[0] {
+            if lst.len() <= want {
+                return Err(format!("`{}` wants at least {} values", sym, want));
+            }
+        }
+
         match op {
             Op::Add | Op::Sub | Op::Mul | Op::Div => {
-                self.build_value(&lst[1]);
-                for i in 2..lst.len() {
-                    self.build_value(&lst[i]);
+                self.build_value(&lst[1])?;
+                for val in &lst[2..] {
+                    self.build_value(val)?;
 
                     match op {
-                        Op::Add => self.func.push_statement(AsmStatement::Add),
-                        Op::Sub => self.func.push_statement(AsmStatement::Sub),
-                        Op::Mul => self.func.push_statement(AsmStatement::Mul),
-                        Op::Div => self.func.push_statement(AsmStatement::Div),
+                        Op::Add => self.push_statement(AsmStatement::Add),
+                        Op::Sub => self.push_statement(AsmStatement::Sub),
+                        Op::Mul => self.push_statement(AsmStatement::Mul),
+                        Op::Div => self.push_statement(AsmStatement::Div),
                         _ => panic!("unexpected op"),
                     }
                 }
             },
             Op::Eq | Op::Ne | Op::Lt | Op::Le | Op::Gt | Op::Ge => {
-                for i in 1..=2 {
-                    self.build_value(&lst[i]);
+                for val in &lst[1..=2] {
+                    self.build_value(val)?;
                 }
 
                 match op {
-                    Op::Eq => self.func.push_statement(AsmStatement::Eq),
-                    Op::Ne => self.func.push_statement(AsmStatement::Ne),
-                    Op::Lt => self.func.push_statement(AsmStatement::Lt),
-                    Op::Le => self.func.push_statement(AsmStatement::Le),
-                    Op::Gt => self.func.push_statement(AsmStatement::Gt),
-                    Op::Ge => self.func.push_statement(AsmStatement::Ge),
+                    Op::Eq => self.push_statement(AsmStatement::Eq),
+                    Op::Ne => self.push_statement(AsmStatement::Ne),
+                    Op::Lt => self.push_statement(AsmStatement::Lt),
+                    Op::Le => self.push_statement(AsmStatement::Le),
+                    Op::Gt => self.push_statement(AsmStatement::Gt),
+                    Op::Ge => self.push_statement(AsmStatement::Ge),
                     _ => panic!("unexpected op"),
                 }
             },
             Op::Let => {
-                let index = {
-                    let name = match &lst[1] {
-                        SExp::Sym(sym) => sym.clone(),
-                        _ => panic!("unexpected token"),
-                    };
-                    match self.locals_index.get(&name) {
-                        Some(idx) => *idx,
-                        None => {
-                            let ret = self.func.locals;
-                            self.locals_index.insert(name, ret);
-                            self.func.locals += 1;
-                            ret
-                        }
+                match &lst[1] {
+                    SExp::Sym(name) if name != "_" => {
+                        let index = self.assign_local(name);
+                        self.build_value(&lst[2])?;
+                        self.push_statement(AsmStatement::Store { index });
                     }
-                };
-                self.build_value(&lst[2]);
-                self.func.push_statement(AsmStatement::Store { index });
-                let ac = Value::Null;
-                let idx = match self.ab.consts_index.get(&ac) {
-                    None => {
-                        self.ab.consts.push(ac.clone());
-                        let idx = self.ab.consts.len() as u32 - 1;
-                        self.ab.consts_index.insert(ac.clone(), idx);
-                        idx
+                    pattern => {
+                        self.build_value(&lst[2])?;
+                        let index = self.new_local();
+                        self.push_statement(AsmStatement::Store { index });
+                        self.build_pattern(pattern, index, None, &mut AsmBind::Assign)?;
                     }
-                    Some(idx) => *idx,
-                };
-                self.func.push_statement(AsmStatement::PushConst { index: idx });
+                }
+                self.push_const(Value::Null);
             },
             Op::If => {
-                self.build_value(&lst[1]);
+                self.build_value(&lst[1])?;
 
-                let fpath_label = AsmLabel::new(format!(".L{}", self.label_cnt));
-                self.label_cnt += 1;
-                let end_label = AsmLabel::new(format!(".L{}", self.label_cnt));
-                self.label_cnt += 1;
-                self.func.push_statement(AsmStatement::JumpFalse { label: fpath_label.clone() });
+                let fpath_label = self.new_label();
+                let end_label = self.new_label();
+                self.push_statement(AsmStatement::JumpFalse { label: fpath_label.clone() });
+                let height = self.height;
 
                 // True path.
-                self.build_value(&lst[2]);
-                self.func.push_statement(AsmStatement::Jump { label: end_label.clone() });
+                self.build_value(&lst[2])?;
+                self.push_statement(AsmStatement::Jump { label: end_label.clone() });
 
                 // False path.
-                self.func.push_statement(AsmStatement::Label { label: fpath_label });
+                self.height = height;
+                self.push_statement(AsmStatement::Label { label: fpath_label });
                 if lst.len() >= 4 {
-                    self.build_value(&lst[3]);
+                    self.build_value(&lst[3])?;
                 } else {
-                    let ac = Value::Null;
-                    let idx = match self.ab.consts_index.get(&ac) {
-                        None => {
-                            self.ab.consts.push(ac.clone());
-                            let idx = self.ab.consts.len() as u32 - 1;
-                            self.ab.consts_index.insert(ac.clone(), idx);
-                            idx
-                        }
-                        Some(idx) => *idx,
-                    };
-                    self.func.push_statement(AsmStatement::PushConst { index: idx });
+                    self.push_const(Value::Null);
                 }
 
-                self.func.push_statement(AsmStatement::Label { label: end_label });
+                self.push_statement(AsmStatement::Label { label: end_label });
             },
             Op::Fn => {
                 let name = match &lst[1] {
                     SExp::Sym(name) => name.clone(),
-                    _ => panic!("runtime error"),
+                    _ => return Err("`fn` wants a name to be SYM".to_string()),
                 };
                 self.ab.consts.push(Value::IFn(self.ab.ifns.len() as u32 + 1));
-                self.ab.fns_index.insert(name, self.ab.consts.len() as u32 - 1);
+                self.ab.fns_index.insert(name.clone(), self.ab.consts.len() as u32 - 1);
 
                 let mut asm_fn_builder = AsmFnBuilder::new(self.ab);
+                asm_fn_builder.func.name = name;
                 match &lst[2] {
                     SExp::Array(arr) => {
-                        let mut idx = 0;
-                        for ele in arr {
-                            let name = match ele {
-                                SExp::Sym(name) => name.clone(),
-                                _ => panic!("argument should be a SYM"),
+                        asm_fn_builder.func.locals = arr.len() as u32;
+                        for (idx, ele) in arr.iter().enumerate() {
+                            match ele {
+                                SExp::Sym(name) if name == "_" => (),
+                                SExp::Sym(name) => {
+                                    asm_fn_builder.locals_index.insert(name.clone(), idx as u32);
+                                }
+                                pattern => {
+                                    asm_fn_builder.build_pattern(pattern, idx as u32, None, &mut AsmBind::Assign)?;
+                                }
                             };
-                            asm_fn_builder.locals_index.insert(name, idx);
-                            idx += 1;
                         }
-                        asm_fn_builder.func.locals = idx;
                     },
-                    _ => panic!("arguments should be an ARRAY"),
+                    _ => return Err("arguments should be an ARRAY".to_string()),
                 }
                 let mut sub_ast = Ast::new();
                 for s_exp in &lst[3..] {
                     sub_ast.push_s_exp(s_exp.clone());
                 }
-                let func = asm_fn_builder.build(sub_ast);
+                let func = asm_fn_builder.build(sub_ast)?;
                 self.ab.ifns.push(func);
             },
             Op::Call => {
                 let name = match &lst[0] {
                     SExp::Sym(name) => name.clone(),
-                    _ => panic!("runtime error"),
+                    _ => return Err("the function of a call should be a SYM".to_string()),
                 };
-                let fn_index = self.ab.fns_index.get(&name);
+                let fn_index = self.ab.fn_const(&name);
+                let is_builtin = fn_index.is_none() && !self.locals_index.contains_key(&name);
+                // (dbg value) of the builtin writes the location of the call.
+                if is_builtin && name == "dbg" && lst.len() == 2 {
+                    self.build_value(&lst[1])?;
+                    let index = self.const_index(Value::Str(format!("{}:{}", pos.lineno, pos.offset)));
+                    self.push_statement(AsmStatement::Dbg { index });
+                    return Ok(());
+                }
                 if let Some(fn_index) = fn_index {
-                    self.func.push_statement(AsmStatement::PushConst {
-                        index: *fn_index,
+                    self.push_statement(AsmStatement::PushConst {
+                        index: fn_index,
                     });
                 } else {
-                    self.build_value(&lst[0]);
+                    self.build_value(&lst[0])?;
                 }
 
-                for val in &lst[1..] {
-                    self.build_value(val);
+                for (i, val) in lst[1..].iter().enumerate() {
+                    match val {
+                        // Compile the literal pattern once, instead of on each call.
+                        SExp::Str(pattern) if is_builtin && i == 0 && (name == "re" || name.starts_with("re/")) => {
+                            match Regex::new(pattern) {
+                                Ok(re) => self.push_const(Value::Regex(re)),
+                                Err(err) => return Err(format!("invalid regex {:?}: {}", pattern, err)),
+                            }
+                        }
+                        val => self.build_value(val)?,
+                    }
+                }
+                self.push_statement(AsmStatement::Call { args: lst.len() as u32 - 1 });
+            }
+            Op::Import => {
+                // (import "path" [name ...]), or all the exported names
+                let path = match lst.get(1) {
+                    Some(SExp::Str(path)) => path,
+                    _ => return Err("`import` wants a path".to_string()),
+                };
+                let names: Option<Vec<String>> = match lst.get(2) {
+                    Some(SExp::Array(names)) if lst.len() == 3 => Some(names.iter().map(|name| match name {
+                        SExp::Sym(name) => Ok(name.clone()),
+                        _ => Err("`import` wants names to be SYM".to_string()),
+                    }).collect::<Result<_, _>>()?),
+                    None => None,
+                    _ => return Err("`import` wants a path and an array of names".to_string()),
+                };
+
+                if let Some(package) = self.ab.packages.get(path).cloned() {
+                    // The names are imported as is, or all with the namespace like `math/sqrt`.
+                    let imports: Vec<(String, String)> = match names {
+                        Some(names) => names.into_iter().map(|name| {
+                            match package.iter().find(|full| full.rsplit('/').next() == Some(name.as_str())) {
+                                Some(full) => Ok((name, full.clone())),
+                                None => Err(format!("package {} does not export {}", path, name)),
+                            }
+                        }).collect::<Result<_, _>>()?,
+                        None => package.into_iter().map(|full| (full.clone(), full)).collect(),
+                    };
+                    for (name, full) in imports {
+                        let index = self.ab.xfn_const(&full);
+                        self.ab.fns_index.insert(name, index);
+                    }
+                } else if path.starts_with("std/") {
+                    return Err(format!("no package {}", path));
+                } else {
+                    let exports = self.ab.load_module(path)?;
+                    let names = names.unwrap_or_else(|| exports.keys().cloned().collect());
+                    for name in names {
+                        match exports.get(&name) {
+                            Some(index) => self.ab.fns_index.insert(name, *index),
+                            None => return Err(format!("module {} does not export {}", path, name)),
+                        };
+                    }
+                }
+                self.push_const(Value::Null);
+            }
+            Op::Export => {
+                // (export name ...)
+                let exports = match &mut self.ab.exports {
+                    Some(exports) => exports,
+                    None => return Err("`export` should be in an imported module".to_string()),
+                };
+                for name in &lst[1..] {
+                    match name {
+                        SExp::Sym(name) => exports.push(name.clone()),
+                        _ => return Err("`export` wants names to be SYM".to_string()),
+                    }
                 }
-                self.func.push_statement(AsmStatement::Call { args: lst.len() as u32 - 1 });
+                self.push_const(Value::Null);
+            }
+            Op::Method => {
+                // (.name obj args ...) as (method obj "name" args ...)
+                let name = match &lst[0] {
+                    SExp::Sym(name) => &name[1..],
+                    _ => panic!("unexpected op"),
+                };
+                if lst.len() < 2 {
+                    return Err(format!("`.{}` wants an object", name));
+                }
+                self.push_builtin("method")?;
+                self.build_value(&lst[1])?;
+                self.push_const(Value::Str(name.to_string()));
+                for val in &lst[2..] {
+                    self.build_value(val)?;
+                }
+                self.push_statement(AsmStatement::Call { args: lst.len() as u32 });
             }
             Op::Do => {
                 let mut is_first = true;
@@ -262,82 +877,481 @@ impl<'a> AsmFnBuilder<'a> {
                     if is_first {
                         is_first = false
                     } else {
-                        self.func.push_statement(AsmStatement::Pop);
+                        self.push_statement(AsmStatement::Pop);
                     }
-                    self.build_value(val);
+                    self.build_value(val)?;
                 }
             }
-        }
-    }
+            Op::While => {
+                // (while cond body...)
+                let start_label = self.new_label();
+                let exit_label = self.new_label();
+                let end_label = self.new_label();
+                let height = self.height;
 
-    fn build_value(&mut self, val: &SExp) {
-        match val {
-            SExp::I64(first) => {
-                self.func.push_statement(AsmStatement::PushI64 { val: *first });
+                self.push_statement(AsmStatement::Label { label: start_label.clone() });
+                self.build_value(&lst[1])?;
+                self.push_statement(AsmStatement::JumpFalse { label: exit_label.clone() });
+
+                self.loops.push(AsmLoop {
+                    kind: AsmLoopKind::While,
+                    continue_label: start_label.clone(),
+                    break_label: end_label.clone(),
+                    height,
+                    bindings: vec![],
+                    tries: self.tries,
+                });
+                self.build_body(&lst[2..])?;
+                self.loops.pop();
+                self.pop_to(height);
+                self.push_statement(AsmStatement::Jump { label: start_label });
+
+                self.push_statement(AsmStatement::Label { label: exit_label });
+                self.push_const(Value::Null);
+                self.push_statement(AsmStatement::Label { label: end_label });
+            }
+            Op::For => {
+                // (for [name (range start end step)] body...)
+                let (name, range) = match &lst[1] {
+                    SExp::Array(binding) if binding.len() == 2 => (&binding[0], &binding[1]),
+                    _ => return Err("`for` wants a binding like [name (range start end)]".to_string()),
+                };
+                let range = match range {
+                    SExp::List(range, _) if range.len() >= 3 && range.len() <= 4
+                        && range[0] == SExp::Sym("range".to_string()) => range,
+                    _ => return Err("`for` only iterates over (range start end [step])".to_string()),
+                };
+
+                let cond_label = self.new_label();
+                let step_label = self.new_label();
+                let exit_label = self.new_label();
+                let end_label = self.new_label();
+                let height = self.height;
+
+                // Evaluate the range before the name is bound.
+                enum Step { Const(i64), Local(u32) }
+                self.build_value(&range[1])?;
+                self.build_value(&range[2])?;
+                let step = match range.get(3) {
+                    None => Step::Const(1),
+                    Some(SExp::I64(step)) => Step::Const(*step),
+                    Some(step) => {
+                        self.build_value(step)?;
+                        let index = self.new_local();
+                        self.push_statement(AsmStatement::Store { index });
+                        Step::Local(index)
+                    }
+                };
+                let end_local = self.new_local();
+                self.push_statement(AsmStatement::Store { index: end_local });
+                let (name, index, old) = self.bind_local(name)?;
+                self.push_statement(AsmStatement::Store { index });
+
+                // Check if the name is still in the range.
+                self.push_statement(AsmStatement::Label { label: cond_label.clone() });
+                match step {
+                    Step::Const(step) => {
+                        self.push_statement(AsmStatement::Load { index });
+                        self.push_statement(AsmStatement::Load { index: end_local });
+                        self.push_statement(if step >= 0 { AsmStatement::Lt } else { AsmStatement::Gt });
+                    }
+                    Step::Local(step_local) => {
+                        let down_label = self.new_label();
+                        let checked_label = self.new_label();
+                        self.push_statement(AsmStatement::Load { index: step_local });
+                        self.push_statement(AsmStatement::PushI64 { val: 0 });
+                        self.push_statement(AsmStatement::Ge);
+                        self.push_statement(AsmStatement::JumpFalse { label: down_label.clone() });
+                        self.push_statement(AsmStatement::Load { index });
+                        self.push_statement(AsmStatement::Load { index: end_local });
+                        self.push_statement(AsmStatement::Lt);
+                        self.push_statement(AsmStatement::Jump { label: checked_label.clone() });
+                        self.height -= 1;
+                        self.push_statement(AsmStatement::Label { label: down_label });
+                        self.push_statement(AsmStatement::Load { index });
+                        self.push_statement(AsmStatement::Load { index: end_local });
+                        self.push_statement(AsmStatement::Gt);
+                        self.push_statement(AsmStatement::Label { label: checked_label });
+                    }
+                }
+                self.push_statement(AsmStatement::JumpFalse { label: exit_label.clone() });
+
+                self.loops.push(AsmLoop {
+                    kind: AsmLoopKind::For,
+                    continue_label: step_label.clone(),
+                    break_label: end_label.clone(),
+                    height,
+                    bindings: vec![],
+                    tries: self.tries,
+                });
+                self.build_body(&lst[2..])?;
+                self.loops.pop();
+                self.pop_to(height);
+
+                // Step to the next one.
+                self.push_statement(AsmStatement::Label { label: step_label });
+                self.push_statement(AsmStatement::Load { index });
+                match step {
+                    Step::Const(step) => self.push_statement(AsmStatement::PushI64 { val: step }),
+                    Step::Local(step_local) => self.push_statement(AsmStatement::Load { index: step_local }),
+                }
+                self.push_statement(AsmStatement::Add);
+                self.push_statement(AsmStatement::Store { index });
+                self.push_statement(AsmStatement::Jump { label: cond_label });
+
+                self.push_statement(AsmStatement::Label { label: exit_label });
+                self.push_const(Value::Null);
+                self.push_statement(AsmStatement::Label { label: end_label });
+                self.unbind_local(name, old);
             }
-            SExp::List(lst) => {
-                self.build_list(lst);
+            Op::Loop => {
+                // (loop [name value ...] body...)
+                let bindings = match &lst[1] {
+                    SExp::Array(bindings) if bindings.len() % 2 == 0 => bindings,
+                    _ => return Err("`loop` wants bindings like [name value ...]".to_string()),
+                };
+                let start_label = self.new_label();
+                let end_label = self.new_label();
+                let height = self.height;
+
+                let mut locals = vec![];
+                let mut olds = vec![];
+                for binding in bindings.chunks(2) {
+                    self.build_value(&binding[1])?;
+                    let (name, index, old) = self.bind_local(&binding[0])?;
+                    self.push_statement(AsmStatement::Store { index });
+                    locals.push(index);
+                    olds.push((name, old));
+                }
+
+                self.push_statement(AsmStatement::Label { label: start_label.clone() });
+                self.loops.push(AsmLoop {
+                    kind: AsmLoopKind::Loop,
+                    continue_label: start_label,
+                    break_label: end_label.clone(),
+                    height,
+                    bindings: locals,
+                    tries: self.tries,
+                });
+                self.build_body(&lst[2..])?;
+                self.loops.pop();
+                self.push_statement(AsmStatement::Label { label: end_label });
+
+                for (name, old) in olds.into_iter().rev() {
+                    self.unbind_local(name, old);
+                }
             }
-            SExp::Sym(name) => {
-                let local_index = self.locals_index.get(name);
-                if let Some(index) = local_index {
-                    self.func.push_statement(AsmStatement::Load { index: *index });
+            Op::Recur => {
+                // (recur value ...)
+                let (label, height, bindings, tries) = match self.loops.iter().rev()
+                    .find(|l| l.kind == AsmLoopKind::Loop) {
+                    Some(l) => (l.continue_label.clone(), l.height, l.bindings.clone(), l.tries),
+                    None => return Err("`recur` should be in a `loop`".to_string()),
+                };
+                if bindings.len() != lst.len() - 1 {
+                    return Err(format!("`recur` wants {} values", bindings.len()));
+                }
+
+                let old_height = self.height;
+                for val in &lst[1..] {
+                    self.build_value(val)?;
+                }
+                for index in bindings.into_iter().rev() {
+                    self.push_statement(AsmStatement::Store { index });
+                }
+                self.pop_to(height);
+                self.end_tries(tries);
+                self.push_statement(AsmStatement::Jump { label });
+                self.height = old_height + 1;
+            }
+            Op::Break => {
+                // (break value)
+                let (label, height, tries) = match self.loops.last() {
+                    Some(l) => (l.break_label.clone(), l.height, l.tries),
+                    None => return Err("`break` should be in a loop".to_string()),
+                };
+
+                let old_height = self.height;
+                self.pop_to(height);
+                self.end_tries(tries);
+                if lst.len() >= 2 {
+                    self.build_value(&lst[1])?;
                 } else {
-                    let fn_index = self.ab.fns_index.get(name).unwrap();
-                    self.func.push_statement(AsmStatement::PushConst { index: *fn_index });
+                    self.push_const(Value::Null);
                 }
+                self.push_statement(AsmStatement::Jump { label });
+                self.height = old_height + 1;
             }
-            SExp::Str(val) => {
-                let ac = Value::Str(val.clone());
-                let idx = match self.ab.consts_index.get(&ac) {
-                    None => {
-                        self.ab.consts.push(ac.clone());
-                        let idx = self.ab.consts.len() as u32 - 1;
-                        self.ab.consts_index.insert(ac.clone(), idx);
-                        idx
-                    }
-                    Some(idx) => *idx,
+            Op::Continue => {
+                // (continue)
+                let (label, height, tries) = match self.loops.last() {
+                    Some(l) if l.kind != AsmLoopKind::Loop => (l.continue_label.clone(), l.height, l.tries),
+                    Some(_) => return Err("`continue` should not be in a `loop`, use `recur`".to_string()),
+                    None => return Err("`continue` should be in a loop".to_string()),
                 };
-                self.func.push_statement(AsmStatement::PushConst { index: idx });
+
+                let old_height = self.height;
+                self.pop_to(height);
+                self.end_tries(tries);
+                self.push_statement(AsmStatement::Jump { label });
+                self.height = old_height + 1;
             }
-            // TODO (@PeterlitsZo) Better error message.
-            _ => panic!("we hope the second item is INTERGER or LIST")
-        }
-    }
-}
+            Op::Cond => {
+                // (cond test value ... else value)
+                if lst.len().is_multiple_of(2) {
+                    return Err("`cond` wants pairs of test and value".to_string());
+                }
+                let end_label = self.new_label();
+                let height = self.height;
+                let mut has_else = false;
 
-#[cfg(test)]
-mod tests {
-    use crate::{asm::asm_statement::AsmLabel, ast::AstBuilder, token_stream::TokenStream};
+                for clause in lst[1..].chunks(2) {
+                    if clause[0] == SExp::Sym("else".to_string()) {
+                        self.build_value(&clause[1])?;
+                        has_else = true;
+                        break;
+                    }
+                    let next_label = self.new_label();
+                    self.build_value(&clause[0])?;
+                    self.push_statement(AsmStatement::JumpFalse { label: next_label.clone() });
+                    self.build_value(&clause[1])?;
+                    self.push_statement(AsmStatement::Jump { label: end_label.clone() });
+                    self.height = height;
+                    self.push_statement(AsmStatement::Label { label: next_label });
+                }
+                if !has_else {
+                    self.push_const(Value::Null);
+                }
 
-    use super::*;
+                self.push_statement(AsmStatement::Label { label: end_label });
+            }
+            Op::Case => {
+                // (case value key value (key key ...) value ... else value)
+                if !lst.len().is_multiple_of(2) {
+                    return Err("`case` wants a value and pairs of key and value".to_string());
+                }
+                let end_label = self.new_label();
+                let height = self.height;
+                let mut has_else = false;
 
-    #[test]
-    fn basic() {
-        let token_stream = TokenStream::new(r###"
-            1
-        "###);
-        let ast = AstBuilder::new(token_stream).build();
-        let asm = AsmBuilder::new(ast).build();
+                self.build_value(&lst[1])?;
+                let index = self.new_local();
+                self.push_statement(AsmStatement::Store { index });
 
-        let mut wanted = Asm::new();
-        wanted.push_fn(AsmFn::new(0, vec![
-            AsmStatement::PushI64 { val: 1 },
-            AsmStatement::Ret,
-        ]));
-        assert_eq!(asm, wanted);
+                for clause in lst[2..].chunks(2) {
+                    let keys = match &clause[0] {
+                        SExp::Sym(sym) if sym == "else" => {
+                            self.build_value(&clause[1])?;
+                            has_else = true;
+                            break;
+                        }
+                        SExp::List(keys, _) => keys.as_slice(),
+                        key => std::slice::from_ref(key),
+                    };
 
-        let token_stream = TokenStream::new(r###"
-            (+ 1 2 3 4 5)
-        "###);
-        let ast = AstBuilder::new(token_stream).build();
-        let asm = AsmBuilder::new(ast).build();
+                    let body_label = self.new_label();
+                    let next_label = self.new_label();
+                    for key in keys {
+                        if !matches!(key, SExp::I64(_) | SExp::Str(_)) {
+                            return Err("`case` wants keys of I64 or STR".to_string());
+                        }
+                        let next_key_label = self.new_label();
+                        self.push_statement(AsmStatement::Load { index });
+                        self.build_value(key)?;
+                        self.push_statement(AsmStatement::Eq);
+                        self.push_statement(AsmStatement::JumpFalse { label: next_key_label.clone() });
+                        self.push_statement(AsmStatement::Jump { label: body_label.clone() });
+                        self.push_statement(AsmStatement::Label { label: next_key_label });
+                    }
+                    self.push_statement(AsmStatement::Jump { label: next_label.clone() });
 
-        let mut wanted = Asm::new();
-        wanted.push_fn(AsmFn::new(0, vec![
-            AsmStatement::PushI64 { val: 1 },
-            AsmStatement::PushI64 { val: 2 },
-            AsmStatement::Add,
+                    self.push_statement(AsmStatement::Label { label: body_label });
+                    self.build_value(&clause[1])?;
+                    self.push_statement(AsmStatement::Jump { label: end_label.clone() });
+                    self.height = height;
+                    self.push_statement(AsmStatement::Label { label: next_label });
+                }
+                if !has_else {
+                    self.push_const(Value::Null);
+                }
+
+                self.push_statement(AsmStatement::Label { label: end_label });
+            }
+            Op::When | Op::Unless => {
+                // (when test body...) or (unless test body...)
+                self.build_value(&lst[1])?;
+
+                let fpath_label = self.new_label();
+                let end_label = self.new_label();
+                self.push_statement(AsmStatement::JumpFalse { label: fpath_label.clone() });
+                let height = self.height;
+
+                match op {
+                    Op::When => self.build_body(&lst[2..])?,
+                    _ => self.push_const(Value::Null),
+                }
+                self.push_statement(AsmStatement::Jump { label: end_label.clone() });
+
+                self.height = height;
+                self.push_statement(AsmStatement::Label { label: fpath_label });
+                match op {
+                    Op::When => self.push_const(Value::Null),
+                    _ => self.build_body(&lst[2..])?,
+                }
+
+                self.push_statement(AsmStatement::Label { label: end_label });
+            }
+            Op::Match => {
+                // (match value [pattern] value [pattern when guard] value ...)
+                if !lst.len().is_multiple_of(2) {
+                    return Err("`match` wants a value and pairs of pattern and value".to_string());
+                }
+                let end_label = self.new_label();
+                let height = self.height;
+
+                self.build_value(&lst[1])?;
+                let index = self.new_local();
+                self.push_statement(AsmStatement::Store { index });
+
+                for clause in lst[2..].chunks(2) {
+                    let (pattern, guard) = match &clause[0] {
+                        SExp::Array(arr) if arr.len() == 1 => (&arr[0], None),
+                        SExp::Array(arr) if arr.len() == 3
+                            && arr[1] == SExp::Sym("when".to_string()) => (&arr[0], Some(&arr[2])),
+                        _ => return Err("`match` wants clauses like [pattern] or [pattern when guard]".to_string()),
+                    };
+
+                    let next_label = self.new_label();
+                    let mut bind = AsmBind::Scoped(vec![]);
+                    self.build_pattern(pattern, index, Some(&next_label), &mut bind)?;
+                    if let Some(guard) = guard {
+                        self.build_value(guard)?;
+                        self.push_statement(AsmStatement::JumpFalse { label: next_label.clone() });
+                    }
+                    self.build_value(&clause[1])?;
+                    self.push_statement(AsmStatement::Jump { label: end_label.clone() });
+                    self.height = height;
+                    self.push_statement(AsmStatement::Label { label: next_label });
+
+                    if let AsmBind::Scoped(olds) = bind {
+                        for (name, old) in olds.into_iter().rev() {
+                            self.unbind_local(name, old);
+                        }
+                    }
+                }
+                self.push_const(Value::Null);
+
+                self.push_statement(AsmStatement::Label { label: end_label });
+            }
+            Op::Try => {
+                // (try value ... (catch name value ...))
+                let (name, handler) = match lst.last() {
+                    Some(SExp::List(clause, _)) if lst.len() >= 2 && clause.len() >= 2
+                        && clause[0] == SExp::Sym("catch".to_string()) => (&clause[1], &clause[2..]),
+                    _ => return Err("`try` should end with (catch name value ...)".to_string()),
+                };
+                let catch_label = self.new_label();
+                let end_label = self.new_label();
+                let height = self.height;
+
+                self.push_statement(AsmStatement::TryBegin { label: catch_label.clone() });
+                self.tries += 1;
+                self.build_body(&lst[1..lst.len() - 1])?;
+                self.tries -= 1;
+                self.push_statement(AsmStatement::TryEnd);
+                self.push_statement(AsmStatement::Jump { label: end_label.clone() });
+
+                // The stack is restored, and the error is pushed.
+                self.height = height + 1;
+                self.push_statement(AsmStatement::Label { label: catch_label });
+                let (name, index, old) = self.bind_local(name)?;
+                self.push_statement(AsmStatement::Store { index });
+                self.build_body(handler)?;
+                self.unbind_local(name, old);
+
+                self.push_statement(AsmStatement::Label { label: end_label });
+            }
+        }
+        Ok(())
+    }
+
+    fn build_value(&mut self, val: &SExp) -> Result<(), String> {
+        match val {
+            SExp::I64(first) => {
+                self.push_statement(AsmStatement::PushI64 { val: *first });
+            }
+            SExp::F64(val) => {
+                self.push_const(Value::F64(*val));
+            }
+            SExp::List(lst, pos) => {
+                self.build_list(lst, *pos)?;
+            }
+            SExp::Sym(name) if literal(name).is_some() => {
+                self.push_const(literal(name).unwrap());
+            }
+            SExp::Sym(name) => {
+                let local_index = self.locals_index.get(name);
+                if let Some(index) = local_index {
+                    self.push_statement(AsmStatement::Load { index: *index });
+                } else if let Some(fn_index) = self.ab.fn_const(name) {
+                    self.push_statement(AsmStatement::PushConst { index: fn_index });
+                } else {
+                    self.push_builtin(name)?;
+                }
+            }
+            SExp::Str(val) => {
+                self.push_const(Value::Str(val.clone()));
+            }
+            SExp::Array(arr) => {
+                for val in arr {
+                    self.build_value(val)?;
+                }
+                self.push_statement(AsmStatement::MakeArray { len: arr.len() as u32 });
+            }
+            SExp::Map(map) => {
+                for (key, val) in map {
+                    self.build_value(key)?;
+                    self.build_value(val)?;
+                }
+                self.push_statement(AsmStatement::MakeMap { len: map.len() as u32 });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{asm::asm_statement::AsmLabel, ast::AstBuilder, token_stream::TokenStream};
+
+    use super::*;
+
+    #[test]
+    fn basic() {
+        let token_stream = TokenStream::new(r###"
+            1
+        "###);
+        let ast = AstBuilder::new(token_stream).build();
+        let asm = AsmBuilder::new(ast).build();
+
+        let mut wanted = Asm::new();
+        wanted.push_fn(AsmFn::new(0, vec![
+            AsmStatement::PushI64 { val: 1 },
+            AsmStatement::Ret,
+        ]));
+        assert_eq!(asm, wanted);
+
+        let token_stream = TokenStream::new(r###"
+            (+ 1 2 3 4 5)
+        "###);
+        let ast = AstBuilder::new(token_stream).build();
+        let asm = AsmBuilder::new(ast).build();
+
+        let mut wanted = Asm::new();
+        wanted.push_fn(AsmFn::new(0, vec![
+            AsmStatement::PushI64 { val: 1 },
+            AsmStatement::PushI64 { val: 2 },
+            AsmStatement::Add,
             AsmStatement::PushI64 { val: 3 },
             AsmStatement::Add,
             AsmStatement::PushI64 { val: 4 },
@@ -481,6 +1495,7 @@ mod tests {
             AsmStatement::PushI64 { val: 5 },
             AsmStatement::Ret,
         ]));
+        wanted.ifns[1].name = "ret5".to_string();
         assert_eq!(asm, wanted);
 
         let token_stream = TokenStream::new(r###"
@@ -507,6 +1522,7 @@ mod tests {
             AsmStatement::Add,
             AsmStatement::Ret,
         ]));
+        wanted.ifns[1].name = "add".to_string();
         assert_eq!(asm, wanted);
 
         let token_stream = TokenStream::new(r###"
@@ -547,6 +1563,7 @@ mod tests {
             AsmStatement::Label { label: AsmLabel::new(".L2") },
             AsmStatement::Ret,
         ]));
+        wanted.ifns[1].name = "fac".to_string();
         assert_eq!(asm, wanted);
 
         let token_stream = TokenStream::new(r###"
@@ -554,19 +1571,13 @@ mod tests {
         "###);
         let ast = AstBuilder::new(token_stream).build();
         let mut asm_builder = AsmBuilder::new(ast);
-        let x_add_3 = |args: Vec<Value>| {
-            assert!(args.len() == 1);
-            match args[0] {
-                Value::I64(val) => Value::I64(val + 3),
-                _ => panic!("unexpected value"),
-            }
-        };
-        asm_builder.register_xfn("x_add_3".to_string(), x_add_3);
+        asm_builder.declare_xfn("x_add_3".to_string());
+        asm_builder.declare_xfn("x_unused".to_string());
         let asm = asm_builder.build();
 
         let mut wanted = Asm::new();
         wanted.xfns = vec![
-            XFn::new("x_add_3".to_string(), x_add_3),
+            "x_add_3".to_string(),
         ];
         wanted.consts = vec![
             Value::XFn(0),
@@ -638,6 +1649,358 @@ mod tests {
             AsmStatement::Label { label: AsmLabel::new(".L2") },
             AsmStatement::Ret,
         ]));
+        wanted.ifns[1].name = "foo".to_string();
+        wanted.ifns[2].name = "repeat".to_string();
         assert_eq!(asm, wanted);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn loops() {
+        let token_stream = TokenStream::new(r###"
+            (let i 0)
+            (while (< i 3) (let i (+ i 1)))
+        "###);
+        let ast = AstBuilder::new(token_stream).build();
+        let asm = AsmBuilder::new(ast).build();
+
+        let mut wanted = Asm::new();
+        wanted.consts = vec![
+            Value::Null,
+        ];
+        wanted.push_fn(AsmFn::new(1, vec![
+            AsmStatement::PushI64 { val: 0 },
+            AsmStatement::Store { index: 0 },
+            AsmStatement::PushConst { index: 0 },
+
+            AsmStatement::Label { label: AsmLabel::new(".L1") },
+            AsmStatement::Load { index: 0 },
+            AsmStatement::PushI64 { val: 3 },
+            AsmStatement::Lt,
+            AsmStatement::JumpFalse { label: AsmLabel::new(".L2") },
+            AsmStatement::Load { index: 0 },
+            AsmStatement::PushI64 { val: 1 },
+            AsmStatement::Add,
+            AsmStatement::Store { index: 0 },
+            AsmStatement::PushConst { index: 0 },
+            AsmStatement::Pop,
+            AsmStatement::Jump { label: AsmLabel::new(".L1") },
+            AsmStatement::Label { label: AsmLabel::new(".L2") },
+            AsmStatement::PushConst { index: 0 },
+            AsmStatement::Label { label: AsmLabel::new(".L3") },
+            AsmStatement::Ret,
+        ]));
+        assert_eq!(asm, wanted);
+
+        let token_stream = TokenStream::new(r###"
+            (loop [i 0] (if (< i 3) (recur (+ i 1)) i))
+        "###);
+        let ast = AstBuilder::new(token_stream).build();
+        let asm = AsmBuilder::new(ast).build();
+
+        let mut wanted = Asm::new();
+        wanted.push_fn(AsmFn::new(1, vec![
+            AsmStatement::PushI64 { val: 0 },
+            AsmStatement::Store { index: 0 },
+            AsmStatement::Label { label: AsmLabel::new(".L1") },
+            AsmStatement::Load { index: 0 },
+            AsmStatement::PushI64 { val: 3 },
+            AsmStatement::Lt,
+            AsmStatement::JumpFalse { label: AsmLabel::new(".L3") },
+            AsmStatement::Load { index: 0 },
+            AsmStatement::PushI64 { val: 1 },
+            AsmStatement::Add,
+            AsmStatement::Store { index: 0 },
+            AsmStatement::Jump { label: AsmLabel::new(".L1") },
+            AsmStatement::Jump { label: AsmLabel::new(".L4") },
+            AsmStatement::Label { label: AsmLabel::new(".L3") },
+            AsmStatement::Load { index: 0 },
+            AsmStatement::Label { label: AsmLabel::new(".L4") },
+            AsmStatement::Label { label: AsmLabel::new(".L2") },
+            AsmStatement::Ret,
+        ]));
+        assert_eq!(asm, wanted);
+    }
+
+    #[test]
+    fn conditions() {
+        let token_stream = TokenStream::new(r###"
+            (cond (== 1 2) 3 else 4)
+        "###);
+        let ast = AstBuilder::new(token_stream).build();
+        let asm = AsmBuilder::new(ast).build();
+
+        let mut wanted = Asm::new();
+        wanted.push_fn(AsmFn::new(0, vec![
+            AsmStatement::PushI64 { val: 1 },
+            AsmStatement::PushI64 { val: 2 },
+            AsmStatement::Eq,
+            AsmStatement::JumpFalse { label: AsmLabel::new(".L2") },
+            AsmStatement::PushI64 { val: 3 },
+            AsmStatement::Jump { label: AsmLabel::new(".L1") },
+            AsmStatement::Label { label: AsmLabel::new(".L2") },
+            AsmStatement::PushI64 { val: 4 },
+            AsmStatement::Label { label: AsmLabel::new(".L1") },
+            AsmStatement::Ret,
+        ]));
+        assert_eq!(asm, wanted);
+
+        let token_stream = TokenStream::new(r###"
+            (case 2 (1 2) 3)
+        "###);
+        let ast = AstBuilder::new(token_stream).build();
+        let asm = AsmBuilder::new(ast).build();
+
+        let mut wanted = Asm::new();
+        wanted.consts = vec![
+            Value::Null,
+        ];
+        wanted.push_fn(AsmFn::new(1, vec![
+            AsmStatement::PushI64 { val: 2 },
+            AsmStatement::Store { index: 0 },
+            AsmStatement::Load { index: 0 },
+            AsmStatement::PushI64 { val: 1 },
+            AsmStatement::Eq,
+            AsmStatement::JumpFalse { label: AsmLabel::new(".L4") },
+            AsmStatement::Jump { label: AsmLabel::new(".L2") },
+            AsmStatement::Label { label: AsmLabel::new(".L4") },
+            AsmStatement::Load { index: 0 },
+            AsmStatement::PushI64 { val: 2 },
+            AsmStatement::Eq,
+            AsmStatement::JumpFalse { label: AsmLabel::new(".L5") },
+            AsmStatement::Jump { label: AsmLabel::new(".L2") },
+            AsmStatement::Label { label: AsmLabel::new(".L5") },
+            AsmStatement::Jump { label: AsmLabel::new(".L3") },
+            AsmStatement::Label { label: AsmLabel::new(".L2") },
+            AsmStatement::PushI64 { val: 3 },
+            AsmStatement::Jump { label: AsmLabel::new(".L1") },
+            AsmStatement::Label { label: AsmLabel::new(".L3") },
+            AsmStatement::PushConst { index: 0 },
+            AsmStatement::Label { label: AsmLabel::new(".L1") },
+            AsmStatement::Ret,
+        ]));
+        assert_eq!(asm, wanted);
+    }
+
+    #[test]
+    fn patterns() {
+        let token_stream = TokenStream::new(r###"
+            (match 1 [1] 2 [x] x)
+        "###);
+        let ast = AstBuilder::new(token_stream).build();
+        let asm = AsmBuilder::new(ast).build();
+
+        let mut wanted = Asm::new();
+        wanted.consts = vec![
+            Value::Null,
+        ];
+        wanted.push_fn(AsmFn::new(2, vec![
+            AsmStatement::PushI64 { val: 1 },
+            AsmStatement::Store { index: 0 },
+
+            AsmStatement::Load { index: 0 },
+            AsmStatement::PushI64 { val: 1 },
+            AsmStatement::Eq,
+            AsmStatement::JumpFalse { label: AsmLabel::new(".L2") },
+            AsmStatement::PushI64 { val: 2 },
+            AsmStatement::Jump { label: AsmLabel::new(".L1") },
+            AsmStatement::Label { label: AsmLabel::new(".L2") },
+
+            AsmStatement::Load { index: 0 },
+            AsmStatement::Store { index: 1 },
+            AsmStatement::Load { index: 1 },
+            AsmStatement::Jump { label: AsmLabel::new(".L1") },
+            AsmStatement::Label { label: AsmLabel::new(".L3") },
+
+            AsmStatement::PushConst { index: 0 },
+            AsmStatement::Label { label: AsmLabel::new(".L1") },
+            AsmStatement::Ret,
+        ]));
+        assert_eq!(asm, wanted);
+    }
+
+    #[test]
+    fn arrays() {
+        let token_stream = TokenStream::new(r###"
+            (get [1 2] [0])
+        "###);
+        let ast = AstBuilder::new(token_stream).build();
+        let asm = AsmBuilder::new(ast).build();
+
+        let mut wanted = Asm::new();
+        wanted.consts = vec![
+            Value::Builtin(lookup_builtin("get").unwrap()),
+        ];
+        wanted.push_fn(AsmFn::new(0, vec![
+            AsmStatement::PushConst { index: 0 },
+            AsmStatement::PushI64 { val: 1 },
+            AsmStatement::PushI64 { val: 2 },
+            AsmStatement::MakeArray { len: 2 },
+            AsmStatement::PushI64 { val: 0 },
+            AsmStatement::MakeArray { len: 1 },
+            AsmStatement::Call { args: 2 },
+            AsmStatement::Ret,
+        ]));
+        assert_eq!(asm, wanted);
+    }
+
+    #[test]
+    fn maps() {
+        let token_stream = TokenStream::new(r###"
+            {"a" 1}
+        "###);
+        let ast = AstBuilder::new(token_stream).build();
+        let asm = AsmBuilder::new(ast).build();
+
+        let mut wanted = Asm::new();
+        wanted.consts = vec![
+            Value::Str("a".to_string()),
+        ];
+        wanted.push_fn(AsmFn::new(0, vec![
+            AsmStatement::PushConst { index: 0 },
+            AsmStatement::PushI64 { val: 1 },
+            AsmStatement::MakeMap { len: 1 },
+            AsmStatement::Ret,
+        ]));
+        assert_eq!(asm, wanted);
+    }
+
+    #[test]
+    fn params() {
+        let token_stream = TokenStream::new(r###"
+            (+ it 1)
+        "###);
+        let ast = AstBuilder::new(token_stream).build();
+        let mut asm_builder = AsmBuilder::new(ast);
+        asm_builder.declare_param("it".to_string());
+        let asm = asm_builder.build();
+
+        let mut wanted = Asm::new();
+        wanted.push_fn(AsmFn::new(1, vec![
+            AsmStatement::Load { index: 0 },
+            AsmStatement::PushI64 { val: 1 },
+            AsmStatement::Add,
+            AsmStatement::Ret,
+        ]));
+        assert_eq!(asm, wanted);
+    }
+
+    #[test]
+    fn regexes() {
+        let token_stream = TokenStream::new(r###"
+            (re/match "a+" "aa")
+            (re/split "a+" "bab")
+        "###);
+        let ast = AstBuilder::new(token_stream).build();
+        let asm = AsmBuilder::new(ast).build();
+
+        let mut wanted = Asm::new();
+        wanted.consts = vec![
+            Value::Builtin(lookup_builtin("re/match").unwrap()),
+            Value::Regex(Regex::new("a+").unwrap()),
+            Value::Str("aa".to_string()),
+            Value::Builtin(lookup_builtin("re/split").unwrap()),
+            Value::Str("bab".to_string()),
+        ];
+        wanted.push_fn(AsmFn::new(0, vec![
+            AsmStatement::PushConst { index: 0 },
+            AsmStatement::PushConst { index: 1 },
+            AsmStatement::PushConst { index: 2 },
+            AsmStatement::Call { args: 2 },
+            AsmStatement::PushConst { index: 3 },
+            AsmStatement::PushConst { index: 1 },
+            AsmStatement::PushConst { index: 4 },
+            AsmStatement::Call { args: 2 },
+            AsmStatement::Ret,
+        ]));
+        assert_eq!(asm, wanted);
+
+        // The invalid literal pattern is an error of the source.
+        let ast = AstBuilder::new(TokenStream::new(r#"(re/match "(" "x")"#)).build();
+        let result = AsmBuilder::new(ast).try_build();
+        assert!(matches!(result, Err(AsmError::Invalid(msg)) if msg.starts_with(r#"invalid regex "(": "#)));
+    }
+
+    #[test]
+    fn methods() {
+        let token_stream = TokenStream::new("(.push 1 2)");
+        let ast = AstBuilder::new(token_stream).build();
+        let asm = AsmBuilder::new(ast).build();
+
+        let mut wanted = Asm::new();
+        wanted.consts = vec![
+            Value::Builtin(lookup_builtin("method").unwrap()),
+            Value::Str("push".to_string()),
+        ];
+        wanted.push_fn(AsmFn::new(0, vec![
+            AsmStatement::PushConst { index: 0 },
+            AsmStatement::PushI64 { val: 1 },
+            AsmStatement::PushConst { index: 1 },
+            AsmStatement::PushI64 { val: 2 },
+            AsmStatement::Call { args: 3 },
+            AsmStatement::Ret,
+        ]));
+        assert_eq!(asm, wanted);
+    }
+
+    #[test]
+    fn import_cycle() {
+        struct Files;
+
+        impl ModuleLoader for Files {
+            fn load(&self, path: &std::path::Path) -> Result<String, String> {
+                match path.to_str() {
+                    Some("a.jisp") => Ok(r#"(import "b.jisp") (fn a [] 1) (export a)"#.to_string()),
+                    _ => Ok(r#"(import "a.jisp") (fn b [] 2) (export b)"#.to_string()),
+                }
+            }
+        }
+
+        let ast = AstBuilder::new(TokenStream::new(r#"(import "a.jisp")"#)).build();
+        let mut asm_builder = AsmBuilder::new(ast);
+        asm_builder.set_loader(Files);
+        assert_eq!(asm_builder.try_build().err(), Some(AsmError::Import(ImportError {
+            path: PathBuf::from("a.jisp"),
+            msg: "import cycle: a.jisp -> b.jisp -> a.jisp".to_string(),
+        })));
+    }
+
+    #[test]
+    fn import_unexported() {
+        struct Files;
+
+        impl ModuleLoader for Files {
+            fn load(&self, _: &std::path::Path) -> Result<String, String> {
+                Ok("(fn hidden [] 1) (fn shown [] 2) (export shown)".to_string())
+            }
+        }
+
+        let ast = AstBuilder::new(TokenStream::new(r#"(import "b.jisp" [shown hidden])"#)).build();
+        let mut asm_builder = AsmBuilder::new(ast);
+        asm_builder.set_loader(Files);
+        assert_eq!(asm_builder.try_build().err(), Some(AsmError::Import(ImportError {
+            path: PathBuf::from("b.jisp"),
+            msg: "does not export hidden".to_string(),
+        })));
+    }
+
+    #[test]
+    fn tries() {
+        let token_stream = TokenStream::new("(try 1 (catch e e))");
+        let ast = AstBuilder::new(token_stream).build();
+        let asm = AsmBuilder::new(ast).build();
+
+        let mut wanted = Asm::new();
+        wanted.push_fn(AsmFn::new(1, vec![
+            AsmStatement::TryBegin { label: AsmLabel::new(".L1") },
+            AsmStatement::PushI64 { val: 1 },
+            AsmStatement::TryEnd,
+            AsmStatement::Jump { label: AsmLabel::new(".L2") },
+            AsmStatement::Label { label: AsmLabel::new(".L1") },
+            AsmStatement::Store { index: 0 },
+            AsmStatement::Load { index: 0 },
+            AsmStatement::Label { label: AsmLabel::new(".L2") },
+            AsmStatement::Ret,
+        ]));
+        assert_eq!(asm, wanted);
+    }
+}
@@ -26,6 +26,13 @@ pub enum AsmStatement {
     JumpFalse { label: AsmLabel }, // Jump to the label if false.
 
     Call { args: u32 },
+    Dbg { index: u32 }, // Write the top of stack with the location in the const (by index) like the `dbg`.
+
+    TryBegin { label: AsmLabel }, // Catch the runtime errors by the handler at the label.
+    TryEnd, // Drop the innermost handler.
+
+    MakeArray { len: u32 }, // Make an array from the values on the top of stack.
+    MakeMap { len: u32 }, // Make a map from the pairs of key and value on the top of stack.
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
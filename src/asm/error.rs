@@ -0,0 +1,31 @@
+use std::fmt::Display;
+
+use super::ImportError;
+
+/// The error raised when the [AsmBuilder](super::AsmBuilder) builds the AST.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum AsmError {
+    /// An imported module can not be loaded.
+    Import(ImportError),
+
+    /// The AST is not a valid program, like calling an unknown function or
+    /// `break` out of a loop.
+    Invalid(String),
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AsmError::Import(err) => err.fmt(f),
+            AsmError::Invalid(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl From<ImportError> for AsmError {
+    fn from(err: ImportError) -> Self {
+        AsmError::Import(err)
+    }
+}
+
+impl std::error::Error for AsmError {}
@@ -1,9 +1,14 @@
 mod asm;
 mod asm_statement;
 mod asm_builder;
+mod error;
+mod module;
 
 pub type Asm = asm::Asm;
 pub type AsmFn = asm::AsmFn;
 pub type AsmStatement = asm_statement::AsmStatement;
 pub type AsmLabel = asm_statement::AsmLabel;
-pub type AsmBuilder = asm_builder::AsmBuilder;
\ No newline at end of file
+pub type AsmBuilder = asm_builder::AsmBuilder;
+pub type ImportError = module::ImportError;
+pub type AsmError = error::AsmError;
+pub use module::{DirsLoader, FsLoader, ModuleLoader};
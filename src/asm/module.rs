@@ -0,0 +1,126 @@
+//! The modules imported by `(import "path" [name ...])`, which are jisp files
+//! of `fn` with the names in `(export name ...)` importable.
+
+use std::{fmt::Display, fs, path::{Component, Path, PathBuf}, rc::Rc};
+
+use crate::bytecode::is_under;
+
+/// Where the source of the modules is read from.
+pub trait ModuleLoader {
+    /// Read the source of the module at the resolved path.
+    fn load(&self, path: &Path) -> Result<String, String>;
+}
+
+/// The [ModuleLoader] of the files, used by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FsLoader;
+
+impl ModuleLoader for FsLoader {
+    fn load(&self, path: &Path) -> Result<String, String> {
+        fs::read_to_string(path).map_err(|err| err.to_string())
+    }
+}
+
+/// The [ModuleLoader] of the files under the directories, like
+/// [Capabilities::read_dirs](crate::bytecode::Capabilities::read_dirs). The
+/// files outside are denied whether they exist or not, so the imports can
+/// not tell what is on the disk.
+#[derive(Debug, Clone, Default)]
+pub struct DirsLoader {
+    dirs: Vec<PathBuf>,
+}
+
+impl DirsLoader {
+    pub fn new(dirs: Vec<PathBuf>) -> Self {
+        Self { dirs }
+    }
+}
+
+impl ModuleLoader for DirsLoader {
+    fn load(&self, path: &Path) -> Result<String, String> {
+        match is_under(&self.dirs, path) {
+            Ok(true) => fs::read_to_string(path).map_err(|err| err.to_string()),
+            _ => Err("permission denied".to_string()),
+        }
+    }
+}
+
+/// The loader shared by the builders, like the one of the
+/// [Engine](crate::engine::Engine).
+impl<L: ModuleLoader + ?Sized> ModuleLoader for Rc<L> {
+    fn load(&self, path: &Path) -> Result<String, String> {
+        (**self).load(path)
+    }
+}
+
+/// The error of loading an imported module: it can not be read, is not
+/// valid, or imports itself.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ImportError {
+    pub path: PathBuf,
+    pub msg: String,
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "can not import {}: {}", self.path.display(), self.msg)
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+/// Resolve the path of the import relative to the importing file, or to the
+/// working directory if the source is not from a file.
+pub fn resolve(from: Option<&Path>, path: &str) -> PathBuf {
+    let base = from.and_then(Path::parent).unwrap_or(Path::new(""));
+    normalize(&base.join(path))
+}
+
+/// Remove the `.` and `..` in the path without touching the file system, so
+/// the same module imported by different paths is found in the cache.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => { normalized.pop(); }
+                Some(Component::RootDir) => (),
+                _ => normalized.push(".."),
+            },
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolution() {
+        assert_eq!(resolve(None, "lib/util.jisp"), PathBuf::from("lib/util.jisp"));
+        assert_eq!(resolve(Some(Path::new("app/main.jisp")), "lib/util.jisp"), PathBuf::from("app/lib/util.jisp"));
+        assert_eq!(resolve(Some(Path::new("app/lib/util.jisp")), "../main.jisp"), PathBuf::from("app/main.jisp"));
+        assert_eq!(resolve(Some(Path::new("/app/main.jisp")), "./../../x.jisp"), PathBuf::from("/x.jisp"));
+        assert_eq!(resolve(Some(Path::new("main.jisp")), "../x.jisp"), PathBuf::from("../x.jisp"));
+    }
+
+    #[test]
+    fn dirs() {
+        let dir = std::env::temp_dir().join(format!("jisp-dirs-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("util.jisp"), "(fn f [] 1)").unwrap();
+
+        let loader = DirsLoader::new(vec![dir.clone()]);
+        assert_eq!(loader.load(&dir.join("util.jisp")), Ok("(fn f [] 1)".to_string()));
+        assert!(loader.load(&dir.join("missing.jisp")).is_err());
+        let denied = Err("permission denied".to_string());
+        assert_eq!(loader.load(Path::new("/etc/passwd")), denied);
+        assert_eq!(loader.load(&dir.join("../../no/such/file")), denied);
+        assert_eq!(DirsLoader::default().load(&dir.join("util.jisp")), denied);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
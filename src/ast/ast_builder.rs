@@ -1,6 +1,6 @@
 use std::{iter::Peekable, process::exit};
 
-use crate::token_stream::{TokenStream, TokenVal};
+use crate::token_stream::{TokenPos, TokenStream, TokenVal};
 
 use super::{Ast, Error, ErrorMsg, SExp};
 
@@ -8,6 +8,13 @@ use super::{Ast, Error, ErrorMsg, SExp};
 pub struct AstBuilder<'a> {
     source_plain: String,
     token_stream: Peekable<TokenStream<'a>>,
+    pos: TokenPos, // The position of the last token, for the tokens which are not valid.
+}
+
+/// What is wanted at the position, or why the token there is not valid.
+struct SyntaxError {
+    pos: TokenPos,
+    msg: ErrorMsg<'static>,
 }
 
 impl<'a> AstBuilder<'a> {
@@ -15,104 +22,148 @@ impl<'a> AstBuilder<'a> {
     pub fn new(token_stream: TokenStream<'a>) -> Self {
         Self {
             source_plain: token_stream.source_plain().to_string(),
-            token_stream: token_stream.peekable()
+            token_stream: token_stream.peekable(),
+            pos: TokenPos { lineno: 1, offset: 1 },
         }
     }
 
-    /// Build a [Ast].
+    /// Build a [Ast], print the error and exit if the source is not valid.
     pub fn build(mut self) -> Ast {
+        match self.build_s_exps() {
+            Ok(ast) => ast,
+            Err(err) => {
+                let err = Error::new(&self.source_plain, err.pos, err.msg);
+                err.print();
+                exit(1);
+            }
+        }
+    }
+
+    /// Build a [Ast], or return the error if the source is not valid.
+    pub fn try_build(mut self) -> Result<Ast, String> {
+        self.build_s_exps().map_err(|err| {
+            let msg = match err.msg {
+                ErrorMsg::Unexpected { want } => format!("want {}", want),
+                ErrorMsg::Invalid { why } => why.to_string(),
+            };
+            format!("{} at line {} column {}", msg, err.pos.lineno, err.pos.offset)
+        })
+    }
+
+    fn build_s_exps(&mut self) -> Result<Ast, SyntaxError> {
         let mut ast = Ast::new();
         loop {
             match self.token_stream.peek() {
                 Some(tok) if tok.val() == &TokenVal::EOF => break,
                 _ => (),
             };
-            let s_exp = self.next_value();
+            let s_exp = self.next_value()?;
             ast.push_s_exp(s_exp);
         }
-        ast
+        Ok(ast)
     }
 
-    fn skip(&mut self, val: TokenVal) {
-        let next_token = self.token_stream.next().unwrap();
+    fn skip(&mut self, val: TokenVal) -> Result<(), SyntaxError> {
+        let next_token = self.next_token()?;
         if next_token.val() == &val {
-            return;
+            return Ok(());
+        }
+        Err(SyntaxError { pos: next_token.pos(), msg: ErrorMsg::Unexpected { want: val.name() } })
+    }
+
+    fn next_token(&mut self) -> Result<crate::token_stream::Token, SyntaxError> {
+        match self.token_stream.next() {
+            Some(tok) => {
+                self.pos = tok.pos();
+                Ok(tok)
+            }
+            None => Err(SyntaxError { pos: self.pos, msg: ErrorMsg::Unexpected { want: "a valid token after" } }),
         }
-        let err = Error::new(
-            &self.source_plain, next_token.pos(),
-            ErrorMsg::Unexpected { want: val.name() }
-        );
-        err.print();
-        exit(1);
     }
 
-    fn next_list(&mut self) -> SExp {
+    fn next_list(&mut self) -> Result<SExp, SyntaxError> {
         let mut result = vec![];
-        self.skip(TokenVal::Lparam);
+        let pos = self.token_stream.peek().unwrap().pos();
+        self.skip(TokenVal::Lparam)?;
         loop {
             match self.token_stream.peek() {
                 Some(tok) if tok.val() == &TokenVal::Rparam => break,
                 _ => (),
             };
-            let s_exp = self.next_value();
+            let s_exp = self.next_value()?;
             result.push(s_exp);
         }
-        self.skip(TokenVal::Rparam);
-        SExp::List(result)
+        self.skip(TokenVal::Rparam)?;
+        Ok(SExp::List(result, pos))
     }
 
-    fn next_arr(&mut self) -> SExp {
+    fn next_arr(&mut self) -> Result<SExp, SyntaxError> {
         let mut result = vec![];
-        self.skip(TokenVal::Lsquare);
+        self.skip(TokenVal::Lsquare)?;
         loop {
             match self.token_stream.peek() {
                 Some(tok) if tok.val() == &TokenVal::Rsquare => break,
                 _ => (),
             };
-            let s_exp = self.next_value();
+            let s_exp = self.next_value()?;
             result.push(s_exp);
         }
-        self.skip(TokenVal::Rsquare);
-        SExp::Array(result)
+        self.skip(TokenVal::Rsquare)?;
+        Ok(SExp::Array(result))
+    }
+
+    fn next_map(&mut self) -> Result<SExp, SyntaxError> {
+        let mut result = vec![];
+        self.skip(TokenVal::Lbrace)?;
+        loop {
+            match self.token_stream.peek() {
+                Some(tok) if tok.val() == &TokenVal::Rbrace => break,
+                _ => (),
+            };
+            let key = self.next_value()?;
+            match self.token_stream.peek() {
+                Some(tok) if tok.val() == &TokenVal::Rbrace => {
+                    return Err(SyntaxError { pos: tok.pos(), msg: ErrorMsg::Unexpected { want: "a value after the key" } });
+                },
+                _ => (),
+            };
+            let val = self.next_value()?;
+            result.push((key, val));
+        }
+        self.skip(TokenVal::Rbrace)?;
+        Ok(SExp::Map(result))
     }
 
-    fn next_value(&mut self) -> SExp {
-        let peek_token = self.token_stream.peek();
-        let peek_token = match peek_token {
+    fn next_value(&mut self) -> Result<SExp, SyntaxError> {
+        let peek_token = match self.token_stream.peek() {
             Some(tok) if tok.val() == &TokenVal::EOF => {
-                let err = Error::new(
-                    &self.source_plain, tok.pos(),
-                    ErrorMsg::Unexpected { want: "RPARAM, I64 or LPARAM" }
-                );
-                err.print();
-                exit(1);
+                return Err(SyntaxError { pos: tok.pos(), msg: ErrorMsg::Unexpected { want: "RPARAM, I64 or LPARAM" } });
             },
             Some(tok) => tok.clone(),
-            None => panic!("should not peek None")
+            None => return Err(SyntaxError { pos: self.pos, msg: ErrorMsg::Unexpected { want: "a valid token after" } }),
         };
         match peek_token.val() {
             TokenVal::Lparam => self.next_list(),
             TokenVal::Lsquare => self.next_arr(),
+            TokenVal::Lbrace => self.next_map(),
             TokenVal::I64(val) => {
-                self.skip(TokenVal::I64(*val));
-                SExp::I64(*val)
+                self.skip(TokenVal::I64(*val))?;
+                Ok(SExp::I64(*val))
+            }
+            TokenVal::F64(val) => {
+                self.skip(TokenVal::F64(*val))?;
+                Ok(SExp::F64(*val))
             }
             TokenVal::Str(val) => {
-                self.skip(TokenVal::Str(val.clone()));
-                SExp::Str(val.clone())
+                self.skip(TokenVal::Str(val.clone()))?;
+                Ok(SExp::Str(val.clone()))
             }
             TokenVal::Sym(sym) => {
-                self.skip(TokenVal::Sym(sym.clone()));
-                SExp::Sym(sym.clone())
-            }
-            _ => {
-                let err = Error::new(
-                    &self.source_plain, peek_token.pos(),
-                    ErrorMsg::Unexpected { want: "LPARAM, I64 or SYM" }
-                );
-                err.print();
-                exit(1);
+                self.skip(TokenVal::Sym(sym.clone()))?;
+                Ok(SExp::Sym(sym.clone()))
             }
+            TokenVal::Error(why) => Err(SyntaxError { pos: peek_token.pos(), msg: ErrorMsg::Invalid { why } }),
+            _ => Err(SyntaxError { pos: peek_token.pos(), msg: ErrorMsg::Unexpected { want: "LPARAM, I64 or SYM" } }),
         }
     }
 }
@@ -140,7 +191,7 @@ mod tests {
                 SExp::Sym("+".to_string()),
                 SExp::I64(1),
                 SExp::I64(2),
-            ]),
+            ], TokenPos { lineno: 2, offset: 13 }),
         ]));
     }
 
@@ -157,14 +208,14 @@ mod tests {
                     SExp::Sym("==".to_string()),
                     SExp::I64(2),
                     SExp::I64(1),
-                ]),
+                ], TokenPos { lineno: 2, offset: 17 }),
                 SExp::I64(1),
                 SExp::List(vec![
                     SExp::Sym("*".to_string()),
                     SExp::I64(2),
                     SExp::I64(1),
-                ]),
-            ]),
+                ], TokenPos { lineno: 2, offset: 28 }),
+            ], TokenPos { lineno: 2, offset: 13 }),
         ]));
     }
 
@@ -179,18 +230,18 @@ mod tests {
                 SExp::Sym("let".to_string()),
                 SExp::Sym("h".to_string()),
                 SExp::Str("hello".to_string()),
-            ]),
+            ], TokenPos { lineno: 2, offset: 13 }),
             SExp::List(vec![
                 SExp::Sym("let".to_string()),
                 SExp::Sym("w".to_string()),
                 SExp::Str("world".to_string()),
-            ]),
+            ], TokenPos { lineno: 2, offset: 29 }),
             SExp::List(vec![
                 SExp::Sym("if".to_string()),
-                SExp::List(vec![SExp::Sym("==".to_string()), SExp::I64(1), SExp::I64(1)]),
+                SExp::List(vec![SExp::Sym("==".to_string()), SExp::I64(1), SExp::I64(1)], TokenPos { lineno: 2, offset: 49 }),
                 SExp::Sym("h".to_string()),
                 SExp::Sym("w".to_string()),
-            ]),
+            ], TokenPos { lineno: 2, offset: 45 }),
         ]));
     }
 
@@ -207,10 +258,10 @@ mod tests {
                 SExp::Sym("ret5".to_string()),
                 SExp::Array(vec![]),
                 SExp::I64(5),
-            ]),
+            ], TokenPos { lineno: 2, offset: 13 }),
             SExp::List(vec![
                 SExp::Sym("ret5".to_string()),
-            ]),
+            ], TokenPos { lineno: 3, offset: 13 }),
         ]));
 
         let token_stream = TokenStream::new(r###"
@@ -227,13 +278,55 @@ mod tests {
                     SExp::Sym("+".to_string()),
                     SExp::Sym("x".to_string()),
                     SExp::Sym("y".to_string()),
-                ]),
-            ]),
+                ], TokenPos { lineno: 2, offset: 27 }),
+            ], TokenPos { lineno: 2, offset: 13 }),
             SExp::List(vec![
                 SExp::Sym("add".to_string()),
                 SExp::I64(3),
                 SExp::I64(5),
+            ], TokenPos { lineno: 3, offset: 13 }),
+        ]));
+    }
+
+    #[test]
+    fn maps() {
+        let token_stream = TokenStream::new(r###"
+            {"a" 1 "b" [2]}
+        "###);
+        let ast = AstBuilder::new(token_stream).build();
+        assert_eq!(ast, Ast::from([
+            SExp::Map(vec![
+                (SExp::Str("a".to_string()), SExp::I64(1)),
+                (SExp::Str("b".to_string()), SExp::Array(vec![SExp::I64(2)])),
             ]),
         ]));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn errors() {
+        let cases = [
+            ("(+ 1", "want RPARAM, I64 or LPARAM at line 1 column 5"),
+            ("(+ 1\n  99999999999999999999)", "integer literal overflows I64 at line 2 column 3"),
+            ("[1 1.2.3]", "number literal is not valid at line 1 column 4"),
+        ];
+        for (source, msg) in cases {
+            assert_eq!(AstBuilder::new(TokenStream::new(source)).try_build(), Err(msg.to_string()));
+        }
+    }
+
+    #[test]
+    fn positions() {
+        let token_stream = TokenStream::new("(+ 1\n  (dbg 2))");
+        let ast = AstBuilder::new(token_stream).build();
+        assert_eq!(ast, Ast::from([
+            SExp::List(vec![
+                SExp::Sym("+".to_string()),
+                SExp::I64(1),
+                SExp::List(vec![
+                    SExp::Sym("dbg".to_string()),
+                    SExp::I64(2),
+                ], TokenPos { lineno: 2, offset: 3 }),
+            ], TokenPos { lineno: 1, offset: 1 }),
+        ]));
+    }
+}
@@ -7,7 +7,8 @@ pub struct Error<'a, 'b> {
 }
 
 pub enum ErrorMsg<'a> {
-    Unexpected{ want: &'a str }
+    Unexpected{ want: &'a str },
+    Invalid{ why: &'a str },
 }
 
 impl<'a, 'b> Error<'a, 'b> {
@@ -18,8 +19,7 @@ impl<'a, 'b> Error<'a, 'b> {
     /// Print the error to stderr.
     pub fn print(&self) {
         let lines = self.source_plain.lines();
-        let mut lineno = 1;
-        for line in lines {
+        for (lineno, line) in (1..).zip(lines) {
             eprintln!("{}", line);
             if lineno == self.pos.lineno {
                 for _ in 0..(self.pos.offset - 1) {
@@ -32,9 +32,11 @@ impl<'a, 'b> Error<'a, 'b> {
                     ErrorMsg::Unexpected { want } => {
                         eprintln!("want {}.", want)
                     }
+                    ErrorMsg::Invalid { why } => {
+                        eprintln!("{}.", why)
+                    }
                 }
             }
-            lineno += 1;
         }
     }
 }
\ No newline at end of file
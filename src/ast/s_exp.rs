@@ -1,9 +1,14 @@
+use crate::token_stream::TokenPos;
+
 /// A simple S-expression.
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum SExp {
     I64(i64),
+    F64(f64),
     Sym(String),
     Str(String),
-    List(Vec<SExp>),
+    /// The list, with the position of it written by the builtin `dbg`.
+    List(Vec<SExp>, TokenPos),
     Array(Vec<SExp>),
+    Map(Vec<(SExp, SExp)>),
 }
\ No newline at end of file
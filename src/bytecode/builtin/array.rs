@@ -0,0 +1,46 @@
+use std::mem::size_of;
+
+use crate::{bytecode::{Runner, RuntimeError}, value::{Array, Value}};
+
+use super::{arity, check_put, want_array, want_i64};
+
+/// (push arr value), push the value in place and return the arr.
+pub fn push(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("push", &args, 2, 2)?;
+    let arr = want_array("push", &args[0])?;
+    check_put("push", &args[0], &args[1])?;
+    runner.alloc(size_of::<Value>())?;
+    arr.push(args[1].clone());
+    Ok(args[0].clone())
+}
+
+/// (pop arr), pop the last value in place, `null` if it is empty.
+pub fn pop(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("pop", &args, 1, 1)?;
+    let arr = want_array("pop", &args[0])?;
+    Ok(arr.pop().unwrap_or(Value::Null))
+}
+
+/// (slice arr start end), the end is the length by default.
+pub fn slice(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("slice", &args, 2, 3)?;
+    let arr = want_array("slice", &args[0])?;
+    let len = arr.len() as i64;
+    let start = want_i64("slice", &args[1])?.clamp(0, len) as usize;
+    let end = match args.get(2) {
+        Some(end) => want_i64("slice", end)?.clamp(0, len) as usize,
+        None => len as usize,
+    };
+    let values = match arr.values().get(start..end) {
+        Some(values) => values.to_vec(),
+        None => vec![],
+    };
+    runner.alloc(values.len() * size_of::<Value>())?;
+    Ok(Value::Array(Array::from(values)))
+}
+
+/// (array? value)
+pub fn is_array(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("array?", &args, 1, 1)?;
+    Ok(Value::Bool(matches!(args[0], Value::Array(_))))
+}
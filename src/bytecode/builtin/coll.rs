@@ -0,0 +1,201 @@
+use crate::{bytecode::{Runner, RuntimeError}, value::{Array, Map, Value}};
+
+use super::{arity, check_put, error, map::entry_bytes, want_array};
+
+/// Get the path (an array of keys, or one key) as keys.
+fn path(val: &Value) -> Vec<Value> {
+    match val {
+        Value::Array(arr) => arr.values().clone(),
+        val => vec![val.clone()],
+    }
+}
+
+/// Resolve the index of array, a negative one counts from the end.
+fn resolve_index(arr: &Array, index: i64) -> Option<usize> {
+    let index = if index < 0 { index + arr.len() as i64 } else { index };
+    usize::try_from(index).ok().filter(|i| *i < arr.len())
+}
+
+fn key_error(name: &'static str, coll: &Value, key: &Value) -> RuntimeError {
+    error(name, format!(
+        "wants ARRAY with I64 or MAP with STR, got {} with {}", coll.type_name(), key.type_name(),
+    ))
+}
+
+/// Get the element by key, `null` if there is not.
+fn step(name: &'static str, coll: &Value, key: &Value) -> Result<Value, RuntimeError> {
+    match (coll, key) {
+        (Value::Array(arr), Value::I64(index)) => {
+            let val = resolve_index(arr, *index).and_then(|i| arr.get(i));
+            Ok(val.unwrap_or(Value::Null))
+        }
+        (Value::Map(map), Value::Str(key)) => Ok(map.get(key).unwrap_or(Value::Null)),
+        (coll, key) => Err(key_error(name, coll, key)),
+    }
+}
+
+/// Get the element by key, insert an empty map if it is missing from a map.
+fn step_or_insert(runner: &Runner, name: &'static str, coll: &Value, key: &Value) -> Result<Value, RuntimeError> {
+    if let (Value::Map(map), Value::Str(key)) = (coll, key) {
+        if map.get(key).is_none_or(|val| val == Value::Null) {
+            let val = Value::Map(Map::new());
+            put(runner, name, coll, &Value::Str(key.clone()), val.clone())?;
+            return Ok(val);
+        }
+    }
+    step(name, coll, key)
+}
+
+/// Put the value by key in place, an index of array must be in range.
+fn put(runner: &Runner, name: &'static str, coll: &Value, key: &Value, val: Value) -> Result<(), RuntimeError> {
+    check_put(name, coll, &val)?;
+    match (coll, key) {
+        (Value::Array(arr), Value::I64(index)) => {
+            match resolve_index(arr, *index) {
+                Some(i) => {
+                    arr.set(i, val);
+                }
+                None => return Err(error(name, format!("index {} out of range {}", index, arr.len()))),
+            }
+        }
+        (Value::Map(map), Value::Str(key)) => {
+            if !map.contains_key(key) {
+                runner.alloc(entry_bytes(key))?;
+            }
+            map.insert(key.clone(), val);
+        }
+        (coll, key) => return Err(key_error(name, coll, key)),
+    }
+    Ok(())
+}
+
+/// Split the path to the keys leading to the parent and the last key.
+fn split_path<'p>(name: &'static str, path: &'p [Value]) -> Result<(&'p Value, &'p [Value]), RuntimeError> {
+    match path.split_last() {
+        Some(split) => Ok(split),
+        None => Err(error(name, "wants a non-empty path")),
+    }
+}
+
+/// (get coll path)
+pub fn get(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("get", &args, 2, 2)?;
+    let mut val = args[0].clone();
+    for key in path(&args[1]) {
+        val = step("get", &val, &key)?;
+    }
+    Ok(val)
+}
+
+/// (set coll path value), set the value in place and return the coll.
+pub fn set(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("set", &args, 3, 3)?;
+    let path = path(&args[1]);
+    let (last, path) = split_path("set", &path)?;
+    let mut coll = args[0].clone();
+    for key in path {
+        coll = step("set", &coll, key)?;
+    }
+    put(runner, "set", &coll, last, args[2].clone())?;
+    Ok(args[0].clone())
+}
+
+/// (len coll) or (len str)
+pub fn len(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("len", &args, 1, 1)?;
+    let len = match &args[0] {
+        Value::Array(arr) => arr.len(),
+        Value::Map(map) => map.len(),
+        Value::Str(s) => s.chars().count(),
+        val => return Err(error("len", format!("wants ARRAY, MAP or STR, got {}", val.type_name()))),
+    };
+    Ok(Value::I64(len as i64))
+}
+
+/// (get-in coll [key ...] default), the default is `null`.
+pub fn get_in(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("get-in", &args, 2, 3)?;
+    let mut val = args[0].clone();
+    for key in want_array("get-in", &args[1])?.values().iter() {
+        val = step("get-in", &val, key)?;
+    }
+    match (val, args.get(2)) {
+        (Value::Null, Some(default)) => Ok(default.clone()),
+        (val, _) => Ok(val),
+    }
+}
+
+/// (get-in? coll [key ...]), like `get-in` but `null` in the middle of the
+/// path gives `null` instead of an error.
+pub fn get_in_or_null(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("get-in?", &args, 2, 2)?;
+    let mut val = args[0].clone();
+    for key in want_array("get-in?", &args[1])?.values().iter() {
+        if val == Value::Null {
+            break;
+        }
+        val = step("get-in?", &val, key)?;
+    }
+    Ok(val)
+}
+
+/// (assoc-in coll [key ...] value), set the value in place and return the
+/// coll, missing maps in the middle of the path are inserted.
+pub fn assoc_in(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("assoc-in", &args, 3, 3)?;
+    let path = want_array("assoc-in", &args[1])?.values().clone();
+    let (last, path) = split_path("assoc-in", &path)?;
+    let mut coll = args[0].clone();
+    for key in path {
+        coll = step_or_insert(runner, "assoc-in", &coll, key)?;
+    }
+    put(runner, "assoc-in", &coll, last, args[2].clone())?;
+    Ok(args[0].clone())
+}
+
+/// (update-in coll [key ...] f arg ...), set the value to `(f old arg ...)`
+/// in place and return the coll, the old value is `null` if it is missing.
+pub fn update_in(runner: &Runner, mut args: Vec<Value>) -> Result<Value, RuntimeError> {
+    if args.len() < 3 {
+        return Err(error("update-in", format!("wants at least 3 arguments, got {}", args.len())));
+    }
+    let extra = args.split_off(3);
+    let path = want_array("update-in", &args[1])?.values().clone();
+    let (last, path) = split_path("update-in", &path)?;
+    let mut coll = args[0].clone();
+    for key in path {
+        coll = step_or_insert(runner, "update-in", &coll, key)?;
+    }
+    let mut f_args = vec![step("update-in", &coll, last)?];
+    f_args.extend(extra);
+    let val = runner.call(args[2].clone(), f_args)?;
+    put(runner, "update-in", &coll, last, val)?;
+    Ok(args[0].clone())
+}
+
+/// (dissoc-in coll [key ...]), remove the value in place and return the
+/// coll, nothing happens if the path is missing.
+pub fn dissoc_in(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("dissoc-in", &args, 2, 2)?;
+    let path = want_array("dissoc-in", &args[1])?.values().clone();
+    let (last, path) = split_path("dissoc-in", &path)?;
+    let mut coll = args[0].clone();
+    for key in path {
+        coll = step("dissoc-in", &coll, key)?;
+        if coll == Value::Null {
+            return Ok(args[0].clone());
+        }
+    }
+    match (&coll, last) {
+        (Value::Array(arr), Value::I64(index)) => {
+            if let Some(i) = resolve_index(arr, *index) {
+                arr.remove(i);
+            }
+        }
+        (Value::Map(map), Value::Str(key)) => {
+            map.remove(key);
+        }
+        (coll, key) => return Err(key_error("dissoc-in", coll, key)),
+    }
+    Ok(args[0].clone())
+}
@@ -0,0 +1,17 @@
+use crate::{bytecode::{Runner, RuntimeError, XFnContext}, value::Value};
+
+use super::{error, want_str};
+
+/// `(method obj name args ...)`, which `(.name obj args ...)` is compiled to.
+pub fn method(runner: &Runner, mut args: Vec<Value>) -> Result<Value, RuntimeError> {
+    if args.len() < 2 {
+        return Err(error("method", format!("wants at least 2 arguments, got {}", args.len())));
+    }
+    let method_args = args.split_off(2);
+    let name = want_str("method", &args[1])?;
+    match &args[0] {
+        Value::Host(obj) => obj.call_method(&XFnContext::new(runner), name, method_args)
+            .map_err(|err| runner.host_error(format!("{}.{}", obj.type_name(), name), err)),
+        val => Err(error("method", format!("wants HOST, got {}", val.type_name()))),
+    }
+}
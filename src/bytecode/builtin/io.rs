@@ -0,0 +1,115 @@
+use std::{env, fs, io::{self, BufRead}, path::{Path, PathBuf}};
+
+use crate::{bytecode::{capabilities::is_under, Runner, RuntimeError}, value::Value};
+
+use super::{arity, error, str::display, want_str};
+
+fn denied(name: &'static str, msg: String) -> RuntimeError {
+    RuntimeError::PermissionDenied { name, msg }
+}
+
+/// Check the file is under the allowed directories.
+fn check_path(name: &'static str, dirs: &[PathBuf], path: &str) -> Result<(), RuntimeError> {
+    match is_under(dirs, Path::new(path)) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(denied(name, path.to_string())),
+        Err(err) => Err(error(name, format!("{}: {}", path, err))),
+    }
+}
+
+/// (fs/read path), read the file as a string.
+pub fn read(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("fs/read", &args, 1, 1)?;
+    let path = want_str("fs/read", &args[0])?;
+    check_path("fs/read", &runner.capabilities().read_dirs, path)?;
+    let content = fs::read_to_string(path).map_err(|err| error("fs/read", format!("{}: {}", path, err)))?;
+    runner.alloc(content.len())?;
+    Ok(Value::Str(content))
+}
+
+/// (fs/write path content), write the string to the file.
+pub fn write(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("fs/write", &args, 2, 2)?;
+    let path = want_str("fs/write", &args[0])?;
+    let content = want_str("fs/write", &args[1])?;
+    check_path("fs/write", &runner.capabilities().write_dirs, path)?;
+    fs::write(path, content).map_err(|err| error("fs/write", format!("{}: {}", path, err)))?;
+    Ok(Value::Null)
+}
+
+/// (env/get name), the environment variable, or null if it is not set.
+pub fn env_get(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("env/get", &args, 1, 1)?;
+    let name = want_str("env/get", &args[0])?;
+    if !runner.capabilities().can_env(name) {
+        return Err(denied("env/get", name.to_string()));
+    }
+    Ok(env::var(name).map_or(Value::Null, Value::Str))
+}
+
+/// (io/print str), write the string to the output as is.
+pub fn print(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("io/print", &args, 1, 1)?;
+    let content = want_str("io/print", &args[0])?;
+    runner.write_output("io/print", content)?;
+    Ok(Value::Null)
+}
+
+/// Join the string forms of the values with a space.
+fn join(runner: &Runner, args: &[Value]) -> String {
+    let values: Vec<String> = args.iter().map(|arg| display(runner, arg)).collect();
+    values.join(" ")
+}
+
+/// (print value ...), write the string forms of the values.
+pub fn print_values(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    runner.write_output("print", &join(runner, &args))?;
+    Ok(Value::Null)
+}
+
+/// (println value ...), write the string forms of the values and a line
+/// break.
+pub fn println(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let line = join(runner, &args) + "\n";
+    runner.write_output("println", &line)?;
+    Ok(Value::Null)
+}
+
+/// (dbg value), write the value and return it. The calls like `(dbg value)`
+/// write the location too, see [write_dbg], but not the `dbg` called as a
+/// value, like `(map dbg arr)`.
+pub fn dbg(runner: &Runner, mut args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("dbg", &args, 1, 1)?;
+    let line = format!("{}\n", runner.display(&args[0]));
+    runner.write_output("dbg", &line)?;
+    Ok(args.swap_remove(0))
+}
+
+/// Write the value with the location of the `(dbg value)`, which is a const
+/// of the instruction built by the [AsmBuilder](crate::asm::AsmBuilder).
+/// Not inlined, to keep the frame of the runner small.
+#[inline(never)]
+pub fn write_dbg(runner: &Runner, location: &Value, val: &Value) -> Result<(), RuntimeError> {
+    let location = want_str("dbg", location)?;
+    let line = format!("[{}] {}\n", location, runner.display(val));
+    runner.write_output("dbg", &line)
+}
+
+/// (io/read-line), read a line from the stdin without the line break, or
+/// null at the end.
+pub fn read_line(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("io/read-line", &args, 0, 0)?;
+    if !runner.capabilities().stdin {
+        return Err(denied("io/read-line", "stdin".to_string()));
+    }
+    let mut line = String::new();
+    match io::stdin().lock().read_line(&mut line) {
+        Ok(0) => Ok(Value::Null),
+        Ok(_) => {
+            let len = line.trim_end_matches(['\n', '\r']).len();
+            line.truncate(len);
+            Ok(Value::Str(line))
+        }
+        Err(err) => Err(error("io/read-line", err.to_string())),
+    }
+}
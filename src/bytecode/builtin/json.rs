@@ -0,0 +1,36 @@
+use crate::{bytecode::{Runner, RuntimeError}, value::Value};
+
+use super::{arity, error, want_str};
+
+/// (json/parse str), parse the JSON document to a value.
+pub fn parse(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("json/parse", &args, 1, 1)?;
+    let source = want_str("json/parse", &args[0])?;
+    // The containers are counted while they are built, so a huge document
+    // stops at the limit instead of being built first.
+    let mut alloc_error = None;
+    let result = Value::from_json_counted(source, &mut |bytes| match runner.alloc(bytes) {
+        Ok(()) => true,
+        Err(err) => {
+            alloc_error = Some(err);
+            false
+        }
+    });
+    match alloc_error {
+        Some(err) => Err(err),
+        None => result.map_err(|err| error("json/parse", err.to_string())),
+    }
+}
+
+/// (json/stringify value pretty), serialize the value to JSON, indented if
+/// `pretty` is true.
+pub fn stringify(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("json/stringify", &args, 1, 2)?;
+    let pretty = match args.get(1) {
+        None | Some(Value::Bool(false)) => false,
+        Some(Value::Bool(true)) => true,
+        Some(val) => return Err(error("json/stringify", format!("wants BOOL, got {}", val.type_name()))),
+    };
+    let json = if pretty { args[0].to_json_pretty() } else { args[0].to_json() };
+    json.map(Value::Str).map_err(|err| error("json/stringify", err.to_string()))
+}
@@ -0,0 +1,76 @@
+use std::mem::size_of;
+
+use crate::{bytecode::{Runner, RuntimeError}, value::{Array, Value}};
+
+use super::{arity, check_put, error, want_map, want_str};
+
+/// The bytes counted for an entry of map.
+pub fn entry_bytes(key: &str) -> usize {
+    size_of::<(String, Value)>() + key.len()
+}
+
+/// (assoc map key value ...), insert the values in place and return the map.
+pub fn assoc(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    if args.len() < 3 || args.len().is_multiple_of(2) {
+        return Err(error("assoc", "wants a map and pairs of key and value"));
+    }
+    let map = want_map("assoc", &args[0])?;
+    for pair in args[1..].chunks(2) {
+        let key = want_str("assoc", &pair[0])?;
+        check_put("assoc", &args[0], &pair[1])?;
+        if !map.contains_key(key) {
+            runner.alloc(entry_bytes(key))?;
+        }
+        map.insert(key.to_string(), pair[1].clone());
+    }
+    Ok(args[0].clone())
+}
+
+/// (dissoc map key ...), remove the keys in place and return the map.
+pub fn dissoc(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    if args.is_empty() {
+        return Err(error("dissoc", "wants a map and keys"));
+    }
+    let map = want_map("dissoc", &args[0])?;
+    for key in &args[1..] {
+        map.remove(want_str("dissoc", key)?);
+    }
+    Ok(args[0].clone())
+}
+
+/// (keys map), the keys in the insertion order.
+pub fn keys(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("keys", &args, 1, 1)?;
+    let map = want_map("keys", &args[0])?;
+    let keys: Vec<Value> = map.entries().iter().map(|(k, _)| Value::Str(k.clone())).collect();
+    runner.alloc(keys.len() * size_of::<Value>())?;
+    Ok(Value::Array(Array::from(keys)))
+}
+
+/// (vals map), the values in the insertion order.
+pub fn vals(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("vals", &args, 1, 1)?;
+    let map = want_map("vals", &args[0])?;
+    let vals: Vec<Value> = map.entries().iter().map(|(_, v)| v.clone()).collect();
+    runner.alloc(vals.len() * size_of::<Value>())?;
+    Ok(Value::Array(Array::from(vals)))
+}
+
+/// (has? map key) or (has? arr index)
+pub fn has(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("has?", &args, 2, 2)?;
+    let has = match (&args[0], &args[1]) {
+        (Value::Map(map), Value::Str(key)) => map.contains_key(key),
+        (Value::Array(arr), Value::I64(index)) => *index >= 0 && (*index as usize) < arr.len(),
+        (coll, key) => return Err(error("has?", format!(
+            "wants ARRAY with I64 or MAP with STR, got {} with {}", coll.type_name(), key.type_name(),
+        ))),
+    };
+    Ok(Value::Bool(has))
+}
+
+/// (map? value)
+pub fn is_map(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("map?", &args, 1, 1)?;
+    Ok(Value::Bool(matches!(args[0], Value::Map(_))))
+}
@@ -0,0 +1,191 @@
+//! The builtin functions, which are called like other functions but run in
+//! the [Runner] directly.
+
+mod coll;
+mod array;
+mod map;
+mod json;
+mod seq;
+mod str;
+mod re;
+mod time;
+mod host;
+mod io;
+
+use std::mem::size_of;
+
+use crate::value::{Array, Map, Value, MAX_NESTING};
+
+use super::{Runner, RuntimeError};
+
+type BuiltinFn = fn(&Runner, Vec<Value>) -> Result<Value, RuntimeError>;
+
+/// All builtin functions, [Value::Builtin] is the index of it.
+const BUILTINS: &[(&str, BuiltinFn)] = &[
+    ("get", coll::get),
+    ("set", coll::set),
+    ("len", coll::len),
+    ("get-in", coll::get_in),
+    ("get-in?", coll::get_in_or_null),
+    ("assoc-in", coll::assoc_in),
+    ("update-in", coll::update_in),
+    ("dissoc-in", coll::dissoc_in),
+
+    ("push", array::push),
+    ("pop", array::pop),
+    ("slice", array::slice),
+    ("array?", array::is_array),
+
+    ("assoc", map::assoc),
+    ("dissoc", map::dissoc),
+    ("keys", map::keys),
+    ("vals", map::vals),
+    ("has?", map::has),
+    ("map?", map::is_map),
+
+    ("map", seq::map),
+    ("filter", seq::filter),
+    ("reduce", seq::reduce),
+    ("flat-map", seq::flat_map),
+    ("sort", seq::sort),
+    ("sort-by", seq::sort_by),
+    ("group-by", seq::group_by),
+    ("distinct", seq::distinct),
+    ("zip", seq::zip),
+    ("range", seq::range),
+    ("any?", seq::any),
+    ("every?", seq::every),
+    ("find", seq::find),
+    ("take", seq::take),
+    ("drop", seq::drop),
+
+    ("str/concat", str::concat),
+    ("substr", str::substr),
+    ("split", str::split),
+    ("join", str::join),
+    ("trim", str::trim),
+    ("upper", str::upper),
+    ("lower", str::lower),
+    ("starts-with?", str::starts_with),
+    ("contains?", str::contains),
+    ("replace", str::replace),
+    ("format", str::format),
+    ("str", str::str),
+    ("parse-int", str::parse_int),
+    ("parse-float", str::parse_float),
+
+    ("re", re::re),
+    ("re/match", re::find),
+    ("re/find-all", re::find_all),
+    ("re/replace", re::replace),
+    ("re/split", re::split),
+
+    ("time/now", time::now),
+    ("time/parse", time::parse),
+    ("time/format", time::format),
+    ("time/truncate", time::truncate),
+    ("time/instant", time::instant),
+    ("time/millis", time::millis),
+    ("time/ms", time::ms),
+    ("time/seconds", time::seconds),
+    ("time/minutes", time::minutes),
+    ("time/hours", time::hours),
+    ("time/days", time::days),
+
+    ("json/parse", json::parse),
+    ("json/stringify", json::stringify),
+
+    ("method", host::method),
+
+    ("fs/read", io::read),
+    ("fs/write", io::write),
+    ("env/get", io::env_get),
+    ("io/print", io::print),
+    ("io/read-line", io::read_line),
+    ("print", io::print_values),
+    ("println", io::println),
+    ("dbg", io::dbg),
+];
+
+pub use map::entry_bytes as map_entry_bytes;
+pub use io::write_dbg;
+
+/// Find the builtin function by name, return the index of it.
+pub fn lookup(name: &str) -> Option<u32> {
+    BUILTINS.iter().position(|(n, _)| *n == name).map(|i| i as u32)
+}
+
+/// The name of the builtin function by index.
+pub fn name(index: u32) -> &'static str {
+    BUILTINS[index as usize].0
+}
+
+/// Call the builtin function by index.
+pub fn call(runner: &Runner, index: u32, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (_, f) = BUILTINS[index as usize];
+    f(runner, args)
+}
+
+fn error<T: Into<String>>(name: &'static str, msg: T) -> RuntimeError {
+    RuntimeError::Builtin { name, msg: msg.into() }
+}
+
+/// Build a new array of the values, counted as allocated.
+fn new_array(runner: &Runner, values: Vec<Value>) -> Result<Value, RuntimeError> {
+    runner.alloc(values.len() * size_of::<Value>())?;
+    Ok(Value::Array(Array::from(values)))
+}
+
+/// Check the value can be put into the coll: it can not hold the coll, which
+/// would be a cycle, or be nested as deep as [MAX_NESTING].
+fn check_put(name: &'static str, coll: &Value, val: &Value) -> Result<(), RuntimeError> {
+    match val.holds(coll) {
+        Some(false) => Ok(()),
+        Some(true) => Err(error(name, format!("can not put {} into itself", coll.type_name()))),
+        None => Err(error(name, format!("nested deeper than {}", MAX_NESTING))),
+    }
+}
+
+/// Check the number of arguments is in `min..=max`.
+fn arity(name: &'static str, args: &[Value], min: usize, max: usize) -> Result<(), RuntimeError> {
+    if args.len() < min || args.len() > max {
+        let want = if min == max { format!("{}", min) } else { format!("{} to {}", min, max) };
+        return Err(error(name, format!("wants {} arguments, got {}", want, args.len())));
+    }
+    Ok(())
+}
+
+fn want_i64(name: &'static str, val: &Value) -> Result<i64, RuntimeError> {
+    match val {
+        Value::I64(val) => Ok(*val),
+        val => Err(error(name, format!("wants I64, got {}", val.type_name()))),
+    }
+}
+
+fn want_bool(name: &'static str, val: &Value) -> Result<bool, RuntimeError> {
+    match val {
+        Value::Bool(val) => Ok(*val),
+        val => Err(error(name, format!("wants BOOL, got {}", val.type_name()))),
+    }
+}
+
+fn want_str<'v>(name: &'static str, val: &'v Value) -> Result<&'v str, RuntimeError> {
+    match val {
+        Value::Str(val) => Ok(val),
+        val => Err(error(name, format!("wants STR, got {}", val.type_name()))),
+    }
+}
+
+fn want_map<'v>(name: &'static str, val: &'v Value) -> Result<&'v Map, RuntimeError> {
+    match val {
+        Value::Map(map) => Ok(map),
+        val => Err(error(name, format!("wants MAP, got {}", val.type_name()))),
+    }
+}
+
+fn want_array<'v>(name: &'static str, val: &'v Value) -> Result<&'v Array, RuntimeError> {
+    match val {
+        Value::Array(arr) => Ok(arr),
+        val => Err(error(name, format!("wants ARRAY, got {}", val.type_name()))),
+    }
+}
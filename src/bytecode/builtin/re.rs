@@ -0,0 +1,92 @@
+//! The regular expressions, a pattern is a REGEX or a STR to compile. The
+//! literal patterns are compiled once into the consts by the
+//! [AsmBuilder](crate::asm::AsmBuilder).
+
+use crate::{bytecode::{Runner, RuntimeError}, value::{Map, Regex, Value}};
+
+use super::{arity, error, map_entry_bytes, new_array, want_str};
+
+fn want_regex(name: &'static str, val: &Value) -> Result<Regex, RuntimeError> {
+    match val {
+        Value::Regex(re) => Ok(re.clone()),
+        Value::Str(pattern) => Regex::new(pattern).map_err(|err| error(name, err)),
+        val => Err(error(name, format!("wants REGEX or STR, got {}", val.type_name()))),
+    }
+}
+
+fn str_or_null(m: Option<regex::Match>) -> Value {
+    match m {
+        Some(m) => Value::Str(m.as_str().to_string()),
+        None => Value::Null,
+    }
+}
+
+/// The groups of the match, a map by names if the regex has named groups, or
+/// an array of the whole match and the groups.
+fn groups(runner: &Runner, re: &Regex, caps: &regex::Captures) -> Result<Value, RuntimeError> {
+    let names: Vec<&str> = re.inner().capture_names().flatten().collect();
+    if names.is_empty() {
+        return new_array(runner, caps.iter().map(str_or_null).collect());
+    }
+    let map = Map::new();
+    for name in names {
+        runner.alloc(map_entry_bytes(name))?;
+        map.insert(name.to_string(), str_or_null(caps.name(name)));
+    }
+    Ok(Value::Map(map))
+}
+
+/// (re "pattern"), compile the pattern to a REGEX.
+pub fn re(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("re", &args, 1, 1)?;
+    Ok(Value::Regex(want_regex("re", &args[0])?))
+}
+
+/// (re/match pattern str), the groups of the first match, or `null`.
+pub fn find(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("re/match", &args, 2, 2)?;
+    let re = want_regex("re/match", &args[0])?;
+    let str = want_str("re/match", &args[1])?;
+    match re.inner().captures(str) {
+        Some(caps) => groups(runner, &re, &caps),
+        None => Ok(Value::Null),
+    }
+}
+
+/// (re/find-all pattern str), all the matches, each is the matched string if
+/// the regex has no groups, or the groups like `re/match`.
+pub fn find_all(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("re/find-all", &args, 2, 2)?;
+    let re = want_regex("re/find-all", &args[0])?;
+    let str = want_str("re/find-all", &args[1])?;
+    let mut matches = vec![];
+    if re.inner().captures_len() == 1 {
+        for m in re.inner().find_iter(str) {
+            matches.push(Value::Str(m.as_str().to_string()));
+        }
+    } else {
+        for caps in re.inner().captures_iter(str) {
+            matches.push(groups(runner, &re, &caps)?);
+        }
+    }
+    new_array(runner, matches)
+}
+
+/// (re/replace pattern str replacement), replace all the matches, `$1` and
+/// `${name}` in the replacement are the groups.
+pub fn replace(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("re/replace", &args, 3, 3)?;
+    let re = want_regex("re/replace", &args[0])?;
+    let str = want_str("re/replace", &args[1])?;
+    let replacement = want_str("re/replace", &args[2])?;
+    Ok(Value::Str(re.inner().replace_all(str, replacement).into_owned()))
+}
+
+/// (re/split pattern str)
+pub fn split(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("re/split", &args, 2, 2)?;
+    let re = want_regex("re/split", &args[0])?;
+    let str = want_str("re/split", &args[1])?;
+    let parts = re.inner().split(str).map(|part| Value::Str(part.to_string())).collect();
+    new_array(runner, parts)
+}
@@ -0,0 +1,245 @@
+use std::{cmp::Ordering, collections::HashSet, mem::size_of};
+
+use crate::{bytecode::{Runner, RuntimeError}, value::{Array, Map, Value}};
+
+use super::{arity, error, map_entry_bytes, new_array, want_array, want_bool, want_i64, want_str};
+
+/// Call the predicate, which should return a BOOL.
+fn test(runner: &Runner, name: &'static str, f: &Value, val: &Value) -> Result<bool, RuntimeError> {
+    want_bool(name, &runner.call(f.clone(), vec![val.clone()])?)
+}
+
+/// Compare two numbers, or two values of the same type, only I64, F64, STR,
+/// BOOL, INSTANT and DURATION are ordered.
+fn compare(name: &'static str, a: &Value, b: &Value) -> Result<Ordering, RuntimeError> {
+    match (a, b) {
+        (Value::I64(a), Value::I64(b)) => Ok(a.cmp(b)),
+        (Value::F64(a), Value::F64(b)) => Ok(a.total_cmp(b)),
+        (Value::I64(a), Value::F64(b)) => Ok((*a as f64).total_cmp(b)),
+        (Value::F64(a), Value::I64(b)) => Ok(a.total_cmp(&(*b as f64))),
+        (Value::Str(a), Value::Str(b)) => Ok(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Ok(a.cmp(b)),
+        (Value::Instant(a), Value::Instant(b)) | (Value::Duration(a), Value::Duration(b)) => Ok(a.cmp(b)),
+        (a, b) => Err(error(name, format!("can not compare {} with {}", a.type_name(), b.type_name()))),
+    }
+}
+
+/// Sort the values by the keys, stable.
+fn sort_by_keys(name: &'static str, values: Vec<(Value, Value)>) -> Result<Vec<Value>, RuntimeError> {
+    let mut err = None;
+    let mut values = values;
+    values.sort_by(|(a, _), (b, _)| {
+        compare(name, a, b).unwrap_or_else(|e| {
+            err.get_or_insert(e);
+            Ordering::Equal
+        })
+    });
+    match err {
+        Some(err) => Err(err),
+        None => Ok(values.into_iter().map(|(_, val)| val).collect()),
+    }
+}
+
+/// (map f arr)
+pub fn map(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("map", &args, 2, 2)?;
+    let values = want_array("map", &args[1])?.values().clone();
+    let mut result = Vec::with_capacity(values.len());
+    for val in values {
+        result.push(runner.call(args[0].clone(), vec![val])?);
+    }
+    new_array(runner, result)
+}
+
+/// (filter pred arr)
+pub fn filter(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("filter", &args, 2, 2)?;
+    let values = want_array("filter", &args[1])?.values().clone();
+    let mut result = vec![];
+    for val in values {
+        if test(runner, "filter", &args[0], &val)? {
+            result.push(val);
+        }
+    }
+    new_array(runner, result)
+}
+
+/// (reduce f init arr) or (reduce f arr), the first value is the init if it
+/// is not given, and `null` for an empty arr.
+pub fn reduce(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("reduce", &args, 2, 3)?;
+    let values = want_array("reduce", args.last().unwrap())?.values().clone();
+    let mut values = values.into_iter();
+    let mut acc = match args.len() {
+        3 => args[1].clone(),
+        _ => values.next().unwrap_or(Value::Null),
+    };
+    for val in values {
+        acc = runner.call(args[0].clone(), vec![acc, val])?;
+    }
+    Ok(acc)
+}
+
+/// (flat-map f arr), `f` returns an array for each value.
+pub fn flat_map(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("flat-map", &args, 2, 2)?;
+    let values = want_array("flat-map", &args[1])?.values().clone();
+    let mut result = vec![];
+    for val in values {
+        let val = runner.call(args[0].clone(), vec![val])?;
+        result.extend(want_array("flat-map", &val)?.values().iter().cloned());
+    }
+    new_array(runner, result)
+}
+
+/// (sort arr), sort the ordered values ascending.
+pub fn sort(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("sort", &args, 1, 1)?;
+    let values = want_array("sort", &args[0])?.values().iter()
+        .map(|val| (val.clone(), val.clone()))
+        .collect();
+    new_array(runner, sort_by_keys("sort", values)?)
+}
+
+/// (sort-by f arr), sort ascending by the keys from `f`, which is called once
+/// for each value.
+pub fn sort_by(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("sort-by", &args, 2, 2)?;
+    let values = want_array("sort-by", &args[1])?.values().clone();
+    let mut keyed = Vec::with_capacity(values.len());
+    for val in values {
+        keyed.push((runner.call(args[0].clone(), vec![val.clone()])?, val));
+    }
+    new_array(runner, sort_by_keys("sort-by", keyed)?)
+}
+
+/// (group-by f arr), a map from the STR keys from `f` to the arrays of
+/// values, in the order of first seen.
+pub fn group_by(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("group-by", &args, 2, 2)?;
+    let values = want_array("group-by", &args[1])?.values().clone();
+    let groups = Map::new();
+    for val in values {
+        let key = runner.call(args[0].clone(), vec![val.clone()])?;
+        let key = want_str("group-by", &key)?;
+        runner.alloc(size_of::<Value>())?;
+        match groups.get(key) {
+            Some(Value::Array(group)) => group.push(val),
+            _ => {
+                runner.alloc(map_entry_bytes(key))?;
+                groups.insert(key.to_string(), Value::Array(Array::from(vec![val])));
+            }
+        }
+    }
+    Ok(Value::Map(groups))
+}
+
+/// (distinct arr), keep the first one of the equal values.
+pub fn distinct(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("distinct", &args, 1, 1)?;
+    let values = want_array("distinct", &args[0])?.values().clone();
+    // The values are not changed while the set lives, so the hashes hold.
+    #[allow(clippy::mutable_key_type)]
+    let mut seen = HashSet::new();
+    let result = values.into_iter().filter(|val| seen.insert(val.clone())).collect();
+    new_array(runner, result)
+}
+
+/// (zip arr ...), the arrays of the values at the same index, as long as the
+/// shortest arr.
+pub fn zip(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let arrs = args.iter()
+        .map(|arr| want_array("zip", arr))
+        .collect::<Result<Vec<&Array>, RuntimeError>>()?;
+    let len = arrs.iter().map(|arr| arr.len()).min().unwrap_or(0);
+    let mut result = Vec::with_capacity(len);
+    for i in 0..len {
+        let values = arrs.iter().map(|arr| arr.get(i).unwrap()).collect();
+        result.push(new_array(runner, values)?);
+    }
+    new_array(runner, result)
+}
+
+/// (range end), (range start end) or (range start end step), the end is
+/// excluded.
+pub fn range(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("range", &args, 1, 3)?;
+    let (start, end) = match args.len() {
+        1 => (0, want_i64("range", &args[0])?),
+        _ => (want_i64("range", &args[0])?, want_i64("range", &args[1])?),
+    };
+    let step = match args.get(2) {
+        Some(step) => want_i64("range", step)?,
+        None => 1,
+    };
+    if step == 0 {
+        return Err(error("range", "wants a non-zero step"));
+    }
+    let len = if (step > 0 && start < end) || (step < 0 && start > end) {
+        (end.abs_diff(start) - 1) / step.unsigned_abs() + 1
+    } else {
+        0
+    };
+    // Count the bytes before building, so a huge range fails early, even
+    // without the limit of the container bytes.
+    let too_large = || error("range", format!("of {} values is too large", len));
+    let len = usize::try_from(len).map_err(|_| too_large())?;
+    let bytes = len.checked_mul(size_of::<Value>()).filter(|bytes| *bytes <= isize::MAX as usize).ok_or_else(too_large)?;
+    runner.alloc(bytes)?;
+    let mut values = Vec::new();
+    values.try_reserve_exact(len).map_err(|_| too_large())?;
+    values.extend((0..len).map(|i| Value::I64(start + (i as i64) * step)));
+    Ok(Value::Array(Array::from(values)))
+}
+
+/// (any? pred arr)
+pub fn any(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("any?", &args, 2, 2)?;
+    let values = want_array("any?", &args[1])?.values().clone();
+    for val in values {
+        if test(runner, "any?", &args[0], &val)? {
+            return Ok(Value::Bool(true));
+        }
+    }
+    Ok(Value::Bool(false))
+}
+
+/// (every? pred arr)
+pub fn every(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("every?", &args, 2, 2)?;
+    let values = want_array("every?", &args[1])?.values().clone();
+    for val in values {
+        if !test(runner, "every?", &args[0], &val)? {
+            return Ok(Value::Bool(false));
+        }
+    }
+    Ok(Value::Bool(true))
+}
+
+/// (find pred arr), the first value matched, or `null`.
+pub fn find(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("find", &args, 2, 2)?;
+    let values = want_array("find", &args[1])?.values().clone();
+    for val in values {
+        if test(runner, "find", &args[0], &val)? {
+            return Ok(val);
+        }
+    }
+    Ok(Value::Null)
+}
+
+/// (take n arr)
+pub fn take(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("take", &args, 2, 2)?;
+    let n = want_i64("take", &args[0])?.max(0) as usize;
+    let values = want_array("take", &args[1])?.values().iter().take(n).cloned().collect();
+    new_array(runner, values)
+}
+
+/// (drop n arr)
+pub fn drop(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("drop", &args, 2, 2)?;
+    let n = want_i64("drop", &args[0])?.max(0) as usize;
+    let values = want_array("drop", &args[1])?.values().iter().skip(n).cloned().collect();
+    new_array(runner, values)
+}
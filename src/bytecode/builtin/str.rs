@@ -0,0 +1,172 @@
+//! The string functions, which count in characters instead of bytes.
+
+use crate::{bytecode::{Runner, RuntimeError}, value::{Array, Value}};
+
+use super::{arity, error, want_array, want_i64, want_str};
+
+/// The string form of the value, strings are not quoted and the others are
+/// displayed as jisp reads them, see [Runner::display].
+pub(super) fn display(runner: &Runner, val: &Value) -> String {
+    match val {
+        Value::Str(val) => val.clone(),
+        val => runner.display(val).to_string(),
+    }
+}
+
+/// Clamp the character index into `0..=len`, a negative one counts from the
+/// end.
+fn clamp_index(index: i64, len: usize) -> usize {
+    let len = len as i64;
+    let index = if index < 0 { index + len } else { index };
+    index.clamp(0, len) as usize
+}
+
+/// (str/concat str ...)
+pub fn concat(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let mut result = String::new();
+    for arg in &args {
+        result.push_str(want_str("str/concat", arg)?);
+    }
+    Ok(Value::Str(result))
+}
+
+/// (substr str start end), the end is the length by default.
+pub fn substr(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("substr", &args, 2, 3)?;
+    let str = want_str("substr", &args[0])?;
+    let len = str.chars().count();
+    let start = clamp_index(want_i64("substr", &args[1])?, len);
+    let end = match args.get(2) {
+        Some(end) => clamp_index(want_i64("substr", end)?, len),
+        None => len,
+    };
+    let result = str.chars().skip(start).take(end.saturating_sub(start)).collect();
+    Ok(Value::Str(result))
+}
+
+/// (split str sep), split into characters if the sep is empty.
+pub fn split(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("split", &args, 2, 2)?;
+    let str = want_str("split", &args[0])?;
+    let sep = want_str("split", &args[1])?;
+    let parts: Vec<Value> = if sep.is_empty() {
+        str.chars().map(|ch| Value::Str(ch.to_string())).collect()
+    } else {
+        str.split(sep).map(|part| Value::Str(part.to_string())).collect()
+    };
+    runner.alloc(parts.len() * std::mem::size_of::<Value>())?;
+    Ok(Value::Array(Array::from(parts)))
+}
+
+/// (join arr sep), the sep is empty by default.
+pub fn join(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("join", &args, 1, 2)?;
+    let arr = want_array("join", &args[0])?;
+    let sep = match args.get(1) {
+        Some(sep) => want_str("join", sep)?,
+        None => "",
+    };
+    let parts = arr.values().iter()
+        .map(|val| want_str("join", val).map(|s| s.to_string()))
+        .collect::<Result<Vec<String>, RuntimeError>>()?;
+    Ok(Value::Str(parts.join(sep)))
+}
+
+/// (trim str)
+pub fn trim(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("trim", &args, 1, 1)?;
+    Ok(Value::Str(want_str("trim", &args[0])?.trim().to_string()))
+}
+
+/// (upper str)
+pub fn upper(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("upper", &args, 1, 1)?;
+    Ok(Value::Str(want_str("upper", &args[0])?.to_uppercase()))
+}
+
+/// (lower str)
+pub fn lower(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("lower", &args, 1, 1)?;
+    Ok(Value::Str(want_str("lower", &args[0])?.to_lowercase()))
+}
+
+/// (starts-with? str prefix)
+pub fn starts_with(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("starts-with?", &args, 2, 2)?;
+    let str = want_str("starts-with?", &args[0])?;
+    Ok(Value::Bool(str.starts_with(want_str("starts-with?", &args[1])?)))
+}
+
+/// (contains? str part)
+pub fn contains(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("contains?", &args, 2, 2)?;
+    let str = want_str("contains?", &args[0])?;
+    Ok(Value::Bool(str.contains(want_str("contains?", &args[1])?)))
+}
+
+/// (replace str from to), replace all the matches.
+pub fn replace(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("replace", &args, 3, 3)?;
+    let str = want_str("replace", &args[0])?;
+    let from = want_str("replace", &args[1])?;
+    if from.is_empty() {
+        return Err(error("replace", "wants a non-empty pattern"));
+    }
+    Ok(Value::Str(str.replace(from, want_str("replace", &args[2])?)))
+}
+
+/// (format fmt arg ...), replace each `{}` with the string form of the next
+/// arg, `{{` and `}}` are the escaped braces.
+pub fn format(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    if args.is_empty() {
+        return Err(error("format", "wants a format string"));
+    }
+    let fmt = want_str("format", &args[0])?;
+    let mut values = args[1..].iter();
+    let mut result = String::new();
+    let mut chars = fmt.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match (ch, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                result.push(ch);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                match values.next() {
+                    Some(val) => result.push_str(&display(runner, val)),
+                    None => return Err(error("format", "wants more arguments for {}")),
+                }
+            }
+            ('{' | '}', _) => return Err(error("format", format!("unmatched '{}'", ch))),
+            (ch, _) => result.push(ch),
+        }
+    }
+    if values.next().is_some() {
+        return Err(error("format", "got more arguments than {}"));
+    }
+    Ok(Value::Str(result))
+}
+
+/// (str value ...), concat the string forms of the values.
+pub fn str(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let mut result = String::new();
+    for arg in &args {
+        result.push_str(&display(runner, arg));
+    }
+    Ok(Value::Str(result))
+}
+
+/// (parse-int str), `null` if it is not an integer.
+pub fn parse_int(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("parse-int", &args, 1, 1)?;
+    let str = want_str("parse-int", &args[0])?;
+    Ok(str.trim().parse::<i64>().map(Value::I64).unwrap_or(Value::Null))
+}
+
+/// (parse-float str), `null` if it is not a number.
+pub fn parse_float(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("parse-float", &args, 1, 1)?;
+    let str = want_str("parse-float", &args[0])?;
+    Ok(str.trim().parse::<f64>().map(Value::F64).unwrap_or(Value::Null))
+}
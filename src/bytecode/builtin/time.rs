@@ -0,0 +1,99 @@
+use crate::{bytecode::{Runner, RuntimeError}, value::{time, Value}};
+
+use super::{arity, error, want_i64, want_str};
+
+fn want_instant(name: &'static str, val: &Value) -> Result<i64, RuntimeError> {
+    match val {
+        Value::Instant(millis) => Ok(*millis),
+        val => Err(error(name, format!("wants INSTANT, got {}", val.type_name()))),
+    }
+}
+
+/// (time/now), the instant from the [Clock](crate::bytecode::Clock) of the
+/// runner.
+pub fn now(runner: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("time/now", &args, 0, 0)?;
+    Ok(Value::Instant(runner.now_millis()))
+}
+
+/// (time/parse str), parse the ISO 8601 date or date and time.
+pub fn parse(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("time/parse", &args, 1, 1)?;
+    let str = want_str("time/parse", &args[0])?;
+    let millis = time::parse_iso8601(str)
+        .map_err(|msg| error("time/parse", format!("invalid time {:?}: {}", str, msg)))?;
+    Ok(Value::Instant(millis))
+}
+
+/// (time/format instant fmt), RFC 3339 by default, see
+/// [format_instant](time::format_instant) for the directives.
+pub fn format(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("time/format", &args, 1, 2)?;
+    let millis = want_instant("time/format", &args[0])?;
+    let str = match args.get(1) {
+        Some(fmt) => time::format_instant(millis, want_str("time/format", fmt)?)
+            .map_err(|msg| error("time/format", msg))?,
+        None => time::to_iso8601(millis),
+    };
+    Ok(Value::Str(str))
+}
+
+/// (time/truncate instant unit), the start of the year, month, day, hour,
+/// minute or second.
+pub fn truncate(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("time/truncate", &args, 2, 2)?;
+    let millis = want_instant("time/truncate", &args[0])?;
+    let unit = want_str("time/truncate", &args[1])?;
+    let millis = time::truncate(millis, unit).map_err(|msg| error("time/truncate", msg))?;
+    Ok(Value::Instant(millis))
+}
+
+/// (time/instant millis), the instant of the milliseconds since the epoch.
+pub fn instant(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("time/instant", &args, 1, 1)?;
+    Ok(Value::Instant(want_i64("time/instant", &args[0])?))
+}
+
+/// (time/millis instant) or (time/millis duration), the milliseconds since
+/// the epoch or of the duration.
+pub fn millis(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("time/millis", &args, 1, 1)?;
+    match &args[0] {
+        Value::Instant(millis) | Value::Duration(millis) => Ok(Value::I64(*millis)),
+        val => Err(error("time/millis", format!("wants INSTANT or DURATION, got {}", val.type_name()))),
+    }
+}
+
+fn duration(name: &'static str, args: Vec<Value>, unit: i64) -> Result<Value, RuntimeError> {
+    arity(name, &args, 1, 1)?;
+    let n = want_i64(name, &args[0])?;
+    match n.checked_mul(unit) {
+        Some(millis) => Ok(Value::Duration(millis)),
+        None => Err(error(name, format!("{} is out of range", n))),
+    }
+}
+
+/// (time/ms n), the duration of n milliseconds.
+pub fn ms(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    duration("time/ms", args, 1)
+}
+
+/// (time/seconds n)
+pub fn seconds(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    duration("time/seconds", args, time::MILLIS_PER_SECOND)
+}
+
+/// (time/minutes n)
+pub fn minutes(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    duration("time/minutes", args, time::MILLIS_PER_MINUTE)
+}
+
+/// (time/hours n)
+pub fn hours(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    duration("time/hours", args, time::MILLIS_PER_HOUR)
+}
+
+/// (time/days n)
+pub fn days(_: &Runner, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    duration("time/days", args, time::MILLIS_PER_DAY)
+}
@@ -1,11 +1,11 @@
-use crate::value::{Value, XFn};
+use crate::value::Value;
 
 /// The bytecode.
 #[derive(Debug, PartialEq, Eq)]
 pub struct Bytecode {
     pub consts: Vec<Value>, // The all consts.
     pub ifns: Vec<BytecodeFn>, // The inner functions.
-    pub xfns: Vec<XFn>, // The extend functions.
+    pub xfns: Vec<String>, // The names of the host functions, bound by the Engine.
 }
 
 impl Bytecode {
@@ -21,6 +21,7 @@ impl Bytecode {
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct BytecodeFn {
+    pub name: String, // The name, empty for the main function.
     pub locals: u32, // The number of local variables.
     bytes: Vec<u8>,
 }
@@ -28,12 +29,12 @@ pub struct BytecodeFn {
 impl BytecodeFn {
     /// Build a empty [BytecodeFn].
     pub fn new() -> Self {
-        Self { locals: 0, bytes: vec![] }
+        Self { name: String::new(), locals: 0, bytes: vec![] }
     }
 
     /// Build a [BytecodeFn].
     pub fn from<T: Into<Vec<u8>>>(locals: u32, bytes: T) -> Self {
-        Self { locals, bytes: bytes.into() }
+        Self { name: String::new(), locals, bytes: bytes.into() }
     }
 
     /// Push one byte to [Bytecode].
@@ -28,7 +28,8 @@ impl BytecodeBuilder {
                     }
 
                     AS::Ret | AS::Add | AS::Sub | AS::Mul | AS::Div | AS::Eq |
-                    AS::Ne | AS::Lt | AS::Le | AS::Gt | AS::Ge | AS::Pop => {
+                    AS::Ne | AS::Lt | AS::Le | AS::Gt | AS::Ge | AS::Pop |
+                    AS::TryEnd => {
                         cur_offset += 1;
                     }
 
@@ -38,13 +39,16 @@ impl BytecodeBuilder {
 
                     AS::Load { index: _ } | AS::Store { index: _ } |
                     AS::Jump { label: _ } | AS::JumpFalse { label: _ } |
-                    AS::PushConst { index: _ } | AS::Call { args: _ } => {
+                    AS::TryBegin { label: _ } |
+                    AS::PushConst { index: _ } | AS::Call { args: _ } | AS::Dbg { index: _ } |
+                    AS::MakeArray { len: _ } | AS::MakeMap { len: _ } => {
                         cur_offset += 1 + 4;
                     }
                 }
             }
 
             let mut bcfn = BytecodeFn::new();
+            bcfn.name = func.name.clone();
             bcfn.locals = func.locals;
             for stmt in &func.statements {
                 match stmt {
@@ -83,20 +87,40 @@ impl BytecodeBuilder {
                     },
 
                     AS::Jump { label } => {
-                        let offset = label_to_offset[&label];
+                        let offset = label_to_offset[label];
                         bcfn.push_byte(ins::JUMP);
                         bcfn.push_bytes(&offset.to_le_bytes());
                     },
                     AS::JumpFalse { label } => {
-                        let offset = label_to_offset[&label];
+                        let offset = label_to_offset[label];
                         bcfn.push_byte(ins::JUMP_FALSE);
                         bcfn.push_bytes(&offset.to_le_bytes());
                     },
 
+                    AS::TryBegin { label } => {
+                        let offset = label_to_offset[label];
+                        bcfn.push_byte(ins::TRY_BEGIN);
+                        bcfn.push_bytes(&offset.to_le_bytes());
+                    }
+                    AS::TryEnd => bcfn.push_byte(ins::TRY_END),
+
                     AS::Call { args: num } => {
                         bcfn.push_byte(ins::CALL);
                         bcfn.push_bytes(&num.to_le_bytes());
                     }
+                    AS::Dbg { index } => {
+                        bcfn.push_byte(ins::DBG);
+                        bcfn.push_bytes(&index.to_le_bytes());
+                    }
+
+                    AS::MakeArray { len } => {
+                        bcfn.push_byte(ins::MAKE_ARRAY);
+                        bcfn.push_bytes(&len.to_le_bytes());
+                    }
+                    AS::MakeMap { len } => {
+                        bcfn.push_byte(ins::MAKE_MAP);
+                        bcfn.push_bytes(&len.to_le_bytes());
+                    }
                 }
             }
             bytecode.ifns.push(bcfn);
@@ -206,4 +230,23 @@ mod tests {
         ]));
         assert_eq!(bytecode, wanted);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn array() {
+        let mut asm = Asm::new();
+        asm.push_fn(AsmFn::new(0, vec![
+            AsmStatement::PushI64 { val: 1 },
+            AsmStatement::MakeArray { len: 1 },
+            AsmStatement::Ret,
+        ]));
+        let bytecode_builder = BytecodeBuilder::new(asm);
+        let bytecode = bytecode_builder.build();
+        let mut wanted = Bytecode::new();
+        wanted.ifns.push(BytecodeFn::from(0, [
+            /* off: 0x00 = 00 */ ins::PUSH_I64, 0x01, 0, 0, 0, 0, 0, 0, 0,
+            /* off: 0x09 = 09 */ ins::MAKE_ARRAY, 0x01, 0, 0, 0,
+            /* off: 0x0e = 14 */ ins::RET,
+        ]));
+        assert_eq!(bytecode, wanted);
+    }
+}
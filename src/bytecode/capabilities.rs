@@ -0,0 +1,67 @@
+use std::{fs, io, path::{Path, PathBuf}};
+
+/// What the I/O builtins like `fs/read` may touch. Nothing is allowed by
+/// default, so the scripts of the end users are sandboxed.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Capabilities {
+    /// The directories under which `fs/read` can read the files, and the
+    /// modules can be imported from by the [Engine](crate::engine::Engine).
+    pub read_dirs: Vec<PathBuf>,
+
+    /// The directories under which `fs/write` can write the files.
+    pub write_dirs: Vec<PathBuf>,
+
+    /// The environment variables `env/get` can read.
+    pub env_vars: Vec<String>,
+
+    /// Whether `env/get` can read all the environment variables.
+    pub all_env_vars: bool,
+
+    /// Whether `io/print` can write to the stdout.
+    pub stdout: bool,
+
+    /// Whether `io/read-line` can read from the stdin.
+    pub stdin: bool,
+}
+
+impl Capabilities {
+    /// Build a [Capabilities] which allows nothing.
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Build a [Capabilities] which allows all, for the trusted scripts.
+    pub fn all() -> Self {
+        Self {
+            read_dirs: vec![PathBuf::from("/")],
+            write_dirs: vec![PathBuf::from("/")],
+            env_vars: vec![],
+            all_env_vars: true,
+            stdout: true,
+            stdin: true,
+        }
+    }
+
+    pub(crate) fn can_env(&self, name: &str) -> bool {
+        self.all_env_vars || self.env_vars.iter().any(|var| var == name)
+    }
+}
+
+/// Whether the file is under one of the directories, after the links and
+/// the `..` are resolved. The file to write may not exist, but its
+/// directory should.
+pub(crate) fn is_under(dirs: &[PathBuf], path: &Path) -> io::Result<bool> {
+    let path = match fs::canonicalize(path) {
+        Ok(path) => path,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            let (parent, name) = match (path.parent(), path.file_name()) {
+                (Some(parent), Some(name)) => (parent, name),
+                _ => return Err(err),
+            };
+            let parent = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+            fs::canonicalize(parent)?.join(name)
+        }
+        Err(err) => return Err(err),
+    };
+    Ok(dirs.iter().any(|dir| fs::canonicalize(dir).is_ok_and(|dir| path.starts_with(dir))))
+}
@@ -0,0 +1,33 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The source of the current time for `time/now`, so the host can make the
+/// runs deterministic.
+pub trait Clock {
+    /// The milliseconds since `1970-01-01T00:00:00Z`.
+    fn now_millis(&self) -> i64;
+}
+
+/// The [Clock] of the system time, used by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_millis(&self) -> i64 {
+        match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(since) => since.as_millis() as i64,
+            Err(err) => -(err.duration().as_millis() as i64),
+        }
+    }
+}
+
+/// The [Clock] which always returns the same time.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock {
+    pub millis: i64,
+}
+
+impl Clock for FixedClock {
+    fn now_millis(&self) -> i64 {
+        self.millis
+    }
+}
@@ -0,0 +1,34 @@
+use std::any::Any;
+
+use crate::value::Value;
+
+use super::{Runner, RunnerLimits, RuntimeError};
+
+/// What a [XFn](crate::value::XFn) can reach of the [Runner] calling it.
+pub struct XFnContext<'r> {
+    runner: &'r Runner,
+}
+
+impl<'r> XFnContext<'r> {
+    pub(crate) fn new(runner: &'r Runner) -> Self {
+        Self { runner }
+    }
+
+    /// Call the function value passed to the host, like a jisp `fn`. The
+    /// call counts towards the [RunnerLimits] of the run.
+    ///
+    /// The error can be returned as is by `?`, it is raised in jisp unchanged.
+    pub fn call(&self, func: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        self.runner.call(func, args)
+    }
+
+    /// The [RunnerLimits] of the run.
+    pub fn limits(&self) -> &RunnerLimits {
+        self.runner.limits()
+    }
+
+    /// The data of the type `T` set by [Runner::set_data].
+    pub fn data<T: Any>(&self) -> Option<&T> {
+        self.runner.data::<T>()
+    }
+}
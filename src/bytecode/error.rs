@@ -0,0 +1,101 @@
+use std::fmt::Display;
+
+use crate::value::{HostError, Map, Value};
+
+use super::limits::Limit;
+
+/// The error raised while running the [Bytecode](super::Bytecode).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum RuntimeError {
+    /// One of the [RunnerLimits](super::RunnerLimits) is exceeded.
+    LimitExceeded(Limit),
+
+    /// The [InterruptHandle](super::InterruptHandle) is interrupted.
+    Cancelled,
+
+    /// The deadline of the [Runner](super::Runner) is passed.
+    TimedOut,
+
+    /// The value does not have the wanted type.
+    Type { want: &'static str, got: &'static str },
+
+    /// The builtin function failed.
+    Builtin { name: &'static str, msg: String },
+
+    /// The I/O builtin is not allowed by the [Capabilities](super::Capabilities).
+    PermissionDenied { name: &'static str, msg: String },
+
+    /// The [XFn](crate::value::XFn) failed, including the wrong arguments.
+    /// The call site is known once the error leaves the [XFn].
+    XFn { name: String, error: HostError, at: Option<CallSite> },
+}
+
+/// Where a function is called in the [Bytecode](super::Bytecode).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CallSite {
+    pub func: usize, // The index of the function, `0` is the main function.
+    pub name: String, // The name of the function, empty for the main function.
+    pub offset: usize, // The offset of the `CALL` instruction.
+}
+
+impl RuntimeError {
+    /// Set the call site of the [RuntimeError::XFn] if it is not known yet.
+    pub(crate) fn called_at(self, site: CallSite) -> Self {
+        match self {
+            RuntimeError::XFn { name, error, at: None } => RuntimeError::XFn { name, error, at: Some(site) },
+            err => err,
+        }
+    }
+
+    /// The error as a value for `catch`, or [None] if it stops the run and
+    /// can not be caught, like [RuntimeError::LimitExceeded].
+    pub(crate) fn to_value(&self) -> Option<Value> {
+        let map = Map::new();
+        let (kind, name) = match self {
+            RuntimeError::Type { .. } => ("type", None),
+            RuntimeError::Builtin { name, .. } => ("builtin", Some(name.to_string())),
+            RuntimeError::PermissionDenied { name, .. } => ("permission", Some(name.to_string())),
+            RuntimeError::XFn { name, .. } => ("host", Some(name.clone())),
+            RuntimeError::LimitExceeded(_) | RuntimeError::Cancelled | RuntimeError::TimedOut => return None,
+        };
+        let msg = match self {
+            RuntimeError::Builtin { msg, .. } => msg.clone(),
+            RuntimeError::PermissionDenied { msg, .. } => format!("permission denied: {}", msg),
+            RuntimeError::XFn { error, .. } => error.msg().to_string(),
+            err => err.to_string(),
+        };
+        map.insert("type".to_string(), Value::Str(kind.to_string()));
+        if let Some(name) = name {
+            map.insert("name".to_string(), Value::Str(name));
+        }
+        map.insert("message".to_string(), Value::Str(msg));
+        Some(Value::Map(map))
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimeError::LimitExceeded(limit) => match limit {
+                Limit::CallDepth(max) => write!(f, "call depth exceeds the limit {}", max),
+                Limit::Instructions(max) => write!(f, "executed instructions exceed the limit {}", max),
+                Limit::StackSize(max) => write!(f, "stack size exceeds the limit {}", max),
+                Limit::ContainerBytes(max) => write!(f, "container bytes exceed the limit {}", max),
+            },
+            RuntimeError::Cancelled => write!(f, "cancelled"),
+            RuntimeError::TimedOut => write!(f, "timed out"),
+            RuntimeError::Type { want, got } => write!(f, "want {}, got {}", want, got),
+            RuntimeError::Builtin { name, msg } => write!(f, "{}: {}", name, msg),
+            RuntimeError::PermissionDenied { name, msg } => write!(f, "{}: permission denied: {}", name, msg),
+            RuntimeError::XFn { name, error, at: None } => write!(f, "{}: {}", name, error),
+            RuntimeError::XFn { name, error, at: Some(at) } if at.name.is_empty() => {
+                write!(f, "{}: {} (called in the main fn)", name, error)
+            }
+            RuntimeError::XFn { name, error, at: Some(at) } => {
+                write!(f, "{}: {} (called in fn {})", name, error, at.name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
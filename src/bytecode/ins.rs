@@ -21,4 +21,12 @@ pub const LOAD: u8 = 0x31;
 pub const JUMP: u8 = 0x40;
 pub const JUMP_FALSE: u8 = 0x41;
 
-pub const CALL: u8 = 0x50;
\ No newline at end of file
+pub const CALL: u8 = 0x50;
+
+pub const TRY_BEGIN: u8 = 0x51;
+pub const TRY_END: u8 = 0x52;
+
+pub const DBG: u8 = 0x53;
+
+pub const MAKE_ARRAY: u8 = 0x60;
+pub const MAKE_MAP: u8 = 0x61;
\ No newline at end of file
@@ -0,0 +1,30 @@
+use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+
+/// The handle to stop a running [Runner](super::Runner) from another thread.
+///
+/// The runner checks the handle every [CHECK_INTERVAL] instructions and
+/// before every call, then stops with [RuntimeError::Cancelled](super::RuntimeError::Cancelled).
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHandle {
+    interrupted: Arc<AtomicBool>,
+}
+
+/// The number of instructions between two checks of interrupt and deadline.
+pub const CHECK_INTERVAL: u64 = 1024;
+
+impl InterruptHandle {
+    /// Build a new [InterruptHandle].
+    pub fn new() -> Self {
+        Self { interrupted: Arc::new(AtomicBool::new(false)) }
+    }
+
+    /// Ask the runner to stop.
+    pub fn interrupt(&self) {
+        self.interrupted.store(true, Ordering::Relaxed);
+    }
+
+    /// Return true if [InterruptHandle::interrupt] is called.
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupted.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,55 @@
+/// The default maximum call depth.
+///
+/// Every jisp call is a native call of the [Runner](super::Runner), so the
+/// depth is limited by default to keep the host's stack from overflowing. It
+/// fits the stack of a spawned thread (2 MiB by default), even for a debug
+/// build and through the builtins like `map` calling back into jisp.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 256;
+
+/// The limits of a [Runner](super::Runner).
+///
+/// `None` means there is no limit.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RunnerLimits {
+    /// The maximum number of nested calls, including the main function.
+    pub max_call_depth: Option<usize>,
+
+    /// The maximum number of executed instructions (the fuel).
+    pub max_instructions: Option<u64>,
+
+    /// The maximum number of values on the stacks of all frames.
+    pub max_stack_size: Option<usize>,
+
+    /// The maximum number of bytes allocated by containers.
+    pub max_container_bytes: Option<usize>,
+}
+
+impl RunnerLimits {
+    /// Build a [RunnerLimits] without any limit.
+    pub fn unlimited() -> Self {
+        Self {
+            max_call_depth: None,
+            max_instructions: None,
+            max_stack_size: None,
+            max_container_bytes: None,
+        }
+    }
+}
+
+impl Default for RunnerLimits {
+    fn default() -> Self {
+        Self {
+            max_call_depth: Some(DEFAULT_MAX_CALL_DEPTH),
+            ..Self::unlimited()
+        }
+    }
+}
+
+/// The limit which is exceeded.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Limit {
+    CallDepth(usize),
+    Instructions(u64),
+    StackSize(usize),
+    ContainerBytes(usize),
+}
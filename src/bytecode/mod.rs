@@ -2,7 +2,28 @@ mod bytecode;
 mod bytecode_builder;
 mod runner;
 mod ins;
+mod limits;
+mod error;
+mod interrupt;
+mod clock;
+mod context;
+mod capabilities;
+mod builtin;
 
 pub type Bytecode = bytecode::Bytecode;
 pub type BytecodeBuilder = bytecode_builder::BytecodeBuilder;
-pub type Runner = runner::Runner;
\ No newline at end of file
+pub type Runner = runner::Runner;
+pub type RunnerLimits = limits::RunnerLimits;
+pub type Limit = limits::Limit;
+pub type RuntimeError = error::RuntimeError;
+pub type CallSite = error::CallSite;
+pub type InterruptHandle = interrupt::InterruptHandle;
+pub type Capabilities = capabilities::Capabilities;
+pub use limits::DEFAULT_MAX_CALL_DEPTH;
+pub use interrupt::CHECK_INTERVAL;
+pub use clock::{Clock, FixedClock, SystemClock};
+pub type XFnContext<'r> = context::XFnContext<'r>;
+pub use builtin::lookup as lookup_builtin;
+pub(crate) use builtin::name as builtin_name;
+pub(crate) use builtin::map_entry_bytes;
+pub(crate) use capabilities::is_under;
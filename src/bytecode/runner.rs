@@ -1,36 +1,266 @@
-use crate::value::Value;
+use std::{any::{Any, TypeId}, cell::{Cell, RefCell}, cmp::Ordering, collections::HashMap, io::{self, Write}, mem::size_of, rc::Rc, time::{Duration, Instant}};
 
-use super::{bytecode::BytecodeFn, ins, Bytecode};
+use crate::value::{Array, HostError, Map, Value, ValueDisplay, XFn};
+
+use super::{builtin, bytecode::BytecodeFn, ins, Bytecode, CallSite, Capabilities, Clock, InterruptHandle, SystemClock, Limit, RunnerLimits, RuntimeError, XFnContext, CHECK_INTERVAL};
 
 /// The [Bytecode] runner.
 pub struct Runner {
     bytecode: Bytecode,
+    limits: RunnerLimits,
+    interrupt: InterruptHandle,
+    deadline: Option<Instant>,
+    clock: Box<dyn Clock>,
+    capabilities: Capabilities,
+    output: RefCell<Option<Box<dyn Write>>>, // The sink of the output builtins, or the stdout.
+    xfns: Vec<Rc<XFn>>, // The host functions bound to the names in the bytecode.
+    data: HashMap<TypeId, Rc<dyn Any>>, // The data for the host functions, by type.
+
+    depth: Cell<usize>, // The number of running frames.
+    instructions: Cell<u64>, // The number of executed instructions.
+    stack_size: Cell<usize>, // The number of values on all stacks.
+    container_bytes: Cell<usize>, // The bytes allocated by containers in total.
 }
 
 impl Runner {
-    /// Build a [Runner].
+    /// Build a [Runner] with the default [RunnerLimits].
     pub fn new(bytecode: Bytecode) -> Self {
-        Self { bytecode }
+        Self::with_limits(bytecode, RunnerLimits::default())
+    }
+
+    /// Build a [Runner] with the given [RunnerLimits].
+    pub fn with_limits(bytecode: Bytecode, limits: RunnerLimits) -> Self {
+        Self {
+            bytecode,
+            limits,
+            interrupt: InterruptHandle::new(),
+            deadline: None,
+            clock: Box::new(SystemClock),
+            capabilities: Capabilities::none(),
+            output: RefCell::new(None),
+            xfns: vec![],
+            data: HashMap::new(),
+
+            depth: Cell::new(0),
+            instructions: Cell::new(0),
+            stack_size: Cell::new(0),
+            container_bytes: Cell::new(0),
+        }
+    }
+
+    /// Get the [InterruptHandle] to stop this runner from another thread.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+
+    /// Stop running with [RuntimeError::TimedOut] once the deadline is passed.
+    pub fn set_deadline(&mut self, deadline: Instant) {
+        self.deadline = Some(deadline);
+    }
+
+    /// Stop running with [RuntimeError::TimedOut] once the timeout is passed
+    /// from now.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.set_deadline(Instant::now() + timeout);
+    }
+
+    /// Use the [Clock] for `time/now` instead of the [SystemClock].
+    pub fn set_clock<C: Clock + 'static>(&mut self, clock: C) {
+        self.clock = Box::new(clock);
+    }
+
+    /// Allow the I/O builtins by the [Capabilities], nothing is allowed by
+    /// default.
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.capabilities = capabilities;
+    }
+
+    /// The [Capabilities] of this runner.
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    /// Write the output of `print`, `println`, `dbg` and `io/print` to the
+    /// sink instead of the stdout, which is allowed without
+    /// [Capabilities::stdout].
+    pub fn set_output<W: Write + 'static>(&mut self, output: W) {
+        self.output = RefCell::new(Some(Box::new(output)));
+    }
+
+    /// Write the content to the output sink, or to the stdout if it is
+    /// allowed.
+    pub(crate) fn write_output(&self, name: &'static str, content: &str) -> Result<(), RuntimeError> {
+        let mut output = self.output.borrow_mut();
+        let result = match output.as_mut() {
+            Some(output) => output.write_all(content.as_bytes()).and_then(|_| output.flush()),
+            None if self.capabilities.stdout => {
+                let mut stdout = io::stdout().lock();
+                stdout.write_all(content.as_bytes()).and_then(|_| stdout.flush())
+            }
+            None => return Err(RuntimeError::PermissionDenied { name, msg: "stdout".to_string() }),
+        };
+        result.map_err(|err| RuntimeError::Builtin { name, msg: err.to_string() })
+    }
+
+    /// Display the value as jisp reads it, with the names of the functions
+    /// in the bytecode.
+    pub fn display<'a>(&'a self, val: &'a Value) -> ValueDisplay<'a> {
+        ValueDisplay::new(val, Some(&self.bytecode))
+    }
+
+    /// The [RunnerLimits] of this runner.
+    pub fn limits(&self) -> &RunnerLimits {
+        &self.limits
+    }
+
+    /// Attach the data for the host functions, which read it by
+    /// [XFnContext::data]. There is one value per type, the old one is
+    /// replaced. Use a [Cell](std::cell::Cell) or [RefCell](std::cell::RefCell)
+    /// for the data they change.
+    pub fn set_data<T: Any>(&mut self, data: T) {
+        self.data.insert(TypeId::of::<T>(), Rc::new(data));
+    }
+
+    /// Bind the host functions of the names in the bytecode, in order, and
+    /// share the data of the [Engine](crate::engine::Engine).
+    pub(crate) fn bind(&mut self, xfns: Vec<Rc<XFn>>, data: HashMap<TypeId, Rc<dyn Any>>) {
+        self.xfns = xfns;
+        self.data.extend(data);
+    }
+
+    /// The data of the type `T` set by [Runner::set_data].
+    pub fn data<T: Any>(&self) -> Option<&T> {
+        self.data.get(&TypeId::of::<T>()).and_then(|data| data.downcast_ref())
+    }
+
+    /// The milliseconds since the epoch from the [Clock].
+    pub(crate) fn now_millis(&self) -> i64 {
+        self.clock.now_millis()
     }
 
     /// Run the bytecode as eval those code.
-    pub fn run(self) -> Value {
-        self.run_frame(0, vec![])
+    pub fn run(&self) -> Result<Value, RuntimeError> {
+        self.run_with(vec![])
+    }
+
+    /// Run the bytecode with the values of the parameters declared by
+    /// [AsmBuilder::declare_param](crate::asm::AsmBuilder::declare_param).
+    ///
+    /// The runner can run many times, like once per line of input, and the
+    /// [RunnerLimits] are counted from zero for each run.
+    pub fn run_with(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        self.depth.set(0);
+        self.instructions.set(0);
+        self.stack_size.set(0);
+        self.container_bytes.set(0);
+        self.run_frame(0, args)
+    }
+
+    fn run_frame(&self, index: usize, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        if let Some(max) = self.limits.max_call_depth {
+            if self.depth.get() >= max {
+                return Err(RuntimeError::LimitExceeded(Limit::CallDepth(max)));
+            }
+        }
+        self.check_stop()?;
+        self.depth.set(self.depth.get() + 1);
+        let frame = RunnerFrame::new(self, index, args);
+        let result = frame.run();
+        self.depth.set(self.depth.get() - 1);
+        result
+    }
+
+    /// Call the function value with the arguments.
+    pub(crate) fn call(&self, func: Value, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        match func {
+            Value::IFn(index) => self.run_frame(index as usize, args),
+            Value::XFn(index) => self.call_xfn(index as usize, args),
+            Value::Builtin(index) => builtin::call(self, index, args),
+            val => Err(RuntimeError::Type { want: "FN", got: val.type_name() }),
+        }
+    }
+
+    /// Call the host function, out of [Runner::call] to keep the stack frame
+    /// of the jisp calls small.
+    #[inline(never)]
+    fn call_xfn(&self, index: usize, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        let name = &self.bytecode.xfns[index];
+        let xfn = match self.xfns.get(index) {
+            Some(xfn) => xfn,
+            None => return Err(RuntimeError::XFn { name: name.clone(), error: HostError::new("is not bound"), at: None }),
+        };
+        xfn.call(&XFnContext::new(self), args).map_err(|error| self.host_error(name.clone(), error))
+    }
+
+    /// The [RuntimeError] of the [HostError] raised by the host function or
+    /// method with the name.
+    pub(crate) fn host_error(&self, name: String, error: HostError) -> RuntimeError {
+        match error.cause {
+            // The error of a callback into jisp is raised unchanged.
+            Some(cause) => *cause,
+            None => RuntimeError::XFn { name, error, at: None },
+        }
+    }
+
+    /// Count the bytes allocated by containers.
+    pub(crate) fn alloc(&self, bytes: usize) -> Result<(), RuntimeError> {
+        let container_bytes = self.container_bytes.get().saturating_add(bytes);
+        if let Some(max) = self.limits.max_container_bytes {
+            if container_bytes > max {
+                return Err(RuntimeError::LimitExceeded(Limit::ContainerBytes(max)));
+            }
+        }
+        self.container_bytes.set(container_bytes);
+        Ok(())
+    }
+
+    /// Count one executed instruction.
+    fn tick(&self) -> Result<(), RuntimeError> {
+        let instructions = self.instructions.get() + 1;
+        if let Some(max) = self.limits.max_instructions {
+            if instructions > max {
+                return Err(RuntimeError::LimitExceeded(Limit::Instructions(max)));
+            }
+        }
+        self.instructions.set(instructions);
+        if instructions.is_multiple_of(CHECK_INTERVAL) {
+            self.check_stop()?;
+        }
+        Ok(())
     }
 
-    fn run_frame(&self, index: usize, args: Vec<Value>) -> Value {
-        let frame = RunnerFrame::new(&self, index, args);
-        frame.run()
+    /// Check if the runner is interrupted or timed out.
+    fn check_stop(&self) -> Result<(), RuntimeError> {
+        if self.interrupt.is_interrupted() {
+            return Err(RuntimeError::Cancelled);
+        }
+        if let Some(deadline) = self.deadline {
+            if Instant::now() >= deadline {
+                return Err(RuntimeError::TimedOut);
+            }
+        }
+        Ok(())
     }
 }
 
+/// Where [RunnerFrame::run_until_error] stops.
+enum RunnerStep {
+    /// The function returns the value.
+    Ret(Value),
+
+    /// The CALL of the function with the arguments, made by [RunnerFrame::run].
+    Call(Value, Vec<Value>),
+}
+
 pub struct RunnerFrame<'r> {
     runner: &'r Runner,
 
+    index: usize, // The index of the function running.
     func: &'r BytecodeFn, // The function running.
     pc: usize, // The program counter.
-    stack: RunnerStack, // The stack.
+    stack: RunnerStack<'r>, // The stack.
     locals: RunnerLocals, // The local variables.
+    handlers: Vec<(usize, usize)>, // The `catch` offsets and stack sizes of `try`, the innermost is the last.
 }
 
 impl<'r> RunnerFrame<'r> {
@@ -45,34 +275,74 @@ impl<'r> RunnerFrame<'r> {
         Self {
             runner,
 
+            index,
             func,
             pc: 0,
-            stack: RunnerStack::new(),
+            stack: RunnerStack::new(&runner.stack_size, runner.limits.max_stack_size),
             locals,
+            handlers: vec![],
         }
     }
 
     /// Run the bytecode as eval those code.
-    pub fn run(mut self) -> Value {
+    ///
+    /// The calls are made here instead of in [RunnerFrame::run_until_error],
+    /// whose frame is large in the debug builds, so the native stack of a
+    /// jisp call stays small.
+    pub fn run(mut self) -> Result<Value, RuntimeError> {
+        loop {
+            let err = match self.run_until_error() {
+                Ok(RunnerStep::Ret(val)) => return Ok(val),
+                Ok(RunnerStep::Call(func, args)) => match self.call(func, args) {
+                    Ok(()) => continue,
+                    Err(err) => err,
+                },
+                Err(err) => err,
+            };
+            // Go to the innermost `catch` with the stack as it was at `try`.
+            match (err.to_value(), self.handlers.pop()) {
+                (Some(val), Some((pc, size))) => {
+                    self.stack.truncate(size);
+                    self.stack.push(val)?;
+                    self.pc = pc;
+                }
+                _ => return Err(err),
+            }
+        }
+    }
+
+    /// Call the function of the CALL at the program counter, and push the
+    /// result.
+    fn call(&mut self, func: Value, args: Vec<Value>) -> Result<(), RuntimeError> {
+        let res = self.runner.call(func, args).map_err(|err| {
+            err.called_at(CallSite { func: self.index, name: self.func.name.clone(), offset: self.pc })
+        })?;
+        self.stack.push(res)?;
+        self.pc += 5;
+        Ok(())
+    }
+
+    fn run_until_error(&mut self) -> Result<RunnerStep, RuntimeError> {
         let bytes = self.func.bytes();
         loop {
+            self.runner.tick()?;
             let byte = bytes[self.pc];
             match byte {
                 ins::RET => {
                     self.pc += 1;
-                    return self.stack.pop();
+                    return Ok(RunnerStep::Ret(self.stack.pop()));
                 },
 
                 ins::PUSH_I64 => {
                     let val = &bytes[self.pc+1..self.pc+9];
                     let val = i64::from_le_bytes(val.try_into().unwrap());
-                    self.stack.push_i64(val);
+                    self.stack.push_i64(val)?;
                     self.pc += 9;
                 },
                 ins::PUSH_CONST => {
                     let index = &bytes[self.pc+1..self.pc+5];
                     let index = u32::from_le_bytes(index.try_into().unwrap());
-                    self.stack.push(self.runner.bytecode.consts[index as usize].clone());
+                    self.stack.push(self.runner.bytecode.consts[index as usize].clone())?;
                     self.pc += 5;
                 },
                 ins::POP => {
@@ -81,70 +351,70 @@ impl<'r> RunnerFrame<'r> {
                 }
 
                 ins::ADD => {
-                    let second = self.stack.pop_i64();
-                    let first = self.stack.pop_i64();
-                    self.stack.push_i64(first + second);
+                    let second = self.stack.pop();
+                    let first = self.stack.pop();
+                    self.stack.push(add(first, second)?)?;
                     self.pc += 1;
                 },
                 ins::SUB => {
-                    let second = self.stack.pop_i64();
-                    let first = self.stack.pop_i64();
-                    self.stack.push_i64(first - second);
+                    let second = self.stack.pop();
+                    let first = self.stack.pop();
+                    self.stack.push(sub(first, second)?)?;
                     self.pc += 1;
                 },
                 ins::MUL => {
-                    let second = self.stack.pop_i64();
-                    let first = self.stack.pop_i64();
-                    self.stack.push_i64(first * second);
+                    let second = self.stack.pop();
+                    let first = self.stack.pop();
+                    self.stack.push(arith("*", first, second, i64::checked_mul, |a, b| a * b)?)?;
                     self.pc += 1;
                 },
                 ins::DIV => {
-                    let second = self.stack.pop_i64();
-                    let first = self.stack.pop_i64();
-                    self.stack.push_i64(first / second);
+                    let second = self.stack.pop();
+                    let first = self.stack.pop();
+                    self.stack.push(div(first, second)?)?;
                     self.pc += 1;
                 },
                 ins::EQ => {
-                    let second = self.stack.pop_i64();
-                    let first = self.stack.pop_i64();
-                    self.stack.push_bool(first == second);
+                    let second = self.stack.pop();
+                    let first = self.stack.pop();
+                    self.stack.push_bool(first == second)?;
                     self.pc += 1;
                 }
                 ins::NE => {
-                    let second = self.stack.pop_i64();
-                    let first = self.stack.pop_i64();
-                    self.stack.push_bool(first != second);
+                    let second = self.stack.pop();
+                    let first = self.stack.pop();
+                    self.stack.push_bool(first != second)?;
                     self.pc += 1;
                 }
                 ins::LT => {
-                    let second = self.stack.pop_i64();
-                    let first = self.stack.pop_i64();
-                    self.stack.push_bool(first < second);
+                    let second = self.stack.pop();
+                    let first = self.stack.pop();
+                    self.stack.push_bool(compare(first, second)?.is_some_and(|ord| ord.is_lt()))?;
                     self.pc += 1;
                 }
                 ins::LE => {
-                    let second = self.stack.pop_i64();
-                    let first = self.stack.pop_i64();
-                    self.stack.push_bool(first <= second);
+                    let second = self.stack.pop();
+                    let first = self.stack.pop();
+                    self.stack.push_bool(compare(first, second)?.is_some_and(|ord| ord.is_le()))?;
                     self.pc += 1;
                 }
                 ins::GT => {
-                    let second = self.stack.pop_i64();
-                    let first = self.stack.pop_i64();
-                    self.stack.push_bool(first > second);
+                    let second = self.stack.pop();
+                    let first = self.stack.pop();
+                    self.stack.push_bool(compare(first, second)?.is_some_and(|ord| ord.is_gt()))?;
                     self.pc += 1;
                 }
                 ins::GE => {
-                    let second = self.stack.pop_i64();
-                    let first = self.stack.pop_i64();
-                    self.stack.push_bool(first >= second);
+                    let second = self.stack.pop();
+                    let first = self.stack.pop();
+                    self.stack.push_bool(compare(first, second)?.is_some_and(|ord| ord.is_ge()))?;
                     self.pc += 1;
                 }
 
                 ins::LOAD => {
                     let index = &bytes[self.pc+1..self.pc+5];
                     let index = u32::from_le_bytes(index.try_into().unwrap());
-                    self.stack.push(self.locals.get(index as usize));
+                    self.stack.push(self.locals.get(index as usize))?;
                     self.pc += 5;
                 },
                 ins::STORE => {
@@ -163,7 +433,7 @@ impl<'r> RunnerFrame<'r> {
                 ins::JUMP_FALSE => {
                     let offset = &bytes[self.pc+1..self.pc+5];
                     let offset = u32::from_le_bytes(offset.try_into().unwrap());
-                    if !self.stack.pop_bool() {
+                    if !self.stack.pop_bool()? {
                         self.pc = offset as usize;
                     } else {
                         self.pc += 5;
@@ -181,17 +451,59 @@ impl<'r> RunnerFrame<'r> {
                     arg_values.reverse();
 
                     let func = self.stack.pop();
-                    let res = match func {
-                        Value::IFn(index) => {
-                            self.runner.run_frame(index as usize, arg_values)
-                        }
-                        Value::XFn(index) => {
-                            let xfn = &self.runner.bytecode.xfns[index as usize];
-                            xfn.call(arg_values)
-                        }
-                        _ => panic!("runtime error"),
-                    };
-                    self.stack.push(res);
+                    return Ok(RunnerStep::Call(func, arg_values));
+                }
+
+                ins::TRY_BEGIN => {
+                    let offset = &bytes[self.pc+1..self.pc+5];
+                    let offset = u32::from_le_bytes(offset.try_into().unwrap());
+                    self.handlers.push((offset as usize, self.stack.len()));
+                    self.pc += 5;
+                }
+                ins::TRY_END => {
+                    self.handlers.pop();
+                    self.pc += 1;
+                }
+
+                ins::DBG => {
+                    let index = &bytes[self.pc+1..self.pc+5];
+                    let index = u32::from_le_bytes(index.try_into().unwrap());
+                    let val = self.stack.pop();
+                    builtin::write_dbg(self.runner, &self.runner.bytecode.consts[index as usize], &val)?;
+                    self.stack.push(val)?;
+                    self.pc += 5;
+                }
+
+                ins::MAKE_ARRAY => {
+                    let len = &bytes[self.pc+1..self.pc+5];
+                    let len = u32::from_le_bytes(len.try_into().unwrap());
+
+                    self.runner.alloc(len as usize * size_of::<Value>())?;
+                    let mut values = vec![];
+                    for _ in 0..len {
+                        values.push(self.stack.pop());
+                    }
+                    values.reverse();
+                    self.stack.push(Value::Array(Array::from(values)))?;
+
+                    self.pc += 5;
+                }
+                ins::MAKE_MAP => {
+                    let len = &bytes[self.pc+1..self.pc+5];
+                    let len = u32::from_le_bytes(len.try_into().unwrap());
+
+                    let mut entries = vec![];
+                    for _ in 0..len {
+                        let val = self.stack.pop();
+                        let key = match self.stack.pop() {
+                            Value::Str(key) => key,
+                            key => return Err(RuntimeError::Type { want: "STR", got: key.type_name() }),
+                        };
+                        self.runner.alloc(builtin::map_entry_bytes(&key))?;
+                        entries.push((key, val));
+                    }
+                    entries.reverse();
+                    self.stack.push(Value::Map(Map::from(entries)))?;
 
                     self.pc += 5;
                 }
@@ -202,49 +514,150 @@ impl<'r> RunnerFrame<'r> {
     }
 }
 
-struct RunnerStack {
-    stack: Vec<Value>
+/// Get the number as F64, I64 is converted.
+fn want_f64(val: Value) -> Result<f64, RuntimeError> {
+    match val {
+        Value::I64(val) => Ok(val as f64),
+        Value::F64(val) => Ok(val),
+        val => Err(RuntimeError::Type { want: "NUMBER", got: val.type_name() }),
+    }
+}
+
+/// Apply the arithmetic on I64 if both are I64, or on F64 otherwise. The
+/// overflow of I64 is an error.
+fn arith(
+    name: &'static str, first: Value, second: Value,
+    int_op: fn(i64, i64) -> Option<i64>, float_op: fn(f64, f64) -> f64,
+) -> Result<Value, RuntimeError> {
+    match (first, second) {
+        (Value::I64(first), Value::I64(second)) => checked(name, int_op(first, second)).map(Value::I64),
+        (first, second) => Ok(Value::F64(float_op(want_f64(first)?, want_f64(second)?))),
+    }
+}
+
+/// The result of the checked arithmetic, or the overflow error.
+fn checked(name: &'static str, val: Option<i64>) -> Result<i64, RuntimeError> {
+    val.ok_or_else(|| RuntimeError::Builtin { name, msg: "overflows I64".to_string() })
+}
+
+/// Add two numbers or durations, an instant and a duration, or concat two
+/// strings. Not inlined, to keep the frame of [RunnerFrame::run_until_error]
+/// small.
+#[inline(never)]
+fn add(first: Value, second: Value) -> Result<Value, RuntimeError> {
+    match (first, second) {
+        (Value::Str(first), Value::Str(second)) => Ok(Value::Str(first + &second)),
+        (Value::Instant(first), Value::Duration(second)) |
+        (Value::Duration(second), Value::Instant(first)) => checked("+", first.checked_add(second)).map(Value::Instant),
+        (Value::Duration(first), Value::Duration(second)) => checked("+", first.checked_add(second)).map(Value::Duration),
+        (first, second) => arith("+", first, second, i64::checked_add, |a, b| a + b),
+    }
+}
+
+/// Subtract two numbers, instants or durations, or a duration from an
+/// instant. Not inlined, to keep the frame of [RunnerFrame::run_until_error]
+/// small.
+#[inline(never)]
+fn sub(first: Value, second: Value) -> Result<Value, RuntimeError> {
+    match (first, second) {
+        (Value::Instant(first), Value::Instant(second)) => checked("-", first.checked_sub(second)).map(Value::Duration),
+        (Value::Instant(first), Value::Duration(second)) => checked("-", first.checked_sub(second)).map(Value::Instant),
+        (Value::Duration(first), Value::Duration(second)) => checked("-", first.checked_sub(second)).map(Value::Duration),
+        (first, second) => arith("-", first, second, i64::checked_sub, |a, b| a - b),
+    }
+}
+
+/// Divide two numbers, the integer division by zero (or of `i64::MIN` by
+/// `-1`) is an error instead of a panic. Not inlined, to keep the frame of
+/// [RunnerFrame::run_until_error] small.
+#[inline(never)]
+fn div(first: Value, second: Value) -> Result<Value, RuntimeError> {
+    match (first, second) {
+        (Value::I64(_), Value::I64(0)) => Err(RuntimeError::Builtin { name: "/", msg: "division by zero".to_string() }),
+        (first, second) => arith("/", first, second, i64::checked_div, |a, b| a / b),
+    }
+}
+
+/// Compare two numbers, or two values of the same type in strings, instants
+/// and durations. `None` if either is `NaN`.
+fn compare(first: Value, second: Value) -> Result<Option<Ordering>, RuntimeError> {
+    match (first, second) {
+        (Value::I64(first), Value::I64(second)) |
+        (Value::Instant(first), Value::Instant(second)) |
+        (Value::Duration(first), Value::Duration(second)) => Ok(Some(first.cmp(&second))),
+        (Value::Str(first), Value::Str(second)) => Ok(Some(first.cmp(&second))),
+        (first, second) => Ok(want_f64(first)?.partial_cmp(&want_f64(second)?)),
+    }
 }
 
-impl RunnerStack {
-    fn new() -> Self {
-        Self { stack: Vec::new() }
+struct RunnerStack<'r> {
+    stack: Vec<Value>,
+
+    size: &'r Cell<usize>, // The number of values on all stacks.
+    max_size: Option<usize>,
+}
+
+impl<'r> RunnerStack<'r> {
+    fn new(size: &'r Cell<usize>, max_size: Option<usize>) -> Self {
+        Self { stack: Vec::new(), size, max_size }
     }
 
-    fn push(&mut self, val: Value) {
+    fn push(&mut self, val: Value) -> Result<(), RuntimeError> {
+        let size = self.size.get() + 1;
+        if let Some(max) = self.max_size {
+            if size > max {
+                return Err(RuntimeError::LimitExceeded(Limit::StackSize(max)));
+            }
+        }
+        self.size.set(size);
         self.stack.push(val);
+        Ok(())
     }
 
     fn pop(&mut self) -> Value {
         match self.stack.pop() {
-            Some(val) => val,
+            Some(val) => {
+                self.size.set(self.size.get() - 1);
+                val
+            }
             _ => panic!("runtime error"),
         }
     }
 
-    fn push_i64(&mut self, val: i64) {
-        self.stack.push(Value::I64(val));
+    fn len(&self) -> usize {
+        self.stack.len()
     }
 
-    fn pop_i64(&mut self) -> i64 {
-        match self.stack.pop() {
-            Some(Value::I64(val)) => val,
-            _ => panic!("runtime error"),
+    /// Drop the values above the first `len` ones.
+    fn truncate(&mut self, len: usize) {
+        if len < self.stack.len() {
+            self.size.set(self.size.get() - (self.stack.len() - len));
+            self.stack.truncate(len);
         }
     }
 
-    fn push_bool(&mut self, val: bool) {
-        self.stack.push(Value::Bool(val));
+    fn push_i64(&mut self, val: i64) -> Result<(), RuntimeError> {
+        self.push(Value::I64(val))
     }
 
-    fn pop_bool(&mut self) -> bool {
-        match self.stack.pop() {
-            Some(Value::Bool(val)) => val,
-            _ => panic!("runtime error"),
+    fn push_bool(&mut self, val: bool) -> Result<(), RuntimeError> {
+        self.push(Value::Bool(val))
+    }
+
+    fn pop_bool(&mut self) -> Result<bool, RuntimeError> {
+        match self.pop() {
+            Value::Bool(val) => Ok(val),
+            val => Err(RuntimeError::Type { want: "BOOL", got: val.type_name() }),
         }
     }
 }
 
+impl<'r> Drop for RunnerStack<'r> {
+    fn drop(&mut self) {
+        self.size.set(self.size.get() - self.stack.len());
+    }
+}
+
 struct RunnerLocals {
     locals: Vec<Value>
 }
@@ -265,7 +678,9 @@ impl RunnerLocals {
 
 #[cfg(test)]
 mod tests {
-    use crate::{asm::{Asm, AsmFn, AsmLabel, AsmStatement}, bytecode::{self, bytecode_builder::BytecodeBuilder}, value::XFn};
+    use std::path::{Path, PathBuf};
+
+    use crate::{asm::{Asm, AsmBuilder, AsmFn, AsmLabel, AsmStatement, ModuleLoader}, ast::AstBuilder, bytecode::{bytecode_builder::BytecodeBuilder, FixedClock}, engine::Engine, token_stream::TokenStream, value::Array};
 
     use super::*;
 
@@ -277,7 +692,7 @@ mod tests {
             AsmStatement::Ret,
         ]));
         let bytecode = BytecodeBuilder::new(asm).build();
-        let result = Runner::new(bytecode).run();
+        let result = Runner::new(bytecode).run().unwrap();
         assert_eq!(result, Value::I64(0xff));
 
         let mut asm = Asm::new();
@@ -290,7 +705,7 @@ mod tests {
             AsmStatement::Ret,
         ]));
         let bytecode = BytecodeBuilder::new(asm).build();
-        let result = Runner::new(bytecode).run();
+        let result = Runner::new(bytecode).run().unwrap();
         assert_eq!(result, Value::I64(6));
 
         let mut asm = Asm::new();
@@ -303,7 +718,7 @@ mod tests {
             AsmStatement::Ret,
         ]));
         let bytecode = BytecodeBuilder::new(asm).build();
-        let result = Runner::new(bytecode).run();
+        let result = Runner::new(bytecode).run().unwrap();
         assert_eq!(result, Value::I64(3));
     }
 
@@ -317,7 +732,7 @@ mod tests {
             AsmStatement::Ret,
         ]));
         let bytecode = BytecodeBuilder::new(asm).build();
-        let result = Runner::new(bytecode).run();
+        let result = Runner::new(bytecode).run().unwrap();
         assert_eq!(result, Value::Bool(false));
 
         let mut asm = Asm::new();
@@ -328,7 +743,7 @@ mod tests {
             AsmStatement::Ret,
         ]));
         let bytecode = BytecodeBuilder::new(asm).build();
-        let result = Runner::new(bytecode).run();
+        let result = Runner::new(bytecode).run().unwrap();
         assert_eq!(result, Value::Bool(true));
 
         let mut asm = Asm::new();
@@ -339,7 +754,7 @@ mod tests {
             AsmStatement::Ret,
         ]));
         let bytecode = BytecodeBuilder::new(asm).build();
-        let result = Runner::new(bytecode).run();
+        let result = Runner::new(bytecode).run().unwrap();
         assert_eq!(result, Value::Bool(false));
     }
 
@@ -357,7 +772,7 @@ mod tests {
             AsmStatement::Ret,
         ]));
         let bytecode = BytecodeBuilder::new(asm).build();
-        let result = Runner::new(bytecode).run();
+        let result = Runner::new(bytecode).run().unwrap();
         assert_eq!(result, Value::I64(25));
     }
 
@@ -379,7 +794,7 @@ mod tests {
             AsmStatement::Ret,
         ]));
         let bytecode = BytecodeBuilder::new(asm).build();
-        let result = Runner::new(bytecode).run();
+        let result = Runner::new(bytecode).run().unwrap();
         assert_eq!(result, Value::I64(2));
     }
 
@@ -399,7 +814,7 @@ mod tests {
             AsmStatement::Ret,
         ]));
         let bytecode = BytecodeBuilder::new(asm).build();
-        let result = Runner::new(bytecode).run();
+        let result = Runner::new(bytecode).run().unwrap();
         assert_eq!(result, Value::I64(5));
 
         let mut asm = Asm::new();
@@ -420,7 +835,7 @@ mod tests {
             AsmStatement::Ret,
         ]));
         let bytecode = BytecodeBuilder::new(asm).build();
-        let result = Runner::new(bytecode).run();
+        let result = Runner::new(bytecode).run().unwrap();
         assert_eq!(result, Value::I64(8));
 
         let mut asm = Asm::new();
@@ -452,32 +867,9 @@ mod tests {
             AsmStatement::Ret,
         ]));
         let bytecode = BytecodeBuilder::new(asm).build();
-        let result = Runner::new(bytecode).run();
+        let result = Runner::new(bytecode).run().unwrap();
         assert_eq!(result, Value::I64(120));
 
-        let mut asm = Asm::new();
-        asm.xfns = vec![
-            XFn::new("x_add_3".to_string(), |args: Vec<Value>| {
-                assert!(args.len() == 1);
-                match args[0] {
-                    Value::I64(val) => Value::I64(val + 3),
-                    _ => panic!("unexpected value"),
-                }
-            })
-        ];
-        asm.consts = vec![
-            Value::XFn(0),
-        ];
-        asm.push_fn(AsmFn::new(0, vec![
-            AsmStatement::PushConst { index: 0 },
-            AsmStatement::PushI64 { val: 5 },
-            AsmStatement::Call { args: 1 },
-            AsmStatement::Ret,
-        ]));
-        let bytecode = BytecodeBuilder::new(asm).build();
-        let result = Runner::new(bytecode).run();
-        assert_eq!(result, Value::I64(8));
-
         let mut asm = Asm::new();
         asm.consts = vec![
             Value::IFn(1),
@@ -524,7 +916,870 @@ mod tests {
             AsmStatement::Ret,
         ]));
         let bytecode = BytecodeBuilder::new(asm).build();
-        let result = Runner::new(bytecode).run();
+        let result = Runner::new(bytecode).run().unwrap();
         assert_eq!(result, Value::I64(50));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn limits() {
+        // The function calls itself forever, keeping one value on its stack.
+        let forever = || {
+            let mut asm = Asm::new();
+            asm.consts = vec![
+                Value::IFn(1),
+            ];
+            asm.push_fn(AsmFn::new(0, vec![
+                AsmStatement::PushConst { index: 0 },
+                AsmStatement::Call { args: 0 },
+                AsmStatement::Ret,
+            ]));
+            asm.push_fn(AsmFn::new(0, vec![
+                AsmStatement::PushI64 { val: 1 },
+                AsmStatement::PushConst { index: 0 },
+                AsmStatement::Call { args: 0 },
+                AsmStatement::Add,
+                AsmStatement::Ret,
+            ]));
+            BytecodeBuilder::new(asm).build()
+        };
+
+        // The default depth fits the stack of a spawned thread (2 MiB), even
+        // through the builtins calling back into jisp.
+        let thread = std::thread::spawn(move || {
+            let engine = Engine::new();
+            let run = |source: &str| engine.runner(engine.compile(source).unwrap()).unwrap().run().err();
+            [
+                Runner::new(forever()).run().err(),
+                run("(fn f [n] (+ 1 (f n))) (f 1)"),
+                run("(fn f [n] (get (map f [n]) 0)) (f 1)"),
+                run("(fn f [n] (sort-by f [n n])) (f 1)"),
+            ]
+        });
+        let depth_exceeded = Some(RuntimeError::LimitExceeded(Limit::CallDepth(crate::bytecode::DEFAULT_MAX_CALL_DEPTH)));
+        assert_eq!(thread.join().unwrap(), [depth_exceeded.clone(), depth_exceeded.clone(), depth_exceeded.clone(), depth_exceeded]);
+
+        let limits = RunnerLimits { max_call_depth: Some(10), ..RunnerLimits::default() };
+        let result = Runner::with_limits(forever(), limits).run();
+        assert_eq!(result, Err(RuntimeError::LimitExceeded(Limit::CallDepth(10))));
+
+        let limits = RunnerLimits { max_instructions: Some(100), ..RunnerLimits::default() };
+        let result = Runner::with_limits(forever(), limits).run();
+        assert_eq!(result, Err(RuntimeError::LimitExceeded(Limit::Instructions(100))));
+
+        let limits = RunnerLimits { max_stack_size: Some(32), ..RunnerLimits::default() };
+        let result = Runner::with_limits(forever(), limits).run();
+        assert_eq!(result, Err(RuntimeError::LimitExceeded(Limit::StackSize(32))));
+
+        let mut asm = Asm::new();
+        asm.push_fn(AsmFn::new(0, vec![
+            AsmStatement::PushI64 { val: 1 },
+            AsmStatement::PushI64 { val: 2 },
+            AsmStatement::Add,
+            AsmStatement::Ret,
+        ]));
+        let limits = RunnerLimits { max_instructions: Some(4), max_stack_size: Some(2), ..RunnerLimits::default() };
+        let bytecode = BytecodeBuilder::new(asm).build();
+        let result = Runner::with_limits(bytecode, limits).run();
+        assert_eq!(result, Ok(Value::I64(3)));
+    }
+
+    #[test]
+    fn interrupt() {
+        // The function loops forever.
+        let forever = || {
+            let mut asm = Asm::new();
+            asm.push_fn(AsmFn::new(0, vec![
+                AsmStatement::Label { label: AsmLabel::new(".L1") },
+                AsmStatement::Jump { label: AsmLabel::new(".L1") },
+                AsmStatement::Ret,
+            ]));
+            BytecodeBuilder::new(asm).build()
+        };
+
+        let runner = Runner::new(forever());
+        runner.interrupt_handle().interrupt();
+        assert_eq!(runner.run(), Err(RuntimeError::Cancelled));
+
+        let runner = Runner::new(forever());
+        let handle = runner.interrupt_handle();
+        let thread = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(10));
+            handle.interrupt();
+        });
+        assert_eq!(runner.run(), Err(RuntimeError::Cancelled));
+        thread.join().unwrap();
+
+        let mut runner = Runner::new(forever());
+        runner.set_timeout(Duration::from_millis(10));
+        assert_eq!(runner.run(), Err(RuntimeError::TimedOut));
+    }
+
+    #[test]
+    fn loops() {
+        let run = |source: &str, limits: RunnerLimits| {
+            let ast = AstBuilder::new(TokenStream::new(source)).build();
+            let asm = AsmBuilder::new(ast).build();
+            let bytecode = BytecodeBuilder::new(asm).build();
+            Runner::with_limits(bytecode, limits).run()
+        };
+
+        // Jumping out of the half-built values should not leak the stack.
+        let limits = RunnerLimits { max_stack_size: Some(16), ..RunnerLimits::default() };
+        let result = run(r###"
+            (let n 0)
+            (for [i (range 0 1000)]
+              (let n (+ n (+ 1 (if (== (/ i 2) (/ (+ i 1) 2)) (continue) 1)))))
+            (let m (loop [i 0] (+ 1 (if (< i 1000) (recur (+ i 1)) i))))
+            (+ n (while (== 1 1) (+ 1 (+ 2 (break m)))))
+        "###, limits);
+        assert_eq!(result, Ok(Value::I64(2001)));
+
+        // Loops do not use the call frames.
+        let limits = RunnerLimits { max_call_depth: Some(1), ..RunnerLimits::default() };
+        let result = run(r###"
+            (let s 0)
+            (for [i (range 0 10000)] (let s (+ s i)))
+            s
+        "###, limits);
+        assert_eq!(result, Ok(Value::I64(49995000)));
+    }
+
+    #[test]
+    fn arrays() {
+        let run = |source: &str, limits: RunnerLimits| {
+            let ast = AstBuilder::new(TokenStream::new(source)).build();
+            let asm = AsmBuilder::new(ast).build();
+            let bytecode = BytecodeBuilder::new(asm).build();
+            Runner::with_limits(bytecode, limits).run()
+        };
+
+        let result = run(r###"
+            (let a [1 2])
+            (let b a)
+            (push b 3)
+            (set a [0] [4 5])
+            a
+        "###, RunnerLimits::default());
+        assert_eq!(result, Ok(Value::Array(Array::from(vec![
+            Value::Array(Array::from(vec![Value::I64(4), Value::I64(5)])),
+            Value::I64(2),
+            Value::I64(3),
+        ]))));
+
+        let result = run(r###"
+            (let [a [b c] & rest] [1 [2 3] 4 5])
+            (+ a b c (len rest))
+        "###, RunnerLimits::default());
+        assert_eq!(result, Ok(Value::I64(8)));
+
+        let result = run("(get 1 [0])", RunnerLimits::default());
+        assert_eq!(result, Err(RuntimeError::Builtin { name: "get", msg: "wants ARRAY with I64 or MAP with STR, got I64 with I64".to_string() }));
+
+        let result = run("(+ [1] 1)", RunnerLimits::default());
+        assert_eq!(result, Err(RuntimeError::Type { want: "NUMBER", got: "ARRAY" }));
+
+        let limits = RunnerLimits { max_container_bytes: Some(1024), ..RunnerLimits::default() };
+        let result = run(r###"
+            (let a [])
+            (while (== 1 1) (push a 1))
+        "###, limits);
+        assert_eq!(result, Err(RuntimeError::LimitExceeded(Limit::ContainerBytes(1024))));
+
+        // The parsed document is counted while it is built.
+        let result = run(&format!(r#"(json/parse "[{}0]")"#, "0,".repeat(100)), limits);
+        assert_eq!(result, Err(RuntimeError::LimitExceeded(Limit::ContainerBytes(1024))));
+        let result = run(r#"(len (json/parse "{\"a\": [0, 0]}"))"#, limits);
+        assert_eq!(result, Ok(Value::I64(1)));
+
+        // The huge range is an error with or without the limit.
+        let too_large = |len: &str| Err(RuntimeError::Builtin { name: "range", msg: format!("of {} values is too large", len) });
+        let result = run("[1] (range 0 9223372036854775807)", RunnerLimits::default());
+        assert_eq!(result, too_large("9223372036854775807"));
+        let result = run("(range 0 288230376151711744)", RunnerLimits::default());
+        assert_eq!(result, too_large("288230376151711744"));
+        let result = run("[1] (range 0 9223372036854775807)", limits);
+        assert_eq!(result, too_large("9223372036854775807"));
+        let result = run("[1] (range 0 288230376151711744)", limits);
+        assert_eq!(result, Err(RuntimeError::LimitExceeded(Limit::ContainerBytes(1024))));
+    }
+
+    #[test]
+    fn maps() {
+        let run = |source: &str| {
+            let ast = AstBuilder::new(TokenStream::new(source)).build();
+            let asm = AsmBuilder::new(ast).build();
+            let bytecode = BytecodeBuilder::new(asm).build();
+            Runner::new(bytecode).run()
+        };
+
+        let result = run(r###"
+            (let m {"b" 1 "a" 2})
+            (assoc m "c" 3 "b" 4)
+            (dissoc m "a")
+            [(keys m) (vals m) (get m "a") (has? m "c")]
+        "###);
+        assert_eq!(result, Ok(Value::Array(Array::from(vec![
+            Value::Array(Array::from(vec![Value::Str("b".to_string()), Value::Str("c".to_string())])),
+            Value::Array(Array::from(vec![Value::I64(4), Value::I64(3)])),
+            Value::Null,
+            Value::Bool(true),
+        ]))));
+
+        let result = run(r###"
+            (fn id [v]
+              (match v
+                [{"type" "user" "id" id}] id
+                [{"type" _}] 0
+                [_] -1))
+            [(id {"type" "user" "id" 7}) (id {"type" "bot" "id" 8}) (id [])]
+        "###);
+        assert_eq!(result, Ok(Value::Array(Array::from(vec![
+            Value::I64(7), Value::I64(0), Value::I64(-1),
+        ]))));
+
+        // The literals match as they are, not bound as names.
+        let result = run(r###"
+            (fn kind [v]
+              (match v
+                [null] "null"
+                [true] "yes"
+                [1.5] "float"
+                [x] x))
+            [(kind null) (kind true) (kind 1.5) (kind false)]
+        "###);
+        assert_eq!(result, Ok(Value::Array(Array::from(vec![
+            Value::Str("null".to_string()), Value::Str("yes".to_string()),
+            Value::Str("float".to_string()), Value::Bool(false),
+        ]))));
+
+        let result = run("{1 2}");
+        assert_eq!(result, Err(RuntimeError::Type { want: "STR", got: "I64" }));
+    }
+
+    #[test]
+    fn json() {
+        let run = |source: &str| {
+            let ast = AstBuilder::new(TokenStream::new(source)).build();
+            let asm = AsmBuilder::new(ast).build();
+            let bytecode = BytecodeBuilder::new(asm).build();
+            Runner::new(bytecode).run()
+        };
+
+        let result = run(r###"
+            (let doc (json/parse (json/stringify {"a" [1 (< 0 1) (get [] 0)] "b" {}} (< 0 1))))
+            [(get doc ["a" 0]) (json/stringify (get doc "a")) (json/stringify (get doc "b"))]
+        "###);
+        assert_eq!(result, Ok(Value::Array(Array::from(vec![
+            Value::I64(1),
+            Value::Str("[1,true,null]".to_string()),
+            Value::Str("{}".to_string()),
+        ]))));
+
+        let result = run(r###"(json/parse "[1,
+2")"###);
+        assert_eq!(result, Err(RuntimeError::Builtin {
+            name: "json/parse",
+            msg: "expected ',' or ']' at line 2 column 2".to_string(),
+        }));
+
+        let result = run("(fn f [] 1) (json/stringify [f])");
+        assert_eq!(result, Err(RuntimeError::Builtin {
+            name: "json/stringify",
+            msg: "FN can not be serialized to JSON".to_string(),
+        }));
+    }
+
+    #[test]
+    fn paths() {
+        let run = |source: &str| {
+            let ast = AstBuilder::new(TokenStream::new(source)).build();
+            let asm = AsmBuilder::new(ast).build();
+            let bytecode = BytecodeBuilder::new(asm).build();
+            Runner::new(bytecode).run()
+        };
+        let json = |source: &str| Ok(Value::from_json(source).unwrap());
+
+        let result = run(r###"
+            (let doc {"a" [{"b" 1} {"b" 2}]})
+            [(get-in doc ["a" -1 "b"]) (get-in? doc ["a" 5 "b"]) (get-in doc ["x"] 0) (get-in doc [])]
+        "###);
+        assert_eq!(result, json(r#"[2, null, 0, {"a": [{"b": 1}, {"b": 2}]}]"#));
+
+        let result = run(r###"(get-in {"a" 1} ["x" "y"])"###);
+        assert_eq!(result, Err(RuntimeError::Builtin {
+            name: "get-in",
+            msg: "wants ARRAY with I64 or MAP with STR, got NULL with STR".to_string(),
+        }));
+        let result = run(r###"(get-in? {"a" 1} ["x" "y"])"###);
+        assert_eq!(result, Ok(Value::Null));
+
+        let result = run(r###"
+            (fn add [x y] (+ x y))
+            (fn wrap [x] [x])
+            (let doc {"a" [1 2 3]})
+            (assoc-in doc ["b" "c"] 1)
+            (update-in doc ["a" -1] add 10)
+            (update-in doc ["b" "d"] wrap)
+            (dissoc-in doc ["a" 0])
+            (dissoc-in doc ["x" "y"])
+            doc
+        "###);
+        assert_eq!(result, json(r#"{"a": [2, 13], "b": {"c": 1, "d": [null]}}"#));
+
+        let result = run(r###"(assoc-in [1] [3] 0)"###);
+        assert_eq!(result, Err(RuntimeError::Builtin { name: "assoc-in", msg: "index 3 out of range 1".to_string() }));
+    }
+
+    #[test]
+    fn params() {
+        let ast = AstBuilder::new(TokenStream::new(r###"(get-in $ ["a" 0])"###)).build();
+        let mut asm_builder = AsmBuilder::new(ast);
+        asm_builder.declare_param("$".to_string());
+        let bytecode = BytecodeBuilder::new(asm_builder.build()).build();
+        let doc = Value::from_json(r#"{"a": [7]}"#).unwrap();
+        assert_eq!(Runner::new(bytecode).run_with(vec![doc]), Ok(Value::I64(7)));
+    }
+
+    #[test]
+    fn run_many() {
+        let ast = AstBuilder::new(TokenStream::new("(let s 0) (for [i (range 0 it)] (let s (+ s i))) s")).build();
+        let mut asm_builder = AsmBuilder::new(ast);
+        asm_builder.declare_param("it".to_string());
+        let bytecode = BytecodeBuilder::new(asm_builder.build()).build();
+        let limits = RunnerLimits { max_instructions: Some(200), ..RunnerLimits::default() };
+        let runner = Runner::with_limits(bytecode, limits);
+
+        for _ in 0..3 {
+            assert_eq!(runner.run_with(vec![Value::I64(10)]), Ok(Value::I64(45)));
+        }
+        assert_eq!(runner.run_with(vec![Value::I64(100)]), Err(RuntimeError::LimitExceeded(Limit::Instructions(200))));
+        assert_eq!(runner.run_with(vec![Value::I64(4)]), Ok(Value::I64(6)));
+    }
+
+    #[test]
+    fn seq() {
+        let run = |source: &str| {
+            let mut engine = Engine::new();
+            engine.register_xfn("x_double".to_string(), |args: Vec<Value>| match args[0] {
+                Value::I64(val) => Value::I64(val * 2),
+                _ => Value::Null,
+            });
+            engine.runner(engine.compile(source).unwrap()).unwrap().run()
+        };
+        let json = |source: &str| Ok(Value::from_json(source).unwrap());
+
+        let result = run(r###"
+            (fn big? [x] (> x 2))
+            (fn add [a b] (+ a b))
+            (fn name [user] (get user "name"))
+            (fn team [user] (get user "team"))
+            (let users [{"name" "b" "team" "x"} {"name" "c" "team" "y"} {"name" "a" "team" "x"}])
+            [(map x_double [1 2]) (filter big? (range 5)) (reduce add (map len [[1] [1 2]]))
+             (map name (sort-by name users)) (map len (vals (group-by team users)))
+             (find big? [1 5 3]) (any? big? []) (every? big? [])]
+        "###);
+        assert_eq!(result, json(r#"[[2, 4], [3, 4], 3, ["a", "b", "c"], [2, 1], 5, false, true]"#));
+
+        let result = run("(fn id [x] x) (filter id [1])");
+        assert_eq!(result, Err(RuntimeError::Builtin { name: "filter", msg: "wants BOOL, got I64".to_string() }));
+
+        let result = run(r###"(sort [1 "a"])"###);
+        assert_eq!(result, Err(RuntimeError::Builtin { name: "sort", msg: "can not compare STR with I64".to_string() }));
+
+        let result = run("(map 1 [1])");
+        assert_eq!(result, Err(RuntimeError::Type { want: "FN", got: "I64" }));
+    }
+
+    #[test]
+    fn strings() {
+        let run = |source: &str| {
+            let ast = AstBuilder::new(TokenStream::new(source)).build();
+            let asm = AsmBuilder::new(ast).build();
+            let bytecode = BytecodeBuilder::new(asm).build();
+            Runner::new(bytecode).run()
+        };
+        let json = |source: &str| Ok(Value::from_json(source).unwrap());
+
+        let result = run(r###"
+            (let name "wörld")
+            [(+ "hello " name) (len name) (substr name 1 -1) (upper name) (split "a b" " ") (join ["a" "b"] ",")
+             (trim " x ") (replace "a.b.c" "." "/") (starts-with? name "wö") (contains? name "x")
+             (format "{}: {} {{}}" name [1 2]) (str "n=" 1 (== 1 1))]
+        "###);
+        assert_eq!(result, json(r#"[
+            "hello wörld", 5, "örl", "WÖRLD", ["a", "b"], "a,b",
+            "x", "a/b/c", true, false,
+            "wörld: [1 2] {}", "n=1true"
+        ]"#));
+
+        let result = run(r###"
+            (let half (parse-float "0.5"))
+            [(parse-int "12") (parse-int "1.5") half (+ half 1) (* 2 half) (< half 1) (== (parse-float "0") (parse-float "-0"))]
+        "###);
+        assert_eq!(result, json(r#"[12, null, 0.5, 1.5, 1.0, true, true]"#));
+
+        // The values in strings are written as jisp reads them, like `dbg`.
+        let result = run(r###"[(str [1 "a"] {"b" null}) (format "{} {}" len (time/ms 5))]"###);
+        assert_eq!(result, json(r#"["[1 \"a\"]{\"b\" null}", "len (time/ms 5)"]"#));
+
+        let result = run(r###"(format "{} {}" 1)"###);
+        assert_eq!(result, Err(RuntimeError::Builtin { name: "format", msg: "wants more arguments for {}".to_string() }));
+
+        let result = run(r###"(+ "a" 1)"###);
+        assert_eq!(result, Err(RuntimeError::Type { want: "NUMBER", got: "STR" }));
+    }
+
+    #[test]
+    fn regexes() {
+        let run = |source: &str| {
+            let ast = AstBuilder::new(TokenStream::new(source)).build();
+            let asm = AsmBuilder::new(ast).build();
+            let bytecode = BytecodeBuilder::new(asm).build();
+            Runner::new(bytecode).run()
+        };
+        let json = |source: &str| Ok(Value::from_json(source).unwrap());
+
+        let result = run(r###"
+            (let log "GET /a 200, POST /b 404")
+            (let pattern (+ "(?P<method>[A-Z]+) " "(?P<path>\S+)"))
+            [(re/match "(\d)(\d)" log) (re/find-all pattern log) (re/find-all "\d+" log)
+             (re/replace "/(\w)" log "[$1]") (re/split ",\s*" log) (re/match "x" log)]
+        "###);
+        assert_eq!(result, json(r#"[
+            ["20", "2", "0"],
+            [{"method": "GET", "path": "/a"}, {"method": "POST", "path": "/b"}],
+            ["200", "404"],
+            "GET [a] 200, POST [b] 404",
+            ["GET /a 200", "POST /b 404"],
+            null
+        ]"#));
+
+        let result = run(r###"(let p "(") (re/match p "")"###);
+        assert!(matches!(result, Err(RuntimeError::Builtin { name: "re/match", .. })));
+    }
+
+    #[test]
+    fn time() {
+        let run = |source: &str| {
+            let ast = AstBuilder::new(TokenStream::new(source)).build();
+            let asm = AsmBuilder::new(ast).build();
+            let bytecode = BytecodeBuilder::new(asm).build();
+            let mut runner = Runner::new(bytecode);
+            runner.set_clock(FixedClock { millis: 1704164645000 });
+            runner.run()
+        };
+        let json = |source: &str| Ok(Value::from_json(source).unwrap());
+
+        let result = run(r###"
+            (fn day [event] (time/format (time/truncate (time/parse (get event "at")) "day") "%Y-%m-%d"))
+            (let events [{"at" "2024-01-01T23:00:00Z"} {"at" "2024-01-02T01:00:00+02:00"} {"at" "2024-01-02T12:00:00Z"}])
+            (let now (time/now))
+            [(time/format now) (map len (vals (group-by day events)))
+             (time/format (- now (time/days 1)) "%d %H") (time/millis (- now (time/parse "2024-01-02")))
+             (> now (time/parse "2024-01-01")) (== (+ (time/seconds 60) now) (+ now (time/minutes 1)))]
+        "###);
+        assert_eq!(result, json(r#"["2024-01-02T03:04:05Z", [2, 1], "01 03", 11045000, true, true]"#));
+
+        let result = run(r###"(time/parse "2024-13-01")"###);
+        assert_eq!(result, Err(RuntimeError::Builtin {
+            name: "time/parse",
+            msg: "invalid time \"2024-13-01\": 13 out of range 1..=12 at 5".to_string(),
+        }));
+
+        let result = run("(- (time/now) 1)");
+        assert_eq!(result, Err(RuntimeError::Type { want: "NUMBER", got: "INSTANT" }));
+    }
+
+    #[test]
+    fn typed_xfns() {
+        let run = |source: &str| {
+            let mut engine = Engine::new();
+            engine.register_fn("x_repeat".to_string(), |s: String, n: i64| s.repeat(n.max(0) as usize));
+            engine.register_fn("x_sum".to_string(), |values: Vec<f64>| values.iter().sum::<f64>());
+            engine.register_fn("x_check".to_string(), |n: i64| if n > 0 { Ok(n) } else { Err("wants a positive number") });
+            engine.runner(engine.compile(source).unwrap()).unwrap().run()
+        };
+
+        let result = run(r###"[(x_repeat "ab" 2) (x_sum [1 (parse-float "0.5")]) (map x_check [1 2])]"###);
+        assert_eq!(result, Ok(Value::from_json(r#"["abab", 1.5, [1, 2]]"#).unwrap()));
+
+        let xfn_error = |name: &str, msg: &str, offset| RuntimeError::XFn {
+            name: name.to_string(),
+            error: HostError::new(msg),
+            at: Some(CallSite { func: 0, name: String::new(), offset }),
+        };
+        let result = run(r###"(x_repeat 2 "ab")"###);
+        assert_eq!(result, Err(xfn_error("x_repeat", "argument 1 wants STR, got I64", 19)));
+
+        let result = run(r###"(x_repeat "ab")"###);
+        assert_eq!(result, Err(xfn_error("x_repeat", "wants 2 arguments, got 1", 10)));
+
+        let result = run("(x_check 0)");
+        assert_eq!(result, Err(xfn_error("x_check", "wants a positive number", 14)));
+        let result = run("(x_check 0)").unwrap_err().to_string();
+        assert_eq!(result, "x_check: wants a positive number (called in the main fn)");
+        let result = run("(fn check [n] (x_check n)) (check 0)").unwrap_err().to_string();
+        assert_eq!(result, "x_check: wants a positive number (called in fn check)");
+    }
+
+    #[test]
+    fn contexts() {
+        struct Calls(Cell<i64>);
+
+        let run = |source: &str| {
+            let mut engine = Engine::new();
+            engine.register_xfn_with_context("x_apply_twice".to_string(), |ctx, args| {
+                let calls = &ctx.data::<Calls>().unwrap().0;
+                calls.set(calls.get() + 1);
+                let once = ctx.call(args[0].clone(), vec![args[1].clone()])?;
+                Ok(ctx.call(args[0].clone(), vec![once])?)
+            });
+            engine.register_xfn_with_context("x_calls".to_string(), |ctx, _| {
+                Ok(Value::I64(ctx.data::<Calls>().unwrap().0.get()))
+            });
+            engine.register_xfn_with_context("x_max_depth".to_string(), |ctx, _| {
+                Ok(ctx.limits().max_call_depth.map_or(Value::Null, |max| Value::I64(max as i64)))
+            });
+            engine.set_limits(RunnerLimits { max_call_depth: Some(16), ..RunnerLimits::default() });
+            engine.set_data(Calls(Cell::new(0)));
+            engine.runner(engine.compile(source).unwrap()).unwrap().run()
+        };
+
+        let result = run("(fn inc [x] (+ x 1)) (fn add2 [x] (x_apply_twice inc x)) [(x_apply_twice inc 1) (x_apply_twice add2 1) (x_calls) (x_max_depth)]");
+        assert_eq!(result, Ok(Value::from_json("[3, 5, 4, 16]").unwrap()));
+
+        // The errors of the callbacks are raised unchanged.
+        let result = run(r###"(fn bad [x] (+ x "a")) (try (x_apply_twice bad 1) (catch e (get e "message")))"###);
+        assert_eq!(result, Ok(Value::Str("want NUMBER, got STR".to_string())));
+        let result = run("(fn deep [x] (x_apply_twice deep x)) (try (deep 1) (catch e 0))");
+        assert_eq!(result, Err(RuntimeError::LimitExceeded(Limit::CallDepth(16))));
+    }
+
+    #[test]
+    fn hosts() {
+        use std::fmt::Formatter;
+        use crate::value::HostObject;
+
+        struct Counter {
+            name: String,
+            count: Cell<i64>,
+        }
+
+        impl HostObject for Counter {
+            fn type_name(&self) -> &'static str {
+                "COUNTER"
+            }
+
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                write!(f, "#<COUNTER {} {}>", self.name, self.count.get())
+            }
+
+            fn equals(&self, other: &dyn HostObject) -> bool {
+                (other as &dyn Any).downcast_ref::<Counter>().is_some_and(|other| other.name == self.name)
+            }
+
+            fn call_method(&self, ctx: &XFnContext<'_>, name: &str, args: Vec<Value>) -> Result<Value, HostError> {
+                match name {
+                    "inc" => {
+                        let by = match args.first() {
+                            Some(Value::I64(by)) => *by,
+                            Some(func) => match ctx.call(func.clone(), vec![Value::I64(self.count.get())])? {
+                                Value::I64(by) => by,
+                                val => return Err(HostError::new(format!("wants I64, got {}", val.type_name()))),
+                            },
+                            None => 1,
+                        };
+                        self.count.set(self.count.get() + by);
+                        Ok(Value::I64(self.count.get()))
+                    }
+                    "name" => Ok(Value::Str(self.name.clone())),
+                    name => Err(HostError::new(format!("no method {}", name))),
+                }
+            }
+        }
+
+        let run = |source: &str| {
+            let mut engine = Engine::new();
+            engine.register_fn("x_counter".to_string(), |name: String| Rc::new(Counter { name, count: Cell::new(0) }));
+            engine.register_fn("x_count".to_string(), |counter: Value| counter.as_host::<Counter>().map(|c| c.count.get()));
+            engine.runner(engine.compile(source).unwrap()).unwrap().run()
+        };
+
+        let result = run(r###"
+            (fn double [n] (* n 2))
+            (let c (x_counter "a"))
+            (.inc c)
+            (.inc c 2)
+            (let m {"c" c})
+            (.inc (get m "c") double)
+            [(.name c) (x_count (get [c] 0)) (x_count 1) (== c (x_counter "a")) (== c (x_counter "b"))]
+        "###);
+        assert_eq!(result, Ok(Value::from_json(r#"["a", 9, null, true, false]"#).unwrap()));
+
+        let result = run(r###"(.dec (x_counter "a"))"###);
+        assert_eq!(result, Err(RuntimeError::XFn {
+            name: "COUNTER.dec".to_string(),
+            error: HostError::new("no method dec"),
+            at: Some(CallSite { func: 0, name: String::new(), offset: 25 }),
+        }));
+        let result = run(r###"(try (.inc (x_counter "a") "x") (catch e (get e "message")))"###);
+        assert_eq!(result, Ok(Value::Str("want FN, got STR".to_string())));
+        let result = run(r###"(.inc 1)"###);
+        assert_eq!(result, Err(RuntimeError::Builtin { name: "method", msg: "wants HOST, got I64".to_string() }));
+    }
+
+    #[test]
+    fn modules() {
+        struct Files;
+
+        impl ModuleLoader for Files {
+            fn load(&self, path: &Path) -> Result<String, String> {
+                match path.to_str() {
+                    Some("app/lib/util.jisp") => Ok(r###"
+                        (import "../shared/math.jisp" [square])
+                        (fn twice [x] (* x 2))
+                        (fn quad [x] (twice (twice x)))
+                        (fn area [x] (square x))
+                        (export quad area)
+                    "###.to_string()),
+                    Some("app/shared/math.jisp") => Ok("(fn square [x] (* x x)) (export square)".to_string()),
+                    _ => Err("not found".to_string()),
+                }
+            }
+        }
+
+        let ast = AstBuilder::new(TokenStream::new(r###"
+            (import "lib/util.jisp" [quad area])
+            (import "./shared/math.jisp")
+            (fn twice [x] (+ x x x))
+            [(quad 3) (area 3) (square 4) (twice 1)]
+        "###)).build();
+        let mut asm_builder = AsmBuilder::new(ast);
+        asm_builder.set_path(PathBuf::from("app/main.jisp"));
+        asm_builder.set_loader(Files);
+        let asm = asm_builder.build();
+        // The main function, `twice`, `quad` and `area` of util, `square` built once, and `twice`.
+        assert_eq!(asm.ifns.len(), 6);
+        let result = Runner::new(BytecodeBuilder::new(asm).build()).run();
+        assert_eq!(result, Ok(Value::from_json("[12, 9, 16, 3]").unwrap()));
+    }
+
+    #[test]
+    fn displays() {
+        let mut engine = Engine::new();
+        engine.register_fn("x_inc".to_string(), |n: i64| n + 1);
+        let runner = engine.runner(engine.compile(r#"
+            (fn fac [n] n)
+            [fac x_inc len {"a" (get {} "x")}]
+        "#).unwrap()).unwrap();
+        let val = runner.run().unwrap();
+        assert_eq!(runner.display(&val).to_string(), r#"[#<fn fac> #<fn x_inc> len {"a" null}]"#);
+        assert_eq!(val.to_string(), r#"[#<fn> #<fn> len {"a" null}]"#);
+    }
+
+    #[test]
+    fn outputs() {
+        #[derive(Clone, Default)]
+        struct Output(Rc<RefCell<Vec<u8>>>);
+
+        impl Write for Output {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.borrow_mut().write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let engine = Engine::new();
+        let source = r#"
+            (print "a" 1)
+            (println [2] {"b" 3})
+            (io/print "c")
+            (+ 1 (dbg (* 2 3)))
+        "#;
+        let mut runner = engine.runner(engine.compile(source).unwrap()).unwrap();
+        let output = Output::default();
+        runner.set_output(output.clone());
+        assert_eq!(runner.run(), Ok(Value::I64(7)));
+        let output = String::from_utf8(output.0.take()).unwrap();
+        assert_eq!(output, "a 1[2] {\"b\" 3}\nc[5:18] 6\n");
+
+        // The stdout is not allowed by default.
+        let runner = engine.runner(engine.compile(source).unwrap()).unwrap();
+        assert_eq!(runner.run(), Err(RuntimeError::PermissionDenied { name: "print", msg: "stdout".to_string() }));
+
+        // Only the calls of the builtin write the location.
+        let run = |source: &str| {
+            let mut runner = engine.runner(engine.compile(source).unwrap()).unwrap();
+            let output = Output::default();
+            runner.set_output(output.clone());
+            let result = runner.run();
+            (result, String::from_utf8(output.0.take()).unwrap())
+        };
+        assert_eq!(run("(map dbg [1 2])"), (Ok(Value::Array(Array::from(vec![Value::I64(1), Value::I64(2)]))), "1\n2\n".to_string()));
+        assert_eq!(run("(fn dbg [x y] (+ x y)) (dbg 1 2)"), (Ok(Value::I64(3)), "".to_string()));
+        assert_eq!(run("(let dbg len) (dbg [1])"), (Ok(Value::I64(1)), "".to_string()));
+        assert_eq!(run("(dbg 1 2)"), (
+            Err(RuntimeError::Builtin { name: "dbg", msg: "wants 1 arguments, got 2".to_string() }),
+            "".to_string(),
+        ));
+    }
+
+    #[test]
+    fn nesting() {
+        let run = |source: &str| {
+            let engine = Engine::new();
+            engine.runner(engine.compile(source).unwrap()).unwrap().run()
+        };
+        let itself = |name| Err(RuntimeError::Builtin { name, msg: "can not put ARRAY into itself".to_string() });
+        assert_eq!(run("(let a []) (push a a)"), itself("push"));
+        assert_eq!(run("(let a []) (let b [[a]]) (push a b)"), itself("push"));
+        assert_eq!(run("(let a [[]]) (set a [0 0] a)"), itself("set"));
+        assert_eq!(run(r#"(let m {}) (try (assoc m "m" m) (catch e (get e "message")))"#), Ok(Value::Str("can not put MAP into itself".to_string())));
+
+        // The deep values are compared, hashed and dropped without recursion.
+        let result = run(r#"
+            (fn chain []
+                (let head [])
+                (let tail head)
+                (let i 0)
+                (while (< i 100000)
+                    (let next [])
+                    (push tail next)
+                    (let tail next)
+                    (let i (+ i 1)))
+                head)
+            (let a (chain))
+            (let b (chain))
+            [(== a b) (== a a) (len (distinct [a b a])) (len (loop [c [] i 0] (if (< i 100000) (recur [c] (+ i 1)) c)))]
+        "#);
+        assert_eq!(result, Ok(Value::from_json("[false, true, 2, 1]").unwrap()));
+        assert_eq!(run("(let a []) (push a (loop [c [] i 0] (if (< i 200) (recur [c] (+ i 1)) c)))"), Err(RuntimeError::Builtin {
+            name: "push",
+            msg: format!("nested deeper than {}", crate::value::MAX_NESTING),
+        }));
+    }
+
+    #[test]
+    fn divisions() {
+        let run = |source: &str| {
+            let engine = Engine::new();
+            engine.runner(engine.compile(source).unwrap()).unwrap().run()
+        };
+        let div_zero = RuntimeError::Builtin { name: "/", msg: "division by zero".to_string() };
+        assert_eq!(run("(/ 1 0)"), Err(div_zero));
+        assert_eq!(run("(/ -9223372036854775808 -1)"), Err(RuntimeError::Builtin {
+            name: "/",
+            msg: "overflows I64".to_string(),
+        }));
+        assert_eq!(run("(try (/ 1 0) (catch e 5))"), Ok(Value::I64(5)));
+        assert_eq!(run(r#"(try (/ 1 0) (catch e (get e "message")))"#), Ok(Value::Str("division by zero".to_string())));
+        assert_eq!(run("(/ 7 2)"), Ok(Value::I64(3)));
+    }
+
+    #[test]
+    fn overflows() {
+        let run = |source: &str| {
+            let engine = Engine::new();
+            engine.runner(engine.compile(source).unwrap()).unwrap().run()
+        };
+        let overflow = |name| Err(RuntimeError::Builtin { name, msg: "overflows I64".to_string() });
+        assert_eq!(run("(+ 9223372036854775807 1)"), overflow("+"));
+        assert_eq!(run("(- -9223372036854775808 1)"), overflow("-"));
+        assert_eq!(run("(* 9223372036854775807 2)"), overflow("*"));
+        assert_eq!(run("(+ (time/instant 9223372036854775807) (time/ms 1))"), overflow("+"));
+        assert_eq!(run("(+ (time/ms 9223372036854775807) (time/ms 1))"), overflow("+"));
+        assert_eq!(run("(- (time/instant -9223372036854775808) (time/instant 1))"), overflow("-"));
+        assert_eq!(run("(- (time/instant -9223372036854775808) (time/ms 1))"), overflow("-"));
+        assert_eq!(run(r#"(try (+ 9223372036854775807 1) (catch e (get e "message")))"#), Ok(Value::Str("overflows I64".to_string())));
+        assert_eq!(run("(+ 9223372036854775806 1)"), Ok(Value::I64(i64::MAX)));
+        assert_eq!(run("(+ 9223372036854775807 1.0)"), Ok(Value::F64(i64::MAX as f64 + 1.0)));
+    }
+
+    #[test]
+    fn io() {
+        let dir = std::env::temp_dir().join(format!("jisp_io_{}", std::process::id()));
+        let allowed = dir.join("allowed");
+        std::fs::create_dir_all(&allowed).unwrap();
+        std::fs::write(dir.join("secret.txt"), "secret").unwrap();
+
+        let run = |source: &str, capabilities: Capabilities| {
+            let engine = Engine::new();
+            let mut runner = engine.runner(engine.compile(source).unwrap()).unwrap();
+            runner.set_capabilities(capabilities);
+            runner.run()
+        };
+        let capabilities = Capabilities {
+            read_dirs: vec![allowed.clone()],
+            write_dirs: vec![allowed.clone()],
+            env_vars: vec!["PATH".to_string()],
+            ..Capabilities::none()
+        };
+
+        let file = allowed.join("a.txt");
+        let source = format!(r#"(fs/write "{0}" "hello") (fs/read "{0}")"#, file.display());
+        assert_eq!(run(&source, capabilities.clone()), Ok(Value::Str("hello".to_string())));
+        assert_eq!(run(&source, Capabilities::none()), Err(RuntimeError::PermissionDenied {
+            name: "fs/write",
+            msg: file.display().to_string(),
+        }));
+
+        // The `..` does not escape the directory.
+        let escaped = format!("{}/../secret.txt", allowed.display());
+        let result = run(&format!(r#"(fs/read "{}")"#, escaped), capabilities.clone());
+        assert_eq!(result, Err(RuntimeError::PermissionDenied { name: "fs/read", msg: escaped }));
+
+        let result = run(r#"[(== (env/get "PATH") (get {} "x")) (try (env/get "HOME") (catch e (get e "message")))]"#, capabilities.clone());
+        assert_eq!(result, Ok(Value::from_json(r#"[false, "permission denied: HOME"]"#).unwrap()));
+        let result = run(r#"(io/print "x")"#, capabilities);
+        assert_eq!(result, Err(RuntimeError::PermissionDenied { name: "io/print", msg: "stdout".to_string() }));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn tries() {
+        let run = |source: &str| {
+            let mut engine = Engine::new();
+            engine.register_fn("x_check".to_string(), |n: i64| if n > 0 { Ok(n) } else { Err("wants a positive number") });
+            engine.set_limits(RunnerLimits { max_instructions: Some(10000), ..Default::default() });
+            engine.runner(engine.compile(source).unwrap()).unwrap().run()
+        };
+
+        let result = run("(try (x_check 1) (catch e 0))");
+        assert_eq!(result, Ok(Value::I64(1)));
+
+        let result = run(r###"(+ 1 (try (+ 2 (x_check 0)) (catch e (get e "message"))) )"###);
+        assert_eq!(result, Err(RuntimeError::Type { want: "NUMBER", got: "STR" }));
+
+        let result = run(r###"[1 (try (+ 2 (x_check 0)) (catch e [(get e "type") (get e "name") (get e "message")]))]"###);
+        assert_eq!(result, Ok(Value::from_json(r#"[1, ["host", "x_check", "wants a positive number"]]"#).unwrap()));
+
+        // The errors from deeper frames and builtins are caught as well.
+        let result = run(r###"
+            (fn check [n] (x_check n))
+            [(try (map check [1 0]) (catch e (get e "name")))
+             (try (get 1 2) (catch e (get e "type")))
+             (try (+ 1 "a") (catch e (get e "message")))]
+        "###);
+        assert_eq!(result, Ok(Value::from_json(r#"["x_check", "builtin", "want NUMBER, got STR"]"#).unwrap()));
+
+        // Leaving `try` by `break` drops its handler.
+        let result = run(r###"
+            (let n 0)
+            (while (< n 3)
+                (try (if (== n 1) (break n) n) (catch e 0))
+                (let n (+ n 1)))
+            (x_check 0)
+        "###);
+        assert!(matches!(result, Err(RuntimeError::XFn { .. })));
+
+        // The error in `catch` goes to the outer `try`, and the limits can not be caught.
+        let result = run("(try (try (x_check 0) (catch e (x_check -1))) (catch e 7))");
+        assert_eq!(result, Ok(Value::I64(7)));
+        let result = run("(try (while (< 0 1) 0) (catch e 0))");
+        assert_eq!(result, Err(RuntimeError::LimitExceeded(Limit::Instructions(10000))));
+    }
+}
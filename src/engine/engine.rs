@@ -0,0 +1,330 @@
+use std::{any::{Any, TypeId}, collections::HashMap, fs, path::Path, rc::Rc};
+
+use crate::{asm::{AsmBuilder, DirsLoader, ModuleLoader}, ast::{Ast, AstBuilder}, bytecode::{Bytecode, BytecodeBuilder, Capabilities, Runner, RunnerLimits, XFnContext}, token_stream::TokenStream, value::{HostError, IntoXFn, Value, XFn}};
+
+use super::{math, BindError, CompileError, Package};
+
+/// The host side of jisp: the host functions, the packages of them, the
+/// loader of the modules, the limits, the capabilities and the data for the
+/// runners.
+///
+/// The bytecode only keeps the names of the host functions, which are bound
+/// when [Engine::runner] creates the [Runner]. So the bytecode compiled once
+/// can run with the host functions of another engine.
+pub struct Engine {
+    xfns: HashMap<String, Rc<XFn>>,
+    packages: HashMap<String, Vec<String>>, // The names of the host functions by the path of the package.
+    loader: Option<Rc<dyn ModuleLoader>>, // The loader of the modules, or the files under the read_dirs.
+    limits: RunnerLimits,
+    capabilities: Capabilities,
+    data: HashMap<TypeId, Rc<dyn Any>>,
+}
+
+impl Engine {
+    /// Build an [Engine] with the standard packages, like `std/math`, and
+    /// the default [RunnerLimits].
+    pub fn new() -> Self {
+        let mut engine = Self {
+            xfns: HashMap::new(),
+            packages: HashMap::new(),
+            loader: None,
+            limits: RunnerLimits::default(),
+            capabilities: Capabilities::none(),
+            data: HashMap::new(),
+        };
+        engine.register_package(math::package());
+        engine
+    }
+
+    pub fn register_xfn<F>(&mut self, name: String, xfn: F) where F: Fn(Vec<Value>) -> Value + 'static {
+        self.push_xfn(XFn::new(name, xfn));
+    }
+
+    /// Register a plain Rust function like `fn(i64, String) -> Result<bool, E>`,
+    /// where `E` converts to [HostError]. The arity and the types of the
+    /// arguments are checked by [FromValue](crate::value::FromValue).
+    pub fn register_fn<Args, F>(&mut self, name: String, f: F) where F: IntoXFn<Args> {
+        self.push_xfn(f.into_xfn(name));
+    }
+
+    /// Register a host function with the [XFnContext], which can call the
+    /// jisp functions passed to it.
+    pub fn register_xfn_with_context<F>(&mut self, name: String, xfn: F)
+    where
+        F: Fn(&XFnContext<'_>, Vec<Value>) -> Result<Value, HostError> + 'static,
+    {
+        self.push_xfn(XFn::with_context(name, xfn));
+    }
+
+    /// Register the host function, the old one with the same name is replaced.
+    fn push_xfn(&mut self, xfn: XFn) {
+        self.xfns.insert(xfn.name().to_string(), Rc::new(xfn));
+    }
+
+    /// Register the [Package], the old one with the same path is replaced.
+    pub fn register_package(&mut self, package: Package) {
+        let (path, xfns) = package.into_xfns();
+        self.remove_package(&path);
+        let names = xfns.iter().map(|xfn| xfn.name().to_string()).collect();
+        for xfn in xfns {
+            self.push_xfn(xfn);
+        }
+        self.packages.insert(path, names);
+    }
+
+    /// Remove the package, like `std/math`, so the scripts of this engine
+    /// can not import it, and the bytecode using it can not run.
+    pub fn remove_package(&mut self, path: &str) {
+        for name in self.packages.remove(path).unwrap_or_default() {
+            self.xfns.remove(&name);
+        }
+    }
+
+    /// Load the modules imported by the scripts of this engine by the
+    /// [ModuleLoader]. By default, only the files under
+    /// [Capabilities::read_dirs] can be imported, so nothing by
+    /// [Capabilities::none].
+    pub fn set_loader<L: ModuleLoader + 'static>(&mut self, loader: L) {
+        self.loader = Some(Rc::new(loader));
+    }
+
+    /// Use the [RunnerLimits] for the runners created later.
+    pub fn set_limits(&mut self, limits: RunnerLimits) {
+        self.limits = limits;
+    }
+
+    /// Allow the I/O builtins of the runners created later, and the imports
+    /// of the scripts compiled later, by the [Capabilities], nothing is
+    /// allowed by default.
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.capabilities = capabilities;
+    }
+
+    /// Attach the data for the host functions, see [Runner::set_data]. The
+    /// data is shared by all the runners created later.
+    pub fn set_data<T: Any>(&mut self, data: T) {
+        self.data.insert(TypeId::of::<T>(), Rc::new(data));
+    }
+
+    /// The [AsmBuilder] with the host functions declared and the loader of
+    /// the modules set, to declare the parameters before building.
+    pub fn asm_builder(&self, ast: Ast) -> AsmBuilder {
+        let mut asm_builder = AsmBuilder::new(ast);
+        match &self.loader {
+            Some(loader) => asm_builder.set_loader(Rc::clone(loader)),
+            None => asm_builder.set_loader(DirsLoader::new(self.capabilities.read_dirs.clone())),
+        }
+        for (path, names) in &self.packages {
+            asm_builder.declare_package(path.clone(), names.clone());
+        }
+        let in_packages: Vec<&String> = self.packages.values().flatten().collect();
+        for name in self.xfns.keys().filter(|name| !in_packages.contains(name)) {
+            asm_builder.declare_xfn(name.clone());
+        }
+        asm_builder
+    }
+
+    /// Compile the source with the host functions declared.
+    pub fn compile(&self, source: &str) -> Result<Bytecode, CompileError> {
+        self.compile_with(source, &[])
+    }
+
+    /// Compile the source with the parameters of the main function declared,
+    /// the values of them are given by [Runner::run_with] in order.
+    pub fn compile_with(&self, source: &str, params: &[&str]) -> Result<Bytecode, CompileError> {
+        let ast = AstBuilder::new(TokenStream::new(source)).try_build().map_err(CompileError::Syntax)?;
+        let mut asm_builder = self.asm_builder(ast);
+        for param in params {
+            asm_builder.declare_param(param.to_string());
+        }
+        Ok(BytecodeBuilder::new(asm_builder.try_build()?).build())
+    }
+
+    /// Compile the file, the imports in it are relative to it.
+    pub fn compile_file(&self, path: &Path) -> Result<Bytecode, CompileError> {
+        let source = fs::read_to_string(path).map_err(|err| CompileError::Read(err.to_string()))?;
+        let ast = AstBuilder::new(TokenStream::new(&source)).try_build().map_err(CompileError::Syntax)?;
+        let mut asm_builder = self.asm_builder(ast);
+        asm_builder.set_path(path.to_path_buf());
+        Ok(BytecodeBuilder::new(asm_builder.try_build()?).build())
+    }
+
+    /// Create the [Runner] of the bytecode, with the host functions bound by
+    /// their names.
+    pub fn runner(&self, bytecode: Bytecode) -> Result<Runner, BindError> {
+        let xfns = bytecode.xfns.iter()
+            .map(|name| self.xfns.get(name).cloned().ok_or_else(|| BindError::UnknownXFn(name.clone())))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut runner = Runner::with_limits(bytecode, self.limits);
+        runner.set_capabilities(self.capabilities.clone());
+        runner.bind(xfns, self.data.clone());
+        Ok(runner)
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::asm::ImportError;
+
+    use super::*;
+
+    #[test]
+    fn binding() {
+        let mut engine = Engine::new();
+        engine.register_fn("x_greet".to_string(), |name: String| format!("hello {}", name));
+        engine.register_fn("x_unused".to_string(), || 0);
+        let bytecode = engine.compile(r#"(x_greet "jisp")"#).unwrap();
+        assert_eq!(bytecode.xfns, vec!["x_greet".to_string()]);
+        let result = engine.runner(bytecode).unwrap().run();
+        assert_eq!(result, Ok(Value::Str("hello jisp".to_string())));
+
+        // The host function taking the values as they are.
+        engine.register_xfn("x_add_3".to_string(), |args: Vec<Value>| match args[..] {
+            [Value::I64(val)] => Value::I64(val + 3),
+            _ => Value::Null,
+        });
+        let result = engine.runner(engine.compile("(x_add_3 5)").unwrap()).unwrap().run();
+        assert_eq!(result, Ok(Value::I64(8)));
+
+        // The same bytecode runs with the host functions of another engine.
+        let mut other = Engine::new();
+        other.register_fn("x_greet".to_string(), |name: String| format!("bye {}", name));
+        let result = other.runner(engine.compile(r#"(x_greet "jisp")"#).unwrap()).unwrap().run();
+        assert_eq!(result, Ok(Value::Str("bye jisp".to_string())));
+
+        let result = Engine::new().runner(engine.compile(r#"(x_greet "jisp")"#).unwrap()).err();
+        assert_eq!(result, Some(BindError::UnknownXFn("x_greet".to_string())));
+    }
+
+    #[test]
+    fn packages() {
+        let mut package = Package::new("acme/geo".to_string());
+        package.register_fn("area", |w: i64, h: i64| w * h);
+        let mut engine = Engine::new();
+        engine.register_package(package);
+
+        let bytecode = engine.compile(r#"(import "acme/geo") (import "std/math" [max]) (max (geo/area 2 3) 5)"#).unwrap();
+        assert_eq!(engine.runner(bytecode).unwrap().run(), Ok(Value::I64(6)));
+
+        // The sandboxed engine can not run the bytecode using the package.
+        let mut sandboxed = Engine::new();
+        sandboxed.remove_package("std/math");
+        let result = sandboxed.runner(engine.compile(r#"(import "std/math" [sqrt]) (sqrt 4)"#).unwrap()).err();
+        assert_eq!(result, Some(BindError::UnknownXFn("math/sqrt".to_string())));
+    }
+
+    #[test]
+    fn removed_package() {
+        let mut engine = Engine::new();
+        engine.remove_package("std/math");
+        let result = engine.compile(r#"(import "std/math")"#).err();
+        assert_eq!(result, Some(CompileError::Import(ImportError {
+            path: PathBuf::from("std/math"),
+            msg: "no such package".to_string(),
+        })));
+
+        let result = Engine::new().compile(r#"(import "std/math" [sqrt cbrtx])"#).err();
+        assert_eq!(result, Some(CompileError::Import(ImportError {
+            path: PathBuf::from("std/math"),
+            msg: "does not export cbrtx".to_string(),
+        })));
+    }
+
+    #[test]
+    fn invalid() {
+        let engine = Engine::new();
+        let bytecode = engine.compile_with("(+ $ it)", &["$", "it"]).unwrap();
+        assert_eq!(engine.runner(bytecode).unwrap().run_with(vec![Value::I64(1), Value::I64(2)]), Ok(Value::I64(3)));
+
+        let cases = [
+            ("(foo 1)", "no builtin function `foo`"),
+            ("(+ $ 1)", "no builtin function `$`"),
+            ("(if)", "`if` wants at least 2 values"),
+            ("()", "`()` is not a call"),
+            ("(break)", "`break` should be in a loop"),
+        ];
+        for (source, msg) in cases {
+            assert_eq!(engine.compile(source).err(), Some(CompileError::Invalid(msg.to_string())));
+        }
+        let result = engine.compile("(foo 1)").err().map(|err| err.to_string());
+        assert_eq!(result, Some("invalid source: no builtin function `foo`".to_string()));
+    }
+
+    #[test]
+    fn imports() {
+        let dir = std::env::temp_dir().join(format!("jisp-imports-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let util = dir.join("util.jisp");
+        fs::write(&util, "(fn twice [n] (* n 2)) (export twice)").unwrap();
+        let source = format!(r#"(import "{}" [twice]) (twice 21)"#, util.display());
+
+        // The sandboxed engine can not import, and can not tell whether the file exists.
+        let engine = Engine::new();
+        for path in [util.clone(), PathBuf::from("/etc/passwd"), PathBuf::from("../../no/such/file")] {
+            let result = engine.compile(&format!(r#"(import "{}")"#, path.display())).err();
+            assert_eq!(result, Some(CompileError::Import(ImportError { path, msg: "permission denied".to_string() })));
+        }
+
+        // The files under the read_dirs can be imported.
+        let mut engine = Engine::new();
+        engine.set_capabilities(Capabilities { read_dirs: vec![dir.clone()], ..Capabilities::none() });
+        assert_eq!(engine.runner(engine.compile(&source).unwrap()).unwrap().run(), Ok(Value::I64(42)));
+        let result = engine.compile(r#"(import "/etc/passwd")"#).err();
+        assert_eq!(result, Some(CompileError::Import(ImportError {
+            path: PathBuf::from("/etc/passwd"),
+            msg: "permission denied".to_string(),
+        })));
+
+        // The invalid module is an error.
+        fs::write(&util, "(fn twice [n]").unwrap();
+        let result = engine.compile(&source).err().map(|err| err.to_string());
+        assert_eq!(result, Some(format!("can not import {}: want RPARAM, I64 or LPARAM at line 1 column 14", util.display())));
+        fs::write(&util, "(fn twice [n] (* n 2)) (twice 1)").unwrap();
+        let result = engine.compile(&source).err().map(|err| err.to_string());
+        assert_eq!(result, Some(format!("can not import {}: should only have fn, import and export", util.display())));
+        fs::remove_dir_all(&dir).unwrap();
+
+        // The loader of the engine replaces the files.
+        struct Modules;
+
+        impl ModuleLoader for Modules {
+            fn load(&self, path: &Path) -> Result<String, String> {
+                match path.to_str() {
+                    Some("util") => Ok("(fn twice [n] (* n 2)) (export twice)".to_string()),
+                    _ => Err("no such module".to_string()),
+                }
+            }
+        }
+
+        let mut engine = Engine::new();
+        engine.set_loader(Modules);
+        let bytecode = engine.compile(r#"(import "util" [twice]) (twice 21)"#).unwrap();
+        assert_eq!(engine.runner(bytecode).unwrap().run(), Ok(Value::I64(42)));
+        let result = engine.compile(r#"(import "other")"#).err().map(|err| err.to_string());
+        assert_eq!(result, Some("can not import other: no such module".to_string()));
+    }
+
+    #[test]
+    fn limits_and_data() {
+        struct Greeting(String);
+
+        let mut engine = Engine::new();
+        engine.register_xfn_with_context("x_greeting".to_string(), |ctx, _| {
+            Ok(Value::Str(ctx.data::<Greeting>().unwrap().0.clone()))
+        });
+        engine.set_data(Greeting("hi".to_string()));
+        engine.set_limits(RunnerLimits { max_instructions: Some(100), ..RunnerLimits::default() });
+
+        let runner = engine.runner(engine.compile("(x_greeting)").unwrap()).unwrap();
+        assert_eq!(runner.run(), Ok(Value::Str("hi".to_string())));
+        assert_eq!(runner.limits().max_instructions, Some(100));
+    }
+}
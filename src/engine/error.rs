@@ -0,0 +1,65 @@
+use std::fmt::Display;
+
+use crate::asm::{AsmError, ImportError};
+
+/// The error raised when the [Engine](super::Engine) creates a
+/// [Runner](crate::bytecode::Runner) for the bytecode.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum BindError {
+    /// The bytecode calls a host function which is not registered.
+    UnknownXFn(String),
+}
+
+impl Display for BindError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BindError::UnknownXFn(name) => write!(f, "host function {} is not registered", name),
+        }
+    }
+}
+
+impl std::error::Error for BindError {}
+
+/// The error raised when the [Engine](super::Engine) compiles the source.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum CompileError {
+    /// The file of the source can not be read.
+    Read(String),
+
+    /// The source is not valid.
+    Syntax(String),
+
+    /// An imported module can not be loaded.
+    Import(ImportError),
+
+    /// The source is not a valid program, like calling an unknown function.
+    Invalid(String),
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompileError::Read(msg) => write!(f, "can not read the source: {}", msg),
+            CompileError::Syntax(msg) => write!(f, "syntax error: {}", msg),
+            CompileError::Import(err) => err.fmt(f),
+            CompileError::Invalid(msg) => write!(f, "invalid source: {}", msg),
+        }
+    }
+}
+
+impl From<ImportError> for CompileError {
+    fn from(err: ImportError) -> Self {
+        CompileError::Import(err)
+    }
+}
+
+impl From<AsmError> for CompileError {
+    fn from(err: AsmError) -> Self {
+        match err {
+            AsmError::Import(err) => CompileError::Import(err),
+            AsmError::Invalid(msg) => CompileError::Invalid(msg),
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
@@ -0,0 +1,64 @@
+//! The `std/math` package.
+
+use std::cmp::Ordering;
+
+use crate::value::{HostError, Value};
+
+use super::Package;
+
+pub fn package() -> Package {
+    let mut package = Package::new("std/math".to_string());
+    package.register_fn("pi", || std::f64::consts::PI);
+    package.register_fn("e", || std::f64::consts::E);
+    package.register_fn("sqrt", f64::sqrt);
+    package.register_fn("pow", f64::powf);
+    package.register_fn("exp", f64::exp);
+    package.register_fn("ln", f64::ln);
+    package.register_fn("log10", f64::log10);
+    package.register_fn("sin", f64::sin);
+    package.register_fn("cos", f64::cos);
+    package.register_fn("tan", f64::tan);
+    package.register_fn("floor", |x: f64| x.floor() as i64);
+    package.register_fn("ceil", |x: f64| x.ceil() as i64);
+    package.register_fn("round", |x: f64| x.round() as i64);
+    package.register_fn("abs", |x: Value| match x {
+        Value::I64(x) => x.checked_abs().map(Value::I64).ok_or_else(|| HostError::new("I64 overflows")),
+        Value::F64(x) => Ok(Value::F64(x.abs())),
+        x => Err(HostError::new(format!("wants NUMBER, got {}", x.type_name()))),
+    });
+    package.register_fn("min", |a: Value, b: Value| pick(a, b, Ordering::Less));
+    package.register_fn("max", |a: Value, b: Value| pick(a, b, Ordering::Greater));
+    package
+}
+
+/// Pick `b` if it is ordered as `want` to `a`, or `a`. The number is kept
+/// as is, I64 or F64.
+fn pick(a: Value, b: Value, want: Ordering) -> Result<Value, HostError> {
+    let order = match (&a, &b) {
+        (Value::I64(x), Value::I64(y)) => Some(y.cmp(x)),
+        (Value::I64(x), Value::F64(y)) => y.partial_cmp(&(*x as f64)),
+        (Value::F64(x), Value::I64(y)) => (*y as f64).partial_cmp(x),
+        (Value::F64(x), Value::F64(y)) => y.partial_cmp(x),
+        _ => return Err(HostError::new(format!("wants NUMBER, got {} and {}", a.type_name(), b.type_name()))),
+    };
+    Ok(if order == Some(want) { b } else { a })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::Engine;
+
+    use super::*;
+
+    #[test]
+    fn math() {
+        let engine = Engine::new();
+        let run = |source: &str| engine.runner(engine.compile(source).unwrap()).unwrap().run();
+        let result = run(r###"
+            (import "std/math" [sqrt floor])
+            (import "std/math")
+            [(sqrt 16) (floor (math/pow 2 (parse-float "0.5"))) (math/abs -3) (math/min 2 (parse-float "1.5")) (math/max 2 1)]
+        "###);
+        assert_eq!(result, Ok(Value::from_json("[4.0, 1, 3, 1.5, 2]").unwrap()));
+    }
+}
@@ -0,0 +1,9 @@
+mod engine;
+mod error;
+mod package;
+mod math;
+
+pub type Engine = engine::Engine;
+pub type BindError = error::BindError;
+pub type CompileError = error::CompileError;
+pub type Package = package::Package;
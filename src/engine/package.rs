@@ -0,0 +1,49 @@
+use crate::{bytecode::XFnContext, value::{HostError, IntoXFn, Value, XFn}};
+
+/// The host functions registered to an [Engine](super::Engine) at once,
+/// which jisp code imports by the path, like `(import "std/math")`.
+///
+/// The functions are named in the namespace of the last part of the path,
+/// like `math/sqrt` of `std/math`.
+pub struct Package {
+    path: String,
+    namespace: String,
+    xfns: Vec<XFn>,
+}
+
+impl Package {
+    pub fn new(path: String) -> Self {
+        let namespace = path.rsplit('/').next().unwrap_or_default().to_string();
+        Self { path, namespace, xfns: vec![] }
+    }
+
+    /// The path to import the package by.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn register_xfn<F>(&mut self, name: &str, xfn: F) where F: Fn(Vec<Value>) -> Value + 'static {
+        self.xfns.push(XFn::new(self.full_name(name), xfn));
+    }
+
+    /// Register a plain Rust function, see [Engine::register_fn](super::Engine::register_fn).
+    pub fn register_fn<Args, F>(&mut self, name: &str, f: F) where F: IntoXFn<Args> {
+        self.xfns.push(f.into_xfn(self.full_name(name)));
+    }
+
+    /// Register a host function with the [XFnContext].
+    pub fn register_xfn_with_context<F>(&mut self, name: &str, xfn: F)
+    where
+        F: Fn(&XFnContext<'_>, Vec<Value>) -> Result<Value, HostError> + 'static,
+    {
+        self.xfns.push(XFn::with_context(self.full_name(name), xfn));
+    }
+
+    fn full_name(&self, name: &str) -> String {
+        format!("{}/{}", self.namespace, name)
+    }
+
+    pub(crate) fn into_xfns(self) -> (String, Vec<XFn>) {
+        (self.path, self.xfns)
+    }
+}
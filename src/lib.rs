@@ -0,0 +1,8 @@
+#![allow(clippy::module_inception)]
+
+pub mod token_stream;
+pub mod ast;
+pub mod value;
+pub mod asm;
+pub mod bytecode;
+pub mod engine;
@@ -1,36 +1,168 @@
-mod token_stream;
-mod ast;
-mod value;
-mod asm;
-mod bytecode;
+use std::{env, fs::File, io::{self, BufRead, BufReader, Read}, path::Path, process::exit};
 
-use std::{env, fs};
+use jisp::{bytecode::{self, Capabilities}, engine::Engine, value::{Array, JsonError, Value}};
 
-use value::Value;
+const USAGE: &str = "\
+Usage: jisp <file>
+       jisp query [options] <expr> [input.json]
+
+Query options:
+    -r, --raw-output  print strings without quotes
+    -c, --compact     print JSON in one line
+    -s, --slurp       read the input as NDJSON and bind the array of values
+    -n, --ndjson      read the input as NDJSON and run the expr per line";
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let content = fs::read_to_string(&args[1]).unwrap();
-    let token_stream = token_stream::TokenStream::new(&content);
-    let ast = ast::AstBuilder::new(token_stream).build();
-    let mut asm_builder = asm::AsmBuilder::new(ast);
-    asm_builder.register_xfn("x_fac".to_string(), |args: Vec<Value>| {
-        fn fac(n: i64) -> i64 {
-            if n == 0 { 1 } else { fac(n - 1) * n }
+    match args.get(1).map(|arg| arg.as_str()) {
+        Some("query") => query(&args[2..]),
+        Some(path) => run_file(path),
+        None => fail(USAGE),
+    }
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    exit(1);
+}
+
+fn run_file(path: &str) {
+    let mut engine = Engine::new();
+    // The file is trusted as the user runs it.
+    engine.set_capabilities(Capabilities::all());
+    engine.register_fn("x_fac".to_string(), |n: i64| -> Result<i64, String> {
+        (1..=n).try_fold(1i64, |fac, i| fac.checked_mul(i))
+            .ok_or_else(|| format!("{}! overflows I64", n))
+    });
+    let bytecode = match engine.compile_file(Path::new(path)) {
+        Ok(bytecode) => bytecode,
+        Err(err) => fail(&format!("Error: {}: {}", path, err)),
+    };
+    let runner = bind(&engine, bytecode);
+    let val = match runner.run() {
+        Ok(val) => val,
+        Err(err) => fail(&format!("Runtime: {}", err)),
+    };
+    println!("{}", runner.display(&val));
+}
+
+/// The options of `jisp query`.
+#[derive(Default)]
+struct QueryOptions {
+    raw_output: bool,
+    compact: bool,
+    slurp: bool,
+    ndjson: bool,
+}
+
+/// Run `jisp query`, the input document is bound to `$` and `it`.
+fn query(args: &[String]) {
+    let mut options = QueryOptions::default();
+    let mut positional = vec![];
+    for arg in args {
+        match arg.as_str() {
+            "-r" | "--raw-output" => options.raw_output = true,
+            "-c" | "--compact" => options.compact = true,
+            "-s" | "--slurp" => options.slurp = true,
+            "-n" | "--ndjson" => options.ndjson = true,
+            arg if arg.starts_with('-') && arg != "-" => fail(&format!("Error: unknown option {}\n\n{}", arg, USAGE)),
+            arg => positional.push(arg),
         }
-        assert!(args.len() == 1);
-        match args[0] {
-            Value::I64(val) => Value::I64(fac(val)),
-            _ => panic!("unexpected value"),
+    }
+    let (expr, input_path) = match positional[..] {
+        [expr] => (expr, "-"),
+        [expr, input_path] => (expr, input_path),
+        _ => fail(USAGE),
+    };
+
+    let runner = compile_query(expr);
+    let input = open_input(input_path);
+    if options.ndjson {
+        // Run per line as it is read, so the input is never loaded at once.
+        for (lineno, line) in ndjson_lines(input_path, input) {
+            let doc = parse_json(input_path, lineno, &line);
+            print_json(&run_query(&runner, doc), &options);
         }
-    });
-    let asm = asm_builder.build();
-    let bytecode = bytecode::BytecodeBuilder::new(asm).build();
-    let val = bytecode::Runner::new(bytecode).run();
-    match val {
-        Value::I64(val) => println!("{}", val),
-        Value::Bool(val) => println!("{}", val),
-        Value::Str(val) => println!("{:?}", val),
-        _ => panic!("unexpected val type"),
+    } else if options.slurp {
+        let docs: Vec<Value> = ndjson_lines(input_path, input)
+            .map(|(lineno, line)| parse_json(input_path, lineno, &line))
+            .collect();
+        print_json(&run_query(&runner, Value::Array(Array::from(docs))), &options);
+    } else {
+        let mut content = String::new();
+        let mut input = input;
+        if let Err(err) = input.read_to_string(&mut content) {
+            fail(&format!("Error: {}: {}", input_path, err));
+        }
+        let doc = parse_json(input_path, 1, &content);
+        print_json(&run_query(&runner, doc), &options);
+    }
+}
+
+/// Open the input file, or the stdin if the path is `-`.
+fn open_input(input_path: &str) -> Box<dyn BufRead> {
+    if input_path == "-" {
+        return Box::new(io::stdin().lock());
+    }
+    match File::open(input_path) {
+        Ok(file) => Box::new(BufReader::new(file)),
+        Err(err) => fail(&format!("Error: {}: {}", input_path, err)),
+    }
+}
+
+/// The non-empty lines of NDJSON with their line numbers, read lazily.
+fn ndjson_lines(input_path: &str, input: Box<dyn BufRead>) -> impl Iterator<Item = (usize, String)> + '_ {
+    input.lines()
+        .enumerate()
+        .map(move |(i, line)| match line {
+            Ok(line) => (i + 1, line),
+            Err(err) => fail(&format!("Error: {}: {}", input_path, err)),
+        })
+        .filter(|(_, line)| !line.trim().is_empty())
+}
+
+/// Parse the JSON starting at the line `lineno` of the input.
+fn parse_json(input_path: &str, lineno: usize, source: &str) -> Value {
+    match Value::from_json(source) {
+        Ok(val) => val,
+        Err(JsonError::Syntax { line, column, msg }) => fail(&format!(
+            "Error: {}: {} at line {} column {}", input_path, msg, lineno + line - 1, column,
+        )),
+        Err(err) => fail(&format!("Error: {}: {}", input_path, err)),
+    }
+}
+
+/// Compile the query once, the document is bound to `$` and `it`.
+fn compile_query(expr: &str) -> bytecode::Runner {
+    let engine = Engine::new();
+    match engine.compile_with(expr, &["$", "it"]) {
+        Ok(bytecode) => bind(&engine, bytecode),
+        Err(err) => fail(&format!("Error: {}", err)),
+    }
+}
+
+fn bind(engine: &Engine, bytecode: bytecode::Bytecode) -> bytecode::Runner {
+    match engine.runner(bytecode) {
+        Ok(runner) => runner,
+        Err(err) => fail(&format!("Error: {}", err)),
+    }
+}
+
+fn run_query(runner: &bytecode::Runner, doc: Value) -> Value {
+    match runner.run_with(vec![doc.clone(), doc]) {
+        Ok(val) => val,
+        Err(err) => fail(&format!("Runtime: {}", err)),
+    }
+}
+
+fn print_json(val: &Value, options: &QueryOptions) {
+    let json = match val {
+        Value::Str(val) if options.raw_output => Ok(val.clone()),
+        val if options.compact => val.to_json(),
+        val => val.to_json_pretty(),
+    };
+    match json {
+        Ok(json) => println!("{}", json),
+        Err(err) => fail(&format!("Error: {}", err)),
     }
 }
@@ -1,5 +1,5 @@
 /// One token of the source.
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Token {
     pos: TokenPos,
     val: TokenVal,
@@ -11,7 +11,8 @@ pub struct TokenPos {
     pub offset: u32,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum TokenVal {
     /// The '('.
     Lparam,
@@ -24,6 +25,12 @@ pub enum TokenVal {
 
     /// The ']'.
     Rsquare,
+
+    /// The '{'.
+    Lbrace,
+
+    /// The '}'.
+    Rbrace,
     
     /// The symbol.
     Sym(String),
@@ -34,8 +41,14 @@ pub enum TokenVal {
     /// The integer of 64-bits.
     I64(i64),
 
+    /// The float of 64-bits, like `1.5` or `1e-7`.
+    F64(f64),
+
     /// The end of file.
     EOF,
+
+    /// The token which is not valid, with the reason.
+    Error(&'static str),
 }
 
 impl Token {
@@ -59,10 +72,14 @@ impl TokenVal {
             TokenVal::Rparam => "RPARAM",
             TokenVal::Lsquare => "LSQUARE",
             TokenVal::Rsquare => "RSQUARE",
+            TokenVal::Lbrace => "LBRACE",
+            TokenVal::Rbrace => "RBRACE",
             TokenVal::I64(_) => "I64",
+            TokenVal::F64(_) => "F64",
             TokenVal::Sym(_) => "SYM",
             TokenVal::Str(_) => "STR",
             TokenVal::EOF => "EOF",
+            TokenVal::Error(_) => "ERROR",
         }
     }
 }
\ No newline at end of file
@@ -36,8 +36,8 @@ impl<'a> TokenStream<'a> {
         self.source_plain
     }
 
-    fn next_num(&mut self) -> Option<Token> {
-        let mut num = 0_i64;
+    fn next_num(&mut self) -> Token {
+        let mut num = String::new();
         let mut next_pos = self.pos;
         loop {
             let peek_char = self.source.peek();
@@ -45,19 +45,23 @@ impl<'a> TokenStream<'a> {
                 None => break,
                 Some(c) => *c,
             };
-            match peek_char {
-                c @ '0'..='9' => {
-                    self.skip_char();
-                    next_pos.offset += 1;
-                    num = num * 10 + (c as i64) - ('0' as i64);
-                }
-                _ => break,
+            // The digits, and the `.` and exponent of floats like `1.5e-7`.
+            let in_num = match peek_char {
+                '0'..='9' | '.' | 'e' | 'E' => true,
+                '-' | '+' => num.ends_with(['e', 'E']),
+                _ => false,
+            };
+            if !in_num {
+                break;
             }
+            self.skip_char();
+            next_pos.offset += 1;
+            num.push(peek_char);
         }
-        let tok = Token::new(self.pos, TokenVal::I64(num));
+        let tok = Token::new(self.pos, number(&num));
         self.pos = next_pos;
         self.eof_pos = self.pos;
-        Some(tok)
+        tok
     }
 
     // TODO Need a error.
@@ -80,6 +84,23 @@ impl<'a> TokenStream<'a> {
             };
             match peek_char {
                 '"' => break,
+                // The escapes written by the `Display` of values, the others
+                // are kept as is, like `\d` of regexes.
+                '\\' => {
+                    self.skip_char();
+                    next_pos.offset += 2;
+                    match self.source.next() {
+                        Some('n') => str.push('\n'),
+                        Some('r') => str.push('\r'),
+                        Some('t') => str.push('\t'),
+                        Some(ch @ ('"' | '\\')) => str.push(ch),
+                        Some(ch) => {
+                            str.push('\\');
+                            str.push(ch);
+                        }
+                        None => return Err(()),
+                    }
+                }
                 ch => {
                     self.skip_char();
                     next_pos.offset += 1;
@@ -101,7 +122,7 @@ impl<'a> TokenStream<'a> {
         Ok(tok)
     }
 
-    fn next_sym(&mut self) -> Option<Token> {
+    fn next_sym(&mut self) -> Token {
         let mut sym = String::new();
         let mut next_pos = self.pos;
         loop {
@@ -111,7 +132,7 @@ impl<'a> TokenStream<'a> {
                 Some(c) => *c,
             };
             match peek_char {
-                ')' | ']' | ' ' | '\t' | '\n' => break,
+                ')' | ']' | '}' | ' ' | '\t' | '\n' | ',' => break,
                 ch => {
                     self.skip_char();
                     next_pos.offset += 1;
@@ -119,10 +140,15 @@ impl<'a> TokenStream<'a> {
                 }
             }
         }
-        let tok = Token::new(self.pos, TokenVal::Sym(sym));
+        // A negative number, like `-1`, but `-` alone is a symbol.
+        let val = match sym.strip_prefix('-') {
+            Some(digits) if digits.starts_with(|c: char| c.is_ascii_digit()) => number(&sym),
+            _ => TokenVal::Sym(sym),
+        };
+        let tok = Token::new(self.pos, val);
         self.pos = next_pos;
         self.eof_pos = self.pos;
-        Some(tok)
+        tok
     }
 
     fn skip_char(&mut self) {
@@ -130,6 +156,22 @@ impl<'a> TokenStream<'a> {
     }
 }
 
+/// The integer, or the float if there is a `.` or an exponent, or the error
+/// if it overflows or is not valid.
+fn number(num: &str) -> TokenVal {
+    let val = match num.contains(['.', 'e', 'E']) {
+        true => num.parse().map(TokenVal::F64).ok(),
+        false => num.parse().map(TokenVal::I64).ok(),
+    };
+    match val {
+        Some(val) => val,
+        None if num.trim_start_matches('-').chars().all(|c| c.is_ascii_digit()) => {
+            TokenVal::Error("integer literal overflows I64")
+        }
+        None => TokenVal::Error("number literal is not valid"),
+    }
+}
+
 impl<'a> Iterator for TokenStream<'a> {
     type Item = Token;
 
@@ -150,7 +192,8 @@ impl<'a> Iterator for TokenStream<'a> {
 
             // Try to build a token from chars.
             let result = match peek_char {
-                ' ' | '\t' | '\n' => {
+                // The ',' is a whitespace, to write arrays like [1, 2, 3].
+                ' ' | '\t' | '\n' | ',' => {
                     self.skip_char();
                     if peek_char == '\n' {
                         self.pos.lineno += 1;
@@ -160,13 +203,15 @@ impl<'a> Iterator for TokenStream<'a> {
                     }
                     continue;
                 },
-                token @ ( '(' | ')' | '[' | ']' ) => {
+                token @ ( '(' | ')' | '[' | ']' | '{' | '}' ) => {
                     self.skip_char();
                     let token = match token {
                         '(' => Token::new(self.pos, TokenVal::Lparam),
                         ')' => Token::new(self.pos, TokenVal::Rparam),
                         '[' => Token::new(self.pos, TokenVal::Lsquare),
                         ']' => Token::new(self.pos, TokenVal::Rsquare),
+                        '{' => Token::new(self.pos, TokenVal::Lbrace),
+                        '}' => Token::new(self.pos, TokenVal::Rbrace),
                         _ => panic!("uncovered token"),
                     };
                     self.pos.offset += 1;
@@ -174,8 +219,8 @@ impl<'a> Iterator for TokenStream<'a> {
                     Some(token)
                 },
                 '"' => self.next_str().ok(),
-                '0'..='9' => self.next_num(),
-                _ => self.next_sym(),
+                '0'..='9' => Some(self.next_num()),
+                _ => Some(self.next_sym()),
             };
             return result;
         }
@@ -277,4 +322,79 @@ mod tests {
             Token::new(TokenPos{ lineno: 1, offset: 23 }, TokenVal::EOF),
         ]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn arrays() {
+        let token_stream = TokenStream::new("[1, 2]");
+        assert_eq!(token_stream.collect::<Vec<Token>>(), vec![
+            Token::new(TokenPos{ lineno: 1, offset: 1 }, TokenVal::Lsquare),
+            Token::new(TokenPos{ lineno: 1, offset: 2 }, TokenVal::I64(1)),
+            Token::new(TokenPos{ lineno: 1, offset: 5 }, TokenVal::I64(2)),
+            Token::new(TokenPos{ lineno: 1, offset: 6 }, TokenVal::Rsquare),
+            Token::new(TokenPos{ lineno: 1, offset: 7 }, TokenVal::EOF),
+        ]);
+    }
+
+    #[test]
+    fn maps() {
+        let token_stream = TokenStream::new("{\"a\" 1}");
+        assert_eq!(token_stream.collect::<Vec<Token>>(), vec![
+            Token::new(TokenPos{ lineno: 1, offset: 1 }, TokenVal::Lbrace),
+            Token::new(TokenPos{ lineno: 1, offset: 2 }, TokenVal::Str("a".to_string())),
+            Token::new(TokenPos{ lineno: 1, offset: 6 }, TokenVal::I64(1)),
+            Token::new(TokenPos{ lineno: 1, offset: 7 }, TokenVal::Rbrace),
+            Token::new(TokenPos{ lineno: 1, offset: 8 }, TokenVal::EOF),
+        ]);
+    }
+
+    #[test]
+    fn escapes() {
+        let token_stream = TokenStream::new(r#""a\"\\\n\d" 1"#);
+        assert_eq!(token_stream.collect::<Vec<Token>>(), vec![
+            Token::new(TokenPos{ lineno: 1, offset: 1 }, TokenVal::Str("a\"\\\n\\d".to_string())),
+            Token::new(TokenPos{ lineno: 1, offset: 13 }, TokenVal::I64(1)),
+            Token::new(TokenPos{ lineno: 1, offset: 14 }, TokenVal::EOF),
+        ]);
+    }
+
+    #[test]
+    fn numbers() {
+        let token_stream = TokenStream::new("(- -12 9223372036854775807 -9223372036854775808 -x)");
+        assert_eq!(token_stream.map(|tok| tok.val().clone()).collect::<Vec<TokenVal>>(), vec![
+            TokenVal::Lparam,
+            TokenVal::Sym("-".to_string()),
+            TokenVal::I64(-12),
+            TokenVal::I64(i64::MAX),
+            TokenVal::I64(i64::MIN),
+            TokenVal::Sym("-x".to_string()),
+            TokenVal::Rparam,
+            TokenVal::EOF,
+        ]);
+
+        let token_stream = TokenStream::new("[2.0 -1.5 1e300 1.5e-7 -0.0 1-x]");
+        assert_eq!(token_stream.map(|tok| tok.val().clone()).collect::<Vec<TokenVal>>(), vec![
+            TokenVal::Lsquare,
+            TokenVal::F64(2.0),
+            TokenVal::F64(-1.5),
+            TokenVal::F64(1e300),
+            TokenVal::F64(1.5e-7),
+            TokenVal::F64(-0.0),
+            TokenVal::I64(1),
+            TokenVal::Sym("-x".to_string()),
+            TokenVal::Rsquare,
+            TokenVal::EOF,
+        ]);
+
+        // The number overflows, or is not valid.
+        let token_stream = TokenStream::new("(+ 9223372036854775808 -9223372036854775809 1.2.3)");
+        assert_eq!(token_stream.collect::<Vec<Token>>(), vec![
+            Token::new(TokenPos{ lineno: 1, offset: 1 }, TokenVal::Lparam),
+            Token::new(TokenPos{ lineno: 1, offset: 2 }, TokenVal::Sym("+".to_string())),
+            Token::new(TokenPos{ lineno: 1, offset: 4 }, TokenVal::Error("integer literal overflows I64")),
+            Token::new(TokenPos{ lineno: 1, offset: 24 }, TokenVal::Error("integer literal overflows I64")),
+            Token::new(TokenPos{ lineno: 1, offset: 45 }, TokenVal::Error("number literal is not valid")),
+            Token::new(TokenPos{ lineno: 1, offset: 50 }, TokenVal::Rparam),
+            Token::new(TokenPos{ lineno: 1, offset: 51 }, TokenVal::EOF),
+        ]);
+    }
+}
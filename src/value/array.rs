@@ -0,0 +1,133 @@
+use std::{cell::{Ref, RefCell}, fmt::Debug, hash::{Hash, Hasher}, mem::take, rc::Rc};
+
+use super::{value::drop_values, Value, MAX_NESTING};
+
+/// The array, shared by all its clones.
+#[derive(Clone, Default)]
+pub struct Array {
+    inner: Rc<RefCell<Vec<Value>>>,
+}
+
+impl Array {
+    /// Build an empty [Array].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.borrow().is_empty()
+    }
+
+    /// Get the value by index, or `None` if it is out of range.
+    pub fn get(&self, index: usize) -> Option<Value> {
+        self.inner.borrow().get(index).cloned()
+    }
+
+    /// Set the value by index, return false if it is out of range.
+    pub fn set(&self, index: usize, val: Value) -> bool {
+        match self.inner.borrow_mut().get_mut(index) {
+            Some(old) => {
+                *old = val;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn push(&self, val: Value) {
+        self.inner.borrow_mut().push(val);
+    }
+
+    pub fn pop(&self) -> Option<Value> {
+        self.inner.borrow_mut().pop()
+    }
+
+    /// Remove the value by index and shift the rest, or `None` if it is out
+    /// of range.
+    pub fn remove(&self, index: usize) -> Option<Value> {
+        let mut values = self.inner.borrow_mut();
+        if index < values.len() {
+            Some(values.remove(index))
+        } else {
+            None
+        }
+    }
+
+    /// Borrow the values to read.
+    pub fn values(&self) -> Ref<'_, Vec<Value>> {
+        self.inner.borrow()
+    }
+
+    /// Return true if the two arrays are the same one.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    /// The identity of the array, shared by its clones.
+    pub(super) fn id(&self) -> usize {
+        Rc::as_ptr(&self.inner) as *const () as usize
+    }
+
+    /// Take the values out if this is the last clone of the array.
+    pub(super) fn take_values(&mut self) -> Vec<Value> {
+        match Rc::get_mut(&mut self.inner) {
+            Some(inner) => take(inner.get_mut()),
+            None => vec![],
+        }
+    }
+
+    pub(super) fn eq_within(&self, other: &Self, depth: usize) -> bool {
+        if self.ptr_eq(other) {
+            return true;
+        }
+        let (values, others) = (self.values(), other.values());
+        depth > 0 && values.len() == others.len()
+            && values.iter().zip(others.iter()).all(|(a, b)| a.eq_within(b, depth - 1))
+    }
+
+    pub(super) fn hash_within<H: Hasher>(&self, state: &mut H, depth: usize) {
+        let values = self.values();
+        values.len().hash(state);
+        if depth > 0 {
+            for val in values.iter() {
+                val.hash_within(state, depth - 1);
+            }
+        }
+    }
+}
+
+impl From<Vec<Value>> for Array {
+    fn from(values: Vec<Value>) -> Self {
+        Self { inner: Rc::new(RefCell::new(values)) }
+    }
+}
+
+impl PartialEq for Array {
+    fn eq(&self, other: &Self) -> bool {
+        self.eq_within(other, MAX_NESTING)
+    }
+}
+
+impl Eq for Array {}
+
+impl Hash for Array {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.hash_within(state, MAX_NESTING);
+    }
+}
+
+impl Drop for Array {
+    fn drop(&mut self) {
+        drop_values(self.take_values());
+    }
+}
+
+impl Debug for Array {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.values().iter()).finish()
+    }
+}
@@ -0,0 +1,316 @@
+//! The conversions between [Value] and Rust types, so plain Rust functions
+//! can be registered as [XFn] by [Engine::register_fn](crate::engine::Engine::register_fn).
+
+use std::rc::Rc;
+
+use super::{Array, HostError, HostObject, Map, Value, XFn};
+
+/// Convert a [Value] to the Rust type, for the arguments of host functions.
+pub trait FromValue: Sized {
+    /// The name of the wanted type in errors.
+    const TYPE_NAME: &'static str;
+
+    /// Return `None` if the value is not of the type.
+    fn from_value(val: Value) -> Option<Self>;
+}
+
+/// Convert the Rust type to a [Value], for the results of host functions.
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+impl FromValue for Value {
+    const TYPE_NAME: &'static str = "ANY";
+
+    fn from_value(val: Value) -> Option<Self> {
+        Some(val)
+    }
+}
+
+impl FromValue for i64 {
+    const TYPE_NAME: &'static str = "I64";
+
+    fn from_value(val: Value) -> Option<Self> {
+        match val {
+            Value::I64(val) => Some(val),
+            _ => None,
+        }
+    }
+}
+
+/// Both numbers are accepted, I64 is converted.
+impl FromValue for f64 {
+    const TYPE_NAME: &'static str = "NUMBER";
+
+    fn from_value(val: Value) -> Option<Self> {
+        match val {
+            Value::I64(val) => Some(val as f64),
+            Value::F64(val) => Some(val),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for bool {
+    const TYPE_NAME: &'static str = "BOOL";
+
+    fn from_value(val: Value) -> Option<Self> {
+        match val {
+            Value::Bool(val) => Some(val),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for String {
+    const TYPE_NAME: &'static str = "STR";
+
+    fn from_value(val: Value) -> Option<Self> {
+        match val {
+            Value::Str(val) => Some(val),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for Array {
+    const TYPE_NAME: &'static str = "ARRAY";
+
+    fn from_value(val: Value) -> Option<Self> {
+        match val {
+            Value::Array(arr) => Some(arr),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for Map {
+    const TYPE_NAME: &'static str = "MAP";
+
+    fn from_value(val: Value) -> Option<Self> {
+        match val {
+            Value::Map(map) => Some(map),
+            _ => None,
+        }
+    }
+}
+
+/// Any object of the host, see [Value::as_host] to get the `T` of it.
+impl FromValue for Rc<dyn HostObject> {
+    const TYPE_NAME: &'static str = "HOST";
+
+    fn from_value(val: Value) -> Option<Self> {
+        match val {
+            Value::Host(obj) => Some(obj),
+            _ => None,
+        }
+    }
+}
+
+/// An array of which all values are of the type, the values are copied.
+impl<T: FromValue> FromValue for Vec<T> {
+    const TYPE_NAME: &'static str = "ARRAY";
+
+    fn from_value(val: Value) -> Option<Self> {
+        match val {
+            Value::Array(arr) => arr.values().iter().cloned().map(T::from_value).collect(),
+            _ => None,
+        }
+    }
+}
+
+/// `null` is `None`.
+impl<T: FromValue> FromValue for Option<T> {
+    const TYPE_NAME: &'static str = T::TYPE_NAME;
+
+    fn from_value(val: Value) -> Option<Self> {
+        match val {
+            Value::Null => Some(None),
+            val => T::from_value(val).map(Some),
+        }
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        Value::Null
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Value {
+        Value::I64(self)
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::F64(self)
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::Bool(self)
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::Str(self)
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        Value::Str(self.to_string())
+    }
+}
+
+impl IntoValue for Array {
+    fn into_value(self) -> Value {
+        Value::Array(self)
+    }
+}
+
+impl IntoValue for Map {
+    fn into_value(self) -> Value {
+        Value::Map(self)
+    }
+}
+
+impl IntoValue for Rc<dyn HostObject> {
+    fn into_value(self) -> Value {
+        Value::Host(self)
+    }
+}
+
+impl<T: HostObject> IntoValue for Rc<T> {
+    fn into_value(self) -> Value {
+        Value::Host(self)
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        Value::Array(Array::from(self.into_iter().map(T::into_value).collect::<Vec<Value>>()))
+    }
+}
+
+/// `None` is `null`.
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        match self {
+            Some(val) => val.into_value(),
+            None => Value::Null,
+        }
+    }
+}
+
+/// The result of host functions, a value, or a [Result] of which the error
+/// is converted to the [HostError] raised in jisp.
+pub trait IntoXFnResult {
+    fn into_xfn_result(self) -> Result<Value, HostError>;
+}
+
+impl<T: IntoValue> IntoXFnResult for T {
+    fn into_xfn_result(self) -> Result<Value, HostError> {
+        Ok(self.into_value())
+    }
+}
+
+impl<T: IntoValue, E: Into<HostError>> IntoXFnResult for Result<T, E> {
+    fn into_xfn_result(self) -> Result<Value, HostError> {
+        self.map(T::into_value).map_err(E::into)
+    }
+}
+
+/// The Rust functions which can be wrapped as [XFn], with the arity and the
+/// types of the arguments checked. `Args` is the tuple of the argument types.
+pub trait IntoXFn<Args> {
+    fn into_xfn(self, name: String) -> XFn;
+}
+
+/// Convert the argument at `index` (from 0), or describe why it can not.
+fn arg<T: FromValue>(index: usize, val: Value) -> Result<T, HostError> {
+    let got = val.type_name();
+    T::from_value(val).ok_or_else(|| HostError::new(format!("argument {} wants {}, got {}", index + 1, T::TYPE_NAME, got)))
+}
+
+macro_rules! impl_into_xfn {
+    ($len:expr $(, $arg:ident)*) => {
+        impl<F, R $(, $arg)*> IntoXFn<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + 'static,
+            R: IntoXFnResult,
+            $($arg: FromValue,)*
+        {
+            #[allow(non_snake_case, unused_mut, unused_variables, unused_assignments)]
+            fn into_xfn(self, name: String) -> XFn {
+                XFn::try_new(name, move |args: Vec<Value>| {
+                    if args.len() != $len {
+                        return Err(HostError::new(format!("wants {} arguments, got {}", $len, args.len())));
+                    }
+                    let mut args = args.into_iter();
+                    let mut index = 0;
+                    $(
+                        let $arg = arg::<$arg>(index, args.next().unwrap())?;
+                        index += 1;
+                    )*
+                    (self)($($arg),*).into_xfn_result()
+                })
+            }
+        }
+    };
+}
+
+impl_into_xfn!(0);
+impl_into_xfn!(1, A1);
+impl_into_xfn!(2, A1, A2);
+impl_into_xfn!(3, A1, A2, A3);
+impl_into_xfn!(4, A1, A2, A3, A4);
+impl_into_xfn!(5, A1, A2, A3, A4, A5);
+impl_into_xfn!(6, A1, A2, A3, A4, A5, A6);
+
+#[cfg(test)]
+mod tests {
+    use crate::bytecode::{Bytecode, Runner, XFnContext};
+
+    use super::*;
+
+    #[test]
+    fn convert() {
+        assert_eq!(i64::from_value(Value::I64(1)), Some(1));
+        assert_eq!(i64::from_value(Value::Str("1".to_string())), None);
+        assert_eq!(f64::from_value(Value::I64(1)), Some(1.0));
+        assert_eq!(Option::<String>::from_value(Value::Null), Some(None));
+        assert_eq!(Vec::<i64>::from_value(Array::from(vec![Value::I64(1), Value::I64(2)]).into_value()), Some(vec![1, 2]));
+        assert_eq!(Vec::<i64>::from_value(Array::from(vec![Value::Null]).into_value()), None);
+
+        assert_eq!(vec![Some("a"), None].into_value(), Value::Array(Array::from(vec![Value::Str("a".to_string()), Value::Null])));
+        assert_eq!(().into_value(), Value::Null);
+    }
+
+    #[test]
+    fn xfns() {
+        let runner = Runner::new(Bytecode::new());
+        let ctx = XFnContext::new(&runner);
+        let repeat = (|s: String, n: i64| s.repeat(n as usize)).into_xfn("repeat".to_string());
+        assert_eq!(repeat.call(&ctx, vec![Value::Str("ab".to_string()), Value::I64(2)]), Ok(Value::Str("abab".to_string())));
+        assert_eq!(repeat.call(&ctx, vec![Value::Str("ab".to_string())]), Err(HostError::new("wants 2 arguments, got 1")));
+        assert_eq!(repeat.call(&ctx, vec![Value::Str("ab".to_string()), Value::Null]), Err(HostError::new("argument 2 wants I64, got NULL")));
+
+        let checked = (|a: i64, b: i64| a.checked_div(b).ok_or("divided by zero")).into_xfn("div".to_string());
+        assert_eq!(checked.call(&ctx, vec![Value::I64(6), Value::I64(3)]), Ok(Value::I64(2)));
+        assert_eq!(checked.call(&ctx, vec![Value::I64(6), Value::I64(0)]), Err(HostError::new("divided by zero")));
+
+        let answer = (|| 42).into_xfn("answer".to_string());
+        assert_eq!(answer.call(&ctx, vec![]), Ok(Value::I64(42)));
+    }
+}
@@ -0,0 +1,202 @@
+use std::fmt::{Display, Formatter, Result, Write};
+
+use crate::bytecode::{builtin_name, Bytecode};
+
+use super::{time, Value, MAX_NESTING};
+
+/// The [Display] of a [Value] as the jisp source evaluated to it, with the
+/// names of the functions in the [Bytecode], see [Runner::display](crate::bytecode::Runner::display).
+/// The regexes, instants, durations and the floats like `NaN` are written as
+/// the calls making them, like `(time/ms 5)`, and the builtin functions as
+/// their names.
+///
+/// Some values can not be read back: the inner and host functions, written
+/// as `#<fn name>`, the host objects, the `undefined` of the locals not
+/// assigned yet, and the arrays and maps nested deeper than [MAX_NESTING],
+/// written as `...`.
+pub struct ValueDisplay<'a> {
+    val: &'a Value,
+    bytecode: Option<&'a Bytecode>,
+}
+
+impl<'a> ValueDisplay<'a> {
+    pub(crate) fn new(val: &'a Value, bytecode: Option<&'a Bytecode>) -> Self {
+        Self { val, bytecode }
+    }
+
+    fn name(&self, val: &Value) -> Option<&'a str> {
+        let bytecode = self.bytecode?;
+        match val {
+            Value::IFn(index) => bytecode.ifns.get(*index as usize).map(|func| func.name.as_str()),
+            Value::XFn(index) => bytecode.xfns.get(*index as usize).map(|name| name.as_str()),
+            _ => None,
+        }
+    }
+
+    fn write(&self, f: &mut Formatter<'_>, val: &Value, depth: usize) -> Result {
+        match val {
+            // A deep (or cyclic) value.
+            Value::Array(_) | Value::Map(_) if depth >= MAX_NESTING => f.write_str("..."),
+            Value::Null => f.write_str("null"),
+            Value::Undefined => f.write_str("undefined"),
+            Value::I64(val) => write!(f, "{}", val),
+            // Keep the `.0` of floats.
+            Value::F64(val) if val.is_finite() => write!(f, "{:?}", val),
+            Value::F64(val) => write!(f, "(parse-float \"{}\")", val),
+            Value::Bool(val) => write!(f, "{}", val),
+            Value::Str(val) => write_str(f, val),
+            Value::Builtin(index) => f.write_str(builtin_name(*index)),
+            Value::IFn(_) | Value::XFn(_) => match self.name(val) {
+                Some(name) if !name.is_empty() => write!(f, "#<fn {}>", name),
+                _ => f.write_str("#<fn>"),
+            },
+            Value::Array(arr) => {
+                f.write_char('[')?;
+                for (i, val) in arr.values().iter().enumerate() {
+                    if i != 0 {
+                        f.write_char(' ')?;
+                    }
+                    self.write(f, val, depth + 1)?;
+                }
+                f.write_char(']')
+            }
+            Value::Map(map) => {
+                f.write_char('{')?;
+                for (i, (key, val)) in map.entries().iter().enumerate() {
+                    if i != 0 {
+                        f.write_char(' ')?;
+                    }
+                    write_str(f, key)?;
+                    f.write_char(' ')?;
+                    self.write(f, val, depth + 1)?;
+                }
+                f.write_char('}')
+            }
+            Value::Regex(re) => {
+                f.write_str("(re ")?;
+                write_str(f, re.as_str())?;
+                f.write_char(')')
+            }
+            // The instants out of the years of RFC 3339 are not parsed back.
+            Value::Instant(millis) => match time::to_iso8601(*millis) {
+                iso if time::parse_iso8601(&iso) == Ok(*millis) => {
+                    f.write_str("(time/parse ")?;
+                    write_str(f, &iso)?;
+                    f.write_char(')')
+                }
+                _ => write!(f, "(time/instant {})", millis),
+            },
+            Value::Duration(millis) => write!(f, "(time/ms {})", millis),
+            Value::Host(obj) => obj.fmt(f),
+        }
+    }
+}
+
+impl Display for ValueDisplay<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        self.write(f, self.val, 0)
+    }
+}
+
+/// Write the string quoted, with the escapes read by the
+/// [TokenStream](crate::token_stream::TokenStream).
+fn write_str(f: &mut Formatter<'_>, val: &str) -> Result {
+    f.write_char('"')?;
+    for ch in val.chars() {
+        match ch {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            ch => f.write_char(ch)?,
+        }
+    }
+    f.write_char('"')
+}
+
+/// Display the value as jisp reads it, the inner and host functions are
+/// displayed without names, use [Runner::display](crate::bytecode::Runner::display)
+/// for them.
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        ValueDisplay::new(self, None).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{bytecode::lookup_builtin, engine::Engine, value::{Array, Map, Regex}};
+
+    use super::*;
+
+    fn eval(source: &str) -> Value {
+        let engine = Engine::new();
+        engine.runner(engine.compile(source).unwrap()).unwrap().run().unwrap()
+    }
+
+    #[test]
+    fn display() {
+        let val = Value::Array(Array::from(vec![
+            Value::Null,
+            Value::I64(1),
+            Value::F64(2.0),
+            Value::Bool(true),
+            Value::Str("a \"b\"\\\n".to_string()),
+            Value::Map(Map::from(vec![("a".to_string(), Value::Array(Array::new()))])),
+            Value::Builtin(lookup_builtin("len").unwrap()),
+            Value::IFn(1),
+            Value::Instant(0),
+            Value::Duration(5),
+            Value::Regex(Regex::new("\\d+").unwrap()),
+        ]));
+        assert_eq!(val.to_string(), r#"[null 1 2.0 true "a \"b\"\\\n" {"a" []} len #<fn> (time/parse "1970-01-01T00:00:00Z") (time/ms 5) (re "\\d+")]"#);
+        assert_eq!(Value::Undefined.to_string(), "undefined");
+    }
+
+    #[test]
+    fn round_trip() {
+        let vals = vec![
+            Value::Null,
+            Value::Bool(true),
+            Value::Bool(false),
+            Value::I64(0),
+            Value::I64(-3),
+            Value::I64(i64::MIN),
+            Value::I64(i64::MAX),
+            Value::F64(2.0),
+            Value::F64(-1.5),
+            Value::F64(-0.0),
+            Value::F64(1e300),
+            Value::F64(1.5e-7),
+            Value::F64(f64::INFINITY),
+            Value::F64(f64::NEG_INFINITY),
+            Value::Str("a \"b\"\\\n\r\t #<fn> ()".to_string()),
+            Value::Builtin(lookup_builtin("len").unwrap()),
+            Value::Regex(Regex::new("(\\d+)\"\\s").unwrap()),
+            Value::Instant(0),
+            Value::Instant(1_700_000_000_123),
+            Value::Instant(-86_400_000),
+            Value::Instant(i64::MAX),
+            Value::Duration(5),
+            Value::Duration(-90_000),
+            Value::Array(Array::new()),
+            Value::Map(Map::new()),
+        ];
+        let nested = Value::Array(Array::from(vec![
+            Value::Map(Map::from(vec![
+                ("null".to_string(), Value::Null),
+                ("all".to_string(), Value::Array(Array::from(vals.clone()))),
+            ])),
+            Value::Array(Array::from(vec![Value::Array(Array::from(vec![Value::F64(0.5)]))])),
+        ]));
+        for val in vals.into_iter().chain([nested]) {
+            let display = val.to_string();
+            assert_eq!(eval(&display), val, "{}", display);
+        }
+
+        // The NaN is not equal to itself.
+        let display = Value::F64(f64::NAN).to_string();
+        assert!(matches!(eval(&display), Value::F64(val) if val.is_nan()), "{}", display);
+    }
+}
@@ -0,0 +1,53 @@
+//! The opaque values of the host, like the handles of database cursors,
+//! which jisp code passes around and calls the methods of by `(.name obj ...)`.
+
+use std::{any::Any, fmt::{Debug, Formatter}, rc::Rc};
+
+use crate::bytecode::XFnContext;
+
+use super::{HostError, Value};
+
+/// The object of the host in [Value::Host].
+pub trait HostObject: Any {
+    /// The name of the type, like the `I64` of [Value::type_name].
+    fn type_name(&self) -> &'static str;
+
+    /// Write how the object is displayed.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#<{}>", self.type_name())
+    }
+
+    /// Whether the object equals the other one. The same object is always
+    /// equal to itself, and the others are not equal by default.
+    fn equals(&self, other: &dyn HostObject) -> bool {
+        let _ = other;
+        false
+    }
+
+    /// Call the method by name, for `(.name obj args ...)`.
+    fn call_method(&self, ctx: &XFnContext<'_>, name: &str, args: Vec<Value>) -> Result<Value, HostError> {
+        let _ = (ctx, args);
+        Err(HostError::new(format!("{} has no method {}", self.type_name(), name)))
+    }
+}
+
+impl Debug for dyn HostObject {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        HostObject::fmt(self, f)
+    }
+}
+
+impl Value {
+    /// Wrap the object of the host.
+    pub fn host<T: HostObject>(obj: T) -> Self {
+        Value::Host(Rc::new(obj))
+    }
+
+    /// The object of the host if it is a `T`.
+    pub fn as_host<T: HostObject>(&self) -> Option<&T> {
+        match self {
+            Value::Host(obj) => (obj.as_ref() as &dyn Any).downcast_ref(),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,530 @@
+use std::{fmt::{Display, Write}, iter::Peekable, mem::size_of, str::Chars};
+
+use crate::bytecode::map_entry_bytes;
+
+use super::{Array, Map, Value};
+
+/// The maximum nesting of arrays and maps, so a deep (or cyclic) value can
+/// not overflow the native stack while it is parsed or written.
+pub const MAX_JSON_DEPTH: usize = super::MAX_NESTING;
+
+/// The error raised while parsing or serializing JSON.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum JsonError {
+    /// The source is not valid JSON, `line` and `column` start from 1.
+    Syntax { line: usize, column: usize, msg: String },
+
+    /// The value can not be represented in JSON, like functions.
+    Unsupported { type_name: &'static str },
+
+    /// The arrays and maps are nested deeper than [MAX_JSON_DEPTH].
+    TooDeep,
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonError::Syntax { line, column, msg } => {
+                write!(f, "{} at line {} column {}", msg, line, column)
+            }
+            JsonError::Unsupported { type_name } => write!(f, "{} can not be serialized to JSON", type_name),
+            JsonError::TooDeep => write!(f, "nested deeper than {}", MAX_JSON_DEPTH),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+impl Value {
+    /// Parse the JSON document, objects become [Map] and keep their order.
+    ///
+    /// Numbers become [Value::I64] if they are integers in the range of
+    /// `i64`, or [Value::F64] otherwise.
+    pub fn from_json(source: &str) -> Result<Value, JsonError> {
+        Self::from_json_counted(source, &mut |_| true)
+    }
+
+    /// Parse the JSON document like [Value::from_json], and count the bytes
+    /// of each element of arrays and entry of maps by `alloc` before it is
+    /// added, the parse stops once `alloc` returns false.
+    pub(crate) fn from_json_counted(source: &str, alloc: &mut dyn FnMut(usize) -> bool) -> Result<Value, JsonError> {
+        let mut parser = JsonParser::new(source, alloc);
+        parser.skip_whitespace();
+        let val = parser.parse_value(0)?;
+        parser.skip_whitespace();
+        match parser.peek() {
+            None => Ok(val),
+            Some(_) => Err(parser.error("trailing characters")),
+        }
+    }
+
+    /// Serialize the value to compact JSON, [Value::Instant] is written as
+    /// an RFC 3339 string and [Value::Duration] as milliseconds.
+    pub fn to_json(&self) -> Result<String, JsonError> {
+        let mut out = String::new();
+        write_json(&mut out, self, None, 0, false)?;
+        Ok(out)
+    }
+
+    /// Serialize the value to JSON indented by two spaces.
+    pub fn to_json_pretty(&self) -> Result<String, JsonError> {
+        let mut out = String::new();
+        write_json(&mut out, self, Some("  "), 0, false)?;
+        Ok(out)
+    }
+
+    /// Display the value as compact JSON, which never fails: the values
+    /// JSON can not represent are written as the strings of their
+    /// [Display], and the ones nested deeper than [MAX_JSON_DEPTH] as
+    /// `"..."`.
+    pub fn json(&self) -> JsonDisplay<'_> {
+        JsonDisplay(self)
+    }
+}
+
+/// The JSON [Display] of a [Value], see [Value::json].
+pub struct JsonDisplay<'a>(&'a Value);
+
+impl Display for JsonDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut out = String::new();
+        write_json(&mut out, self.0, None, 0, true).map_err(|_| std::fmt::Error)?;
+        f.write_str(&out)
+    }
+}
+
+struct JsonParser<'s> {
+    source: Peekable<Chars<'s>>,
+    line: usize,
+    column: usize,
+    alloc: &'s mut dyn FnMut(usize) -> bool, // Count the bytes of the containers, see [Value::from_json_counted].
+}
+
+impl<'s> JsonParser<'s> {
+    fn new(source: &'s str, alloc: &'s mut dyn FnMut(usize) -> bool) -> Self {
+        Self { source: source.chars().peekable(), line: 1, column: 1, alloc }
+    }
+
+    fn alloc(&mut self, bytes: usize) -> Result<(), JsonError> {
+        match (self.alloc)(bytes) {
+            true => Ok(()),
+            false => Err(self.error("too large")),
+        }
+    }
+
+    fn error<T: Into<String>>(&self, msg: T) -> JsonError {
+        JsonError::Syntax { line: self.line, column: self.column, msg: msg.into() }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.source.peek().copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let ch = self.source.next()?;
+        if ch == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(ch)
+    }
+
+    fn expect(&mut self, want: char) -> Result<(), JsonError> {
+        match self.peek() {
+            Some(ch) if ch == want => {
+                self.next();
+                Ok(())
+            }
+            Some(ch) => Err(self.error(format!("expected '{}', got '{}'", want, ch))),
+            None => Err(self.error(format!("expected '{}', got end of input", want))),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.peek() {
+            self.next();
+        }
+    }
+
+    fn parse_value(&mut self, depth: usize) -> Result<Value, JsonError> {
+        match self.peek() {
+            Some('{') => self.parse_object(depth + 1),
+            Some('[') => self.parse_array(depth + 1),
+            Some('"') => Ok(Value::Str(self.parse_str()?)),
+            Some('-' | '0'..='9') => self.parse_number(),
+            Some('t') => self.parse_keyword("true", Value::Bool(true)),
+            Some('f') => self.parse_keyword("false", Value::Bool(false)),
+            Some('n') => self.parse_keyword("null", Value::Null),
+            Some(ch) => Err(self.error(format!("expected a value, got '{}'", ch))),
+            None => Err(self.error("expected a value, got end of input")),
+        }
+    }
+
+    fn parse_keyword(&mut self, keyword: &str, val: Value) -> Result<Value, JsonError> {
+        let err = self.error(format!("expected '{}'", keyword));
+        for want in keyword.chars() {
+            if self.next() != Some(want) {
+                return Err(err);
+            }
+        }
+        Ok(val)
+    }
+
+    fn parse_number(&mut self) -> Result<Value, JsonError> {
+        let mut digits = String::new();
+        let mut is_float = false;
+        if self.peek() == Some('-') {
+            digits.push('-');
+            self.next();
+        }
+        match self.peek() {
+            Some('0') => {
+                digits.push('0');
+                self.next();
+                if let Some('0'..='9') = self.peek() {
+                    return Err(self.error("leading zero in number"));
+                }
+            }
+            Some('1'..='9') => self.push_digits(&mut digits),
+            _ => return Err(self.error("expected a digit")),
+        }
+        if self.peek() == Some('.') {
+            is_float = true;
+            digits.push('.');
+            self.next();
+            if !matches!(self.peek(), Some('0'..='9')) {
+                return Err(self.error("expected a digit"));
+            }
+            self.push_digits(&mut digits);
+        }
+        if let Some(ch @ ('e' | 'E')) = self.peek() {
+            is_float = true;
+            digits.push(ch);
+            self.next();
+            if let Some(ch @ ('+' | '-')) = self.peek() {
+                digits.push(ch);
+                self.next();
+            }
+            if !matches!(self.peek(), Some('0'..='9')) {
+                return Err(self.error("expected a digit"));
+            }
+            self.push_digits(&mut digits);
+        }
+        // Integers out of the range of i64 fall back to F64 as well.
+        match digits.parse::<i64>() {
+            Ok(val) if !is_float => Ok(Value::I64(val)),
+            _ => Ok(Value::F64(digits.parse::<f64>().unwrap())),
+        }
+    }
+
+    fn push_digits(&mut self, digits: &mut String) {
+        while let Some(ch @ '0'..='9') = self.peek() {
+            digits.push(ch);
+            self.next();
+        }
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, JsonError> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self.peek().and_then(|ch| ch.to_digit(16));
+            match digit {
+                Some(digit) => {
+                    self.next();
+                    code = code * 16 + digit;
+                }
+                None => return Err(self.error("expected a hex digit")),
+            }
+        }
+        Ok(code)
+    }
+
+    fn parse_str(&mut self) -> Result<String, JsonError> {
+        self.expect('"')?;
+        let mut str = String::new();
+        loop {
+            let ch = match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some(ch) if (ch as u32) < 0x20 => return Err(self.error("control character in string")),
+                Some(ch) => ch,
+            };
+            self.next();
+            match ch {
+                '"' => return Ok(str),
+                '\\' => {
+                    let escaped = match self.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.parse_unicode_escape()?,
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    str.push(escaped);
+                }
+                ch => str.push(ch),
+            }
+        }
+    }
+
+    /// Parse the rest of `\uXXXX`, including the low half of a surrogate pair.
+    fn parse_unicode_escape(&mut self) -> Result<char, JsonError> {
+        let high = self.parse_hex4()?;
+        let code = if (0xD800..0xDC00).contains(&high) {
+            if self.next() != Some('\\') || self.next() != Some('u') {
+                return Err(self.error("expected a low surrogate"));
+            }
+            let low = self.parse_hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error("invalid low surrogate"));
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    fn parse_array(&mut self, depth: usize) -> Result<Value, JsonError> {
+        if depth > MAX_JSON_DEPTH {
+            return Err(JsonError::TooDeep);
+        }
+        self.expect('[')?;
+        let arr = Array::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.next();
+            return Ok(Value::Array(arr));
+        }
+        loop {
+            self.skip_whitespace();
+            self.alloc(size_of::<Value>())?;
+            arr.push(self.parse_value(depth)?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.next(),
+                Some(']') => {
+                    self.next();
+                    return Ok(Value::Array(arr));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            };
+        }
+    }
+
+    fn parse_object(&mut self, depth: usize) -> Result<Value, JsonError> {
+        if depth > MAX_JSON_DEPTH {
+            return Err(JsonError::TooDeep);
+        }
+        self.expect('{')?;
+        let map = Map::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.next();
+            return Ok(Value::Map(map));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error("expected a string key"));
+            }
+            let key = self.parse_str()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+            self.alloc(map_entry_bytes(&key))?;
+            map.insert(key, self.parse_value(depth)?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.next(),
+                Some('}') => {
+                    self.next();
+                    return Ok(Value::Map(map));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            };
+        }
+    }
+}
+
+fn write_str(out: &mut String, str: &str) {
+    out.push('"');
+    for ch in str.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            ch if (ch as u32) < 0x20 => write!(out, "\\u{:04x}", ch as u32).unwrap(),
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+}
+
+/// Write a newline and the indent of `depth` if it is pretty printing.
+fn write_indent(out: &mut String, indent: Option<&str>, depth: usize) {
+    if let Some(indent) = indent {
+        out.push('\n');
+        for _ in 0..depth {
+            out.push_str(indent);
+        }
+    }
+}
+
+/// Write the value as JSON, or if it is `lossy`, write the values JSON can
+/// not represent as strings instead of failing.
+fn write_json(out: &mut String, val: &Value, indent: Option<&str>, depth: usize, lossy: bool) -> Result<(), JsonError> {
+    match val {
+        Value::Null => out.push_str("null"),
+        Value::I64(val) => write!(out, "{}", val).unwrap(),
+        // Keep the `.0` of floats, and JSON has no NaN or infinity.
+        Value::F64(val) if val.is_finite() => write!(out, "{:?}", val).unwrap(),
+        Value::F64(_) => out.push_str("null"),
+        Value::Instant(millis) => write_str(out, &super::time::to_iso8601(*millis)),
+        Value::Duration(millis) => write!(out, "{}", millis).unwrap(),
+        Value::Bool(val) => write!(out, "{}", val).unwrap(),
+        Value::Str(val) => write_str(out, val),
+        Value::Array(arr) => {
+            if depth >= MAX_JSON_DEPTH {
+                return too_deep(out, lossy);
+            }
+            let values = arr.values();
+            if values.is_empty() {
+                out.push_str("[]");
+                return Ok(());
+            }
+            out.push('[');
+            for (i, val) in values.iter().enumerate() {
+                if i != 0 {
+                    out.push(',');
+                }
+                write_indent(out, indent, depth + 1);
+                write_json(out, val, indent, depth + 1, lossy)?;
+            }
+            write_indent(out, indent, depth);
+            out.push(']');
+        }
+        Value::Map(map) => {
+            if depth >= MAX_JSON_DEPTH {
+                return too_deep(out, lossy);
+            }
+            let entries = map.entries();
+            if entries.is_empty() {
+                out.push_str("{}");
+                return Ok(());
+            }
+            out.push('{');
+            for (i, (key, val)) in entries.iter().enumerate() {
+                if i != 0 {
+                    out.push(',');
+                }
+                write_indent(out, indent, depth + 1);
+                write_str(out, key);
+                out.push_str(if indent.is_some() { ": " } else { ":" });
+                write_json(out, val, indent, depth + 1, lossy)?;
+            }
+            write_indent(out, indent, depth);
+            out.push('}');
+        }
+        val if lossy => write_str(out, &val.to_string()),
+        val => return Err(JsonError::Unsupported { type_name: val.type_name() }),
+    }
+    Ok(())
+}
+
+fn too_deep(out: &mut String, lossy: bool) -> Result<(), JsonError> {
+    if !lossy {
+        return Err(JsonError::TooDeep);
+    }
+    write_str(out, "...");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let val = Value::from_json(r#" {"a": [1, -2, true, false, null], "b": {"c": "d\né😀"}, "a": 0} "#);
+        assert_eq!(val, Ok(Value::Map(Map::from(vec![
+            ("a".to_string(), Value::I64(0)),
+            ("b".to_string(), Value::Map(Map::from(vec![
+                ("c".to_string(), Value::Str("d\n\u{e9}\u{1f600}".to_string())),
+            ]))),
+        ]))));
+
+        assert_eq!(Value::from_json("[]"), Ok(Value::Array(Array::new())));
+        assert_eq!(Value::from_json("9223372036854775807"), Ok(Value::I64(i64::MAX)));
+        assert_eq!(Value::from_json("9223372036854775808"), Ok(Value::F64(9223372036854775808.0)));
+        assert_eq!(Value::from_json("[-1.5, 2e3, 1E-2]"), Ok(Value::Array(Array::from(vec![
+            Value::F64(-1.5), Value::F64(2000.0), Value::F64(0.01),
+        ]))));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |line, column, msg: &str| Err(JsonError::Syntax { line, column, msg: msg.to_string() });
+
+        assert_eq!(Value::from_json("[1,\n 2,\n 3"), error(3, 3, "expected ',' or ']'"));
+        assert_eq!(Value::from_json("{\"a\" 1}"), error(1, 6, "expected ':', got '1'"));
+        assert_eq!(Value::from_json("{1: 2}"), error(1, 2, "expected a string key"));
+        assert_eq!(Value::from_json("[tru]"), error(1, 2, "expected 'true'"));
+        assert_eq!(Value::from_json("01"), error(1, 2, "leading zero in number"));
+        assert_eq!(Value::from_json("1."), error(1, 3, "expected a digit"));
+        assert_eq!(Value::from_json("-e1"), error(1, 2, "expected a digit"));
+        assert_eq!(Value::from_json("\"abc"), error(1, 5, "unterminated string"));
+        assert_eq!(Value::from_json("1 2"), error(1, 3, "trailing characters"));
+        assert_eq!(Value::from_json(""), error(1, 1, "expected a value, got end of input"));
+        assert_eq!(Value::from_json(&"[".repeat(MAX_JSON_DEPTH + 1)), Err(JsonError::TooDeep));
+    }
+
+    #[test]
+    fn stringify() {
+        let val = Value::Map(Map::from(vec![
+            ("a".to_string(), Value::Array(Array::from(vec![Value::I64(1), Value::Null]))),
+            ("b\"".to_string(), Value::Str("x\ty".to_string())),
+            ("c".to_string(), Value::Map(Map::new())),
+        ]));
+        assert_eq!(val.to_json(), Ok(r#"{"a":[1,null],"b\"":"x\ty","c":{}}"#.to_string()));
+        assert_eq!(val.to_json_pretty(), Ok([
+            "{",
+            "  \"a\": [",
+            "    1,",
+            "    null",
+            "  ],",
+            "  \"b\\\"\": \"x\\ty\",",
+            "  \"c\": {}",
+            "}",
+        ].join("\n")));
+        assert_eq!(Value::from_json(&val.to_json().unwrap()), Ok(val));
+
+        assert_eq!(Value::Array(Array::from(vec![Value::F64(1.0), Value::F64(f64::NAN)])).to_json(), Ok("[1.0,null]".to_string()));
+        assert_eq!(Value::IFn(0).to_json(), Err(JsonError::Unsupported { type_name: "FN" }));
+
+        let arr = Array::new();
+        arr.push(Value::Array(arr.clone()));
+        assert_eq!(Value::Array(arr.clone()).to_json(), Err(JsonError::TooDeep));
+        let json = Value::Array(arr).json().to_string();
+        assert_eq!(json, "[".repeat(MAX_JSON_DEPTH) + "\"...\"" + &"]".repeat(MAX_JSON_DEPTH));
+    }
+
+    #[test]
+    fn display() {
+        let val = Value::Map(Map::from(vec![
+            ("a".to_string(), Value::Array(Array::from(vec![Value::IFn(0), Value::Undefined]))),
+            ("b".to_string(), Value::Duration(5)),
+        ]));
+        assert_eq!(val.json().to_string(), r##"{"a":["#<fn>","undefined"],"b":5}"##);
+    }
+}
@@ -0,0 +1,160 @@
+use std::{cell::{Ref, RefCell}, collections::{hash_map::DefaultHasher, HashMap}, fmt::Debug, hash::{Hash, Hasher}, mem::take, rc::Rc};
+
+use super::{value::drop_values, Value, MAX_NESTING};
+
+/// The map from strings to values, keeps the insertion order and is shared by
+/// all its clones.
+#[derive(Clone, Default)]
+pub struct Map {
+    inner: Rc<RefCell<MapInner>>,
+}
+
+#[derive(Default)]
+struct MapInner {
+    entries: Vec<(String, Value)>,
+    index: HashMap<String, usize>, // The index of the key in entries.
+}
+
+impl Map {
+    /// Build an empty [Map].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.borrow().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.borrow().entries.is_empty()
+    }
+
+    /// Get the value by key, or `None` if there is not.
+    pub fn get(&self, key: &str) -> Option<Value> {
+        let inner = self.inner.borrow();
+        inner.index.get(key).map(|i| inner.entries[*i].1.clone())
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.inner.borrow().index.contains_key(key)
+    }
+
+    /// Insert the value by key. The new key is put at the end, and the old
+    /// key keeps its place.
+    pub fn insert(&self, key: String, val: Value) {
+        let mut inner = self.inner.borrow_mut();
+        match inner.index.get(&key) {
+            Some(i) => {
+                let i = *i;
+                inner.entries[i].1 = val;
+            }
+            None => {
+                let i = inner.entries.len();
+                inner.index.insert(key.clone(), i);
+                inner.entries.push((key, val));
+            }
+        }
+    }
+
+    /// Remove the value by key, return it if there is.
+    pub fn remove(&self, key: &str) -> Option<Value> {
+        let mut inner = self.inner.borrow_mut();
+        let i = inner.index.remove(key)?;
+        let (_, val) = inner.entries.remove(i);
+        for (_, index) in inner.index.iter_mut() {
+            if *index > i {
+                *index -= 1;
+            }
+        }
+        Some(val)
+    }
+
+    /// Borrow the entries to read, in the insertion order.
+    pub fn entries(&self) -> Ref<'_, Vec<(String, Value)>> {
+        Ref::map(self.inner.borrow(), |inner| &inner.entries)
+    }
+
+    /// Return true if the two maps are the same one.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    /// The identity of the map, shared by its clones.
+    pub(super) fn id(&self) -> usize {
+        Rc::as_ptr(&self.inner) as *const () as usize
+    }
+
+    /// Take the values out if this is the last clone of the map.
+    pub(super) fn take_values(&mut self) -> Vec<Value> {
+        match Rc::get_mut(&mut self.inner) {
+            Some(inner) => {
+                let inner = inner.get_mut();
+                inner.index.clear();
+                take(&mut inner.entries).into_iter().map(|(_, val)| val).collect()
+            }
+            None => vec![],
+        }
+    }
+
+    /// Two maps are equal if they have the same entries, in any order.
+    pub(super) fn eq_within(&self, other: &Self, depth: usize) -> bool {
+        if self.ptr_eq(other) {
+            return true;
+        }
+        depth > 0 && self.len() == other.len() && self.entries().iter().all(|(key, val)| {
+            other.get(key).is_some_and(|other| val.eq_within(&other, depth - 1))
+        })
+    }
+
+    pub(super) fn hash_within<H: Hasher>(&self, state: &mut H, depth: usize) {
+        self.len().hash(state);
+        if depth == 0 {
+            return;
+        }
+        // Sum the hash of entries, so the order does not matter.
+        let mut sum = 0u64;
+        for (key, val) in self.entries().iter() {
+            let mut hasher = DefaultHasher::new();
+            key.hash(&mut hasher);
+            val.hash_within(&mut hasher, depth - 1);
+            sum = sum.wrapping_add(hasher.finish());
+        }
+        sum.hash(state);
+    }
+}
+
+impl From<Vec<(String, Value)>> for Map {
+    fn from(entries: Vec<(String, Value)>) -> Self {
+        let map = Map::new();
+        for (key, val) in entries {
+            map.insert(key, val);
+        }
+        map
+    }
+}
+
+impl PartialEq for Map {
+    fn eq(&self, other: &Self) -> bool {
+        self.eq_within(other, MAX_NESTING)
+    }
+}
+
+impl Eq for Map {}
+
+impl Hash for Map {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.hash_within(state, MAX_NESTING);
+    }
+}
+
+impl Drop for Map {
+    fn drop(&mut self) {
+        drop_values(self.take_values());
+    }
+}
+
+impl Debug for Map {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.entries().iter().map(|(k, v)| (k, v))).finish()
+    }
+}
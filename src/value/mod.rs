@@ -1,4 +1,21 @@
 mod value;
+mod array;
+mod map;
+mod json;
+mod regex;
+pub mod time;
+mod convert;
+mod host;
+mod display;
 
 pub type Value = value::Value;
-pub use value::XFn as XFn;
\ No newline at end of file
+pub type Array = array::Array;
+pub type Map = map::Map;
+pub type JsonError = json::JsonError;
+pub type JsonDisplay<'a> = json::JsonDisplay<'a>;
+pub type ValueDisplay<'a> = display::ValueDisplay<'a>;
+pub type Regex = regex::Regex;
+pub use value::{HostError, XFn, MAX_NESTING};
+pub use host::HostObject;
+pub use convert::{FromValue, IntoValue, IntoXFn, IntoXFnResult};
+pub use json::MAX_JSON_DEPTH;
@@ -0,0 +1,46 @@
+use std::{fmt::Debug, hash::Hash, rc::Rc};
+
+/// The compiled regular expression, shared by all its clones.
+#[derive(Clone)]
+pub struct Regex {
+    inner: Rc<regex::Regex>,
+}
+
+impl Regex {
+    /// Compile the pattern, return the message of the error if it is invalid.
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        match regex::Regex::new(pattern) {
+            Ok(inner) => Ok(Self { inner: Rc::new(inner) }),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.inner.as_str()
+    }
+
+    /// Borrow the compiled regular expression to match.
+    pub fn inner(&self) -> &regex::Regex {
+        &self.inner
+    }
+}
+
+impl PartialEq for Regex {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for Regex {}
+
+impl Hash for Regex {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl Debug for Regex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Regex({:?})", self.as_str())
+    }
+}
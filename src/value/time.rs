@@ -0,0 +1,254 @@
+//! The calendar of [Value::Instant](super::Value::Instant), which is the
+//! milliseconds since `1970-01-01T00:00:00Z`. Only UTC is supported, the
+//! offsets are applied when parsing.
+
+use std::fmt::Write;
+
+pub const MILLIS_PER_SECOND: i64 = 1000;
+pub const MILLIS_PER_MINUTE: i64 = 60 * MILLIS_PER_SECOND;
+pub const MILLIS_PER_HOUR: i64 = 60 * MILLIS_PER_MINUTE;
+pub const MILLIS_PER_DAY: i64 = 24 * MILLIS_PER_HOUR;
+
+/// The days since `1970-01-01` of the date in the proleptic Gregorian
+/// calendar.
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = month as i64;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// The date `(year, month, day)` of the days since `1970-01-01`.
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// The reader of the fixed-width fields of ISO 8601.
+struct Fields<'s> {
+    source: &'s str,
+    pos: usize,
+}
+
+impl<'s> Fields<'s> {
+    fn peek(&self) -> Option<u8> {
+        self.source.as_bytes().get(self.pos).copied()
+    }
+
+    fn eat(&mut self, want: u8) -> bool {
+        if self.peek() == Some(want) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, want: u8) -> Result<(), String> {
+        match self.eat(want) {
+            true => Ok(()),
+            false => Err(format!("expected '{}' at {}", want as char, self.pos)),
+        }
+    }
+
+    /// Read a number of exactly `width` digits in `min..=max`.
+    fn number(&mut self, width: usize, min: u32, max: u32) -> Result<u32, String> {
+        let digits = self.source.get(self.pos..self.pos + width)
+            .filter(|digits| digits.bytes().all(|b| b.is_ascii_digit()))
+            .ok_or_else(|| format!("expected {} digits at {}", width, self.pos))?;
+        let val: u32 = digits.parse().unwrap();
+        if val < min || val > max {
+            return Err(format!("{} out of range {}..={} at {}", val, min, max, self.pos));
+        }
+        self.pos += width;
+        Ok(val)
+    }
+}
+
+/// Parse the ISO 8601 date `2024-01-02`, or the date and time like
+/// `2024-01-02T03:04:05.678+08:00`. The seconds, the fraction and the offset
+/// are optional, and the time without offset is in UTC.
+pub fn parse_iso8601(source: &str) -> Result<i64, String> {
+    let mut fields = Fields { source, pos: 0 };
+    let year = fields.number(4, 0, 9999)? as i64;
+    fields.expect(b'-')?;
+    let month = fields.number(2, 1, 12)?;
+    fields.expect(b'-')?;
+    let day = fields.number(2, 1, days_in_month(year, month))?;
+    let mut millis = days_from_civil(year, month, day) * MILLIS_PER_DAY;
+
+    if fields.eat(b'T') || fields.eat(b't') || fields.eat(b' ') {
+        millis += fields.number(2, 0, 23)? as i64 * MILLIS_PER_HOUR;
+        fields.expect(b':')?;
+        millis += fields.number(2, 0, 59)? as i64 * MILLIS_PER_MINUTE;
+        if fields.eat(b':') {
+            millis += fields.number(2, 0, 59)? as i64 * MILLIS_PER_SECOND;
+            if fields.eat(b'.') {
+                // Keep the milliseconds, and drop the finer digits.
+                let start = fields.pos;
+                let mut scale = 100;
+                while let Some(digit @ b'0'..=b'9') = fields.peek() {
+                    millis += (digit - b'0') as i64 * scale;
+                    scale /= 10;
+                    fields.pos += 1;
+                }
+                if fields.pos == start {
+                    return Err(format!("expected digits at {}", start));
+                }
+            }
+        }
+        if !(fields.eat(b'Z') || fields.eat(b'z')) {
+            let sign = match fields.peek() {
+                Some(b'+') => 1,
+                Some(b'-') => -1,
+                _ => 0,
+            };
+            if sign != 0 {
+                fields.pos += 1;
+                let hours = fields.number(2, 0, 23)? as i64;
+                fields.eat(b':');
+                let minutes = fields.number(2, 0, 59)? as i64;
+                millis -= sign * (hours * MILLIS_PER_HOUR + minutes * MILLIS_PER_MINUTE);
+            }
+        }
+    }
+    if fields.pos != source.len() {
+        return Err(format!("unexpected characters at {}", fields.pos));
+    }
+    Ok(millis)
+}
+
+/// Format the instant by `fmt`, which supports `%Y` (year), `%m` (month),
+/// `%d` (day), `%H` (hour), `%M` (minute), `%S` (second), `%f` (millisecond),
+/// `%j` (day of year), `%s` (seconds since the epoch) and `%%`.
+pub fn format_instant(millis: i64, fmt: &str) -> Result<String, String> {
+    let days = millis.div_euclid(MILLIS_PER_DAY);
+    let in_day = millis.rem_euclid(MILLIS_PER_DAY);
+    let (year, month, day) = civil_from_days(days);
+    let mut out = String::new();
+    let mut chars = fmt.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '%' {
+            out.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => write!(out, "{:04}", year),
+            Some('m') => write!(out, "{:02}", month),
+            Some('d') => write!(out, "{:02}", day),
+            Some('H') => write!(out, "{:02}", in_day / MILLIS_PER_HOUR),
+            Some('M') => write!(out, "{:02}", in_day % MILLIS_PER_HOUR / MILLIS_PER_MINUTE),
+            Some('S') => write!(out, "{:02}", in_day % MILLIS_PER_MINUTE / MILLIS_PER_SECOND),
+            Some('f') => write!(out, "{:03}", in_day % MILLIS_PER_SECOND),
+            Some('j') => write!(out, "{:03}", days - days_from_civil(year, 1, 1) + 1),
+            Some('s') => write!(out, "{}", millis.div_euclid(MILLIS_PER_SECOND)),
+            Some('%') => write!(out, "%"),
+            Some(ch) => return Err(format!("unknown directive %{}", ch)),
+            None => return Err("incomplete directive %".to_string()),
+        }.unwrap();
+    }
+    Ok(out)
+}
+
+/// Format the instant in RFC 3339, the milliseconds are omitted if zero.
+pub fn to_iso8601(millis: i64) -> String {
+    let fmt = if millis.rem_euclid(MILLIS_PER_SECOND) == 0 { "%Y-%m-%dT%H:%M:%SZ" } else { "%Y-%m-%dT%H:%M:%S.%fZ" };
+    format_instant(millis, fmt).unwrap()
+}
+
+/// Truncate the instant to the start of the `unit`, which is one of `year`,
+/// `month`, `day`, `hour`, `minute` and `second`.
+pub fn truncate(millis: i64, unit: &str) -> Result<i64, String> {
+    // The start of the unit may be before the earliest instant.
+    let out_of_range = || format!("the {} of the instant is out of range", unit);
+    let floor = |step: i64| millis.div_euclid(step).checked_mul(step).ok_or_else(out_of_range);
+    match unit {
+        "second" => floor(MILLIS_PER_SECOND),
+        "minute" => floor(MILLIS_PER_MINUTE),
+        "hour" => floor(MILLIS_PER_HOUR),
+        "day" => floor(MILLIS_PER_DAY),
+        "month" | "year" => {
+            let (year, month, _) = civil_from_days(millis.div_euclid(MILLIS_PER_DAY));
+            let month = if unit == "year" { 1 } else { month };
+            days_from_civil(year, month, 1).checked_mul(MILLIS_PER_DAY).ok_or_else(out_of_range)
+        }
+        unit => Err(format!("unknown unit {:?}", unit)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+        for days in [-800000, -1, 0, 59, 11016, 11017, 19723, 2932896] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+        assert_eq!(civil_from_days(19724), (2024, 1, 2));
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(parse_iso8601("1970-01-01"), Ok(0));
+        assert_eq!(parse_iso8601("2024-01-02T03:04:05Z"), Ok(1704164645000));
+        assert_eq!(parse_iso8601("2024-01-02 03:04"), Ok(1704164640000));
+        assert_eq!(parse_iso8601("2024-01-02T03:04:05.6789Z"), Ok(1704164645678));
+        assert_eq!(parse_iso8601("2024-01-02T11:04:05+08:00"), Ok(1704164645000));
+        assert_eq!(parse_iso8601("2024-01-01T22:04:05-0500"), Ok(1704164645000));
+        assert_eq!(parse_iso8601("2024-02-29"), Ok(1709164800000));
+
+        assert_eq!(parse_iso8601("2023-02-29"), Err("29 out of range 1..=28 at 8".to_string()));
+        assert_eq!(parse_iso8601("2024-1-02"), Err("expected 2 digits at 5".to_string()));
+        assert_eq!(parse_iso8601("2024-01-02T03:04:05.Z"), Err("expected digits at 20".to_string()));
+        assert_eq!(parse_iso8601("2024-01-02x"), Err("unexpected characters at 10".to_string()));
+    }
+
+    #[test]
+    fn format() {
+        let millis = 1704164645678;
+        assert_eq!(to_iso8601(millis), "2024-01-02T03:04:05.678Z");
+        assert_eq!(to_iso8601(0), "1970-01-01T00:00:00Z");
+        assert_eq!(to_iso8601(-1), "1969-12-31T23:59:59.999Z");
+        assert_eq!(format_instant(millis, "%d/%m/%Y %j %s %%"), Ok("02/01/2024 002 1704164645 %".to_string()));
+        assert_eq!(format_instant(millis, "%x"), Err("unknown directive %x".to_string()));
+    }
+
+    #[test]
+    fn truncation() {
+        let millis = parse_iso8601("2024-03-15T10:20:30.400Z").unwrap();
+        let truncated = |unit| truncate(millis, unit).map(to_iso8601);
+        assert_eq!(truncated("second"), Ok("2024-03-15T10:20:30Z".to_string()));
+        assert_eq!(truncated("hour"), Ok("2024-03-15T10:00:00Z".to_string()));
+        assert_eq!(truncated("day"), Ok("2024-03-15T00:00:00Z".to_string()));
+        assert_eq!(truncated("month"), Ok("2024-03-01T00:00:00Z".to_string()));
+        assert_eq!(truncated("year"), Ok("2024-01-01T00:00:00Z".to_string()));
+        assert_eq!(truncated("week"), Err("unknown unit \"week\"".to_string()));
+        assert_eq!(truncate(i64::MIN, "second"), Err("the second of the instant is out of range".to_string()));
+        assert_eq!(truncate(i64::MIN, "year"), Err("the year of the instant is out of range".to_string()));
+    }
+}
@@ -1,24 +1,234 @@
-use std::fmt::Debug;
+use std::{collections::HashMap, fmt::{Debug, Display}, hash::{Hash, Hasher}, mem::discriminant, rc::Rc};
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+use crate::bytecode::{RuntimeError, XFnContext};
+
+use super::{Array, HostObject, Map, Regex};
+
+/// The maximum nesting of arrays and maps that is compared and hashed in
+/// full, or that a value can have to be put into a container, so a deep (or
+/// cyclic) value can not overflow the native stack.
+pub const MAX_NESTING: usize = 128;
+
+#[derive(Debug, Clone)]
 pub enum Value {
     Null,
     Undefined,
     I64(i64),
+    F64(f64),
     Bool(bool),
     Str(String),
     IFn(u32),
     XFn(u32),
+    Builtin(u32),
+    Array(Array),
+    Map(Map),
+    Regex(Regex),
+
+    /// The milliseconds since `1970-01-01T00:00:00Z`, see [time](super::time).
+    Instant(i64),
+
+    /// The milliseconds between two [Value::Instant].
+    Duration(i64),
+
+    /// The opaque object of the host, see [HostObject].
+    Host(Rc<dyn HostObject>),
+}
+
+impl Value {
+    /// The name of the value's type.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "NULL",
+            Value::Undefined => "UNDEFINED",
+            Value::I64(_) => "I64",
+            Value::F64(_) => "F64",
+            Value::Bool(_) => "BOOL",
+            Value::Str(_) => "STR",
+            Value::IFn(_) | Value::XFn(_) | Value::Builtin(_) => "FN",
+            Value::Array(_) => "ARRAY",
+            Value::Map(_) => "MAP",
+            Value::Regex(_) => "REGEX",
+            Value::Instant(_) => "INSTANT",
+            Value::Duration(_) => "DURATION",
+            Value::Host(obj) => obj.type_name(),
+        }
+    }
+}
+
+/// The bits of the float to compare and hash, so that `0.0` equals `-0.0`
+/// and `NaN` equals itself, to keep [Value] usable as a key.
+fn f64_key(val: f64) -> u64 {
+    if val == 0.0 {
+        0.0f64.to_bits()
+    } else if val.is_nan() {
+        f64::NAN.to_bits()
+    } else {
+        val.to_bits()
+    }
 }
 
+impl Value {
+    /// Compare the values, the arrays and maps nested deeper than `depth`
+    /// are only equal if they are the same one.
+    pub(super) fn eq_within(&self, other: &Self, depth: usize) -> bool {
+        match (self, other) {
+            (Value::Null, Value::Null) | (Value::Undefined, Value::Undefined) => true,
+            (Value::I64(a), Value::I64(b)) => a == b,
+            (Value::F64(a), Value::F64(b)) => f64_key(*a) == f64_key(*b),
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Str(a), Value::Str(b)) => a == b,
+            (Value::IFn(a), Value::IFn(b)) => a == b,
+            (Value::XFn(a), Value::XFn(b)) => a == b,
+            (Value::Builtin(a), Value::Builtin(b)) => a == b,
+            (Value::Array(a), Value::Array(b)) => a.eq_within(b, depth),
+            (Value::Map(a), Value::Map(b)) => a.eq_within(b, depth),
+            (Value::Regex(a), Value::Regex(b)) => a == b,
+            (Value::Instant(a), Value::Instant(b)) => a == b,
+            (Value::Duration(a), Value::Duration(b)) => a == b,
+            (Value::Host(a), Value::Host(b)) => Rc::ptr_eq(a, b) || a.equals(b.as_ref()),
+            _ => false,
+        }
+    }
+
+    /// Hash the value, only the lengths of the arrays and maps nested deeper
+    /// than `depth` are hashed, like they are compared.
+    pub(super) fn hash_within<H: Hasher>(&self, state: &mut H, depth: usize) {
+        discriminant(self).hash(state);
+        match self {
+            Value::Null | Value::Undefined => (),
+            Value::I64(val) | Value::Instant(val) | Value::Duration(val) => val.hash(state),
+            Value::F64(val) => f64_key(*val).hash(state),
+            Value::Bool(val) => val.hash(state),
+            Value::Str(val) => val.hash(state),
+            Value::IFn(index) | Value::XFn(index) | Value::Builtin(index) => index.hash(state),
+            Value::Array(arr) => arr.hash_within(state, depth),
+            Value::Map(map) => map.hash_within(state, depth),
+            Value::Regex(re) => re.hash(state),
+            // The equal objects have the same type at least.
+            Value::Host(obj) => obj.type_name().hash(state),
+        }
+    }
+
+    /// Whether the value is or holds the same array or map as the `coll`,
+    /// which can not be put into it then. `None` if the value is nested as
+    /// deep as [MAX_NESTING].
+    pub(crate) fn holds(&self, coll: &Value) -> Option<bool> {
+        let id = |val: &Value| match val {
+            Value::Array(arr) => Some(arr.id()),
+            Value::Map(map) => Some(map.id()),
+            _ => None,
+        };
+        let Some(coll) = id(coll) else { return Some(false) };
+        // The deepest level each container is seen at, a shared one is only
+        // walked again if it is seen deeper.
+        let mut seen = HashMap::new();
+        let mut stack = vec![(self.clone(), 1)];
+        while let Some((val, depth)) = stack.pop() {
+            let Some(id) = id(&val) else { continue };
+            if id == coll {
+                return Some(true);
+            }
+            if seen.get(&id).is_some_and(|seen| *seen >= depth) {
+                continue;
+            }
+            seen.insert(id, depth);
+            let children: Vec<Value> = match &val {
+                Value::Array(arr) => arr.values().iter().filter(|val| is_coll(val)).cloned().collect(),
+                Value::Map(map) => map.entries().iter().map(|(_, val)| val).filter(|val| is_coll(val)).cloned().collect(),
+                _ => vec![],
+            };
+            if !children.is_empty() && depth + 1 >= MAX_NESTING {
+                return None;
+            }
+            stack.extend(children.into_iter().map(|val| (val, depth + 1)));
+        }
+        Some(false)
+    }
+}
+
+fn is_coll(val: &Value) -> bool {
+    matches!(val, Value::Array(_) | Value::Map(_))
+}
+
+/// Drop the values with the arrays and maps only held by them in a loop
+/// instead of by recursion, so a deep value can not overflow the stack.
+pub(super) fn drop_values(mut values: Vec<Value>) {
+    while let Some(val) = values.pop() {
+        match val {
+            Value::Array(mut arr) => values.extend(arr.take_values()),
+            Value::Map(mut map) => values.extend(map.take_values()),
+            _ => (),
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.eq_within(other, MAX_NESTING)
+    }
+}
+
+impl Eq for Value {}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.hash_within(state, MAX_NESTING);
+    }
+}
+
+/// The error raised by a [XFn], which can be caught by `try` in jisp.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HostError {
+    msg: String,
+    pub(crate) cause: Option<Box<RuntimeError>>, // The error of a callback by XFnContext::call.
+}
+
+impl HostError {
+    pub fn new<T: Into<String>>(msg: T) -> Self {
+        Self { msg: msg.into(), cause: None }
+    }
+
+    pub fn msg(&self) -> &str {
+        &self.msg
+    }
+}
+
+impl Display for HostError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+impl std::error::Error for HostError {}
+
+impl From<String> for HostError {
+    fn from(msg: String) -> Self {
+        Self::new(msg)
+    }
+}
+
+impl From<&str> for HostError {
+    fn from(msg: &str) -> Self {
+        Self::new(msg)
+    }
+}
+
+impl From<RuntimeError> for HostError {
+    fn from(err: RuntimeError) -> Self {
+        Self { msg: err.to_string(), cause: Some(Box::new(err)) }
+    }
+}
+
+type XFnInner = dyn Fn(&XFnContext<'_>, Vec<Value>) -> Result<Value, HostError>;
+
 pub struct XFn {
     id: String,
-    inner: Box<dyn Fn(Vec<Value>) -> Value>,
+    inner: Box<XFnInner>,
 }
 
 impl PartialEq for XFn {
     fn eq(&self, other: &Self) -> bool {
-        return self.id == other.id;
+        self.id == other.id
     }
 }
 
@@ -32,10 +242,29 @@ impl Debug for XFn {
 
 impl XFn {
     pub fn new<F>(id: String, f: F) -> Self where F: Fn(Vec<Value>) -> Value + 'static {
+        Self::try_new(id, move |args| Ok(f(args)))
+    }
+
+    /// Build a [XFn] which may fail, the [HostError] is raised in jisp.
+    pub fn try_new<F>(id: String, f: F) -> Self where F: Fn(Vec<Value>) -> Result<Value, HostError> + 'static {
+        Self::with_context(id, move |_, args| f(args))
+    }
+
+    /// Build a [XFn] which reaches the runner by the [XFnContext], to call
+    /// the functions passed to it or read the data of the host.
+    pub fn with_context<F>(id: String, f: F) -> Self
+    where
+        F: Fn(&XFnContext<'_>, Vec<Value>) -> Result<Value, HostError> + 'static,
+    {
         XFn { id, inner: Box::new(f) }
     }
 
-    pub fn call(&self, args: Vec<Value>) -> Value {
-        (self.inner)(args)
+    /// The name it is registered as.
+    pub fn name(&self) -> &str {
+        &self.id
     }
-}
\ No newline at end of file
+
+    pub fn call(&self, ctx: &XFnContext<'_>, args: Vec<Value>) -> Result<Value, HostError> {
+        (self.inner)(ctx, args)
+    }
+}
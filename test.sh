@@ -21,6 +21,19 @@ test() {
     fi
 }
 
+test_query() {
+    wanted="$1"
+    input="$2"
+    shift 2
+    result=$(printf "%s" "$input" | ./target/debug/jisp query "$@")
+    if [ "$result" = "$wanted" ]; then
+        echo "jisp query $* <<< $input => $result"
+    else
+        echo "jisp query $* <<< $input => $wanted expected, got $result"
+        exit 1
+    fi
+}
+
 cleanup() {
     rm /tmp/e2e_test.jisp
 }
@@ -83,4 +96,110 @@ test 50 '
   (repeat 10 foo)
 '
 
+test 45 '(let i 0) (let s 0) (while (< i 10) (let s (+ s i)) (let i (+ i 1))) s'
+test 23 '
+  (let s 0)
+  (for [i (range 0 10)]
+    (if (== i 5) (continue))
+    (if (== i 8) (break))
+    (let s (+ s i)))
+  s
+'
+test 30 '(let s 0) (for [i (range 10 0 -2)] (let s (+ s i))) s'
+test 1024 '(loop [i 0 acc 1] (if (< i 10) (recur (+ i 1) (* acc 2)) acc))'
+test 42 '(while (== 1 1) (break 42))'
+
+test '"neg"' '(let x -5) (cond (< x 0) "neg" (> x 0) "pos" else "zero")'
+test '"zero"' '(let x 0) (cond (< x 0) "neg" (> x 0) "pos" else "zero")'
+test '"few"' '(case 3 1 "one" (2 3) "few" else "many")'
+test '"many"' '(case 9 1 "one" (2 3) "few" else "many")'
+test 2 '(case "b" "a" 1 "b" 2)'
+test true '(== "a" "a")'
+test 2 '(when (== 1 1) 1 2)'
+test 4 '(unless (== 1 2) 3 4)'
+
+test '"negative"' '
+  (fn describe [x]
+    (match x
+      [0] "zero"
+      ["hi"] "greeting"
+      [n when (< n 0)] "negative"
+      [n] (+ n 100)))
+  (describe -3)
+'
+test 105 '(match 5 [0] "zero" [n] (+ n 100))'
+test 2 '(fn second [_ y] y) (second 1 2)'
+
+test 1 '(let a [1, 2, 3, 4, 5]) (get a [0])'
+test 2 '(let b [1 2 3 4 5]) (get b [1])'
+test '["world" [3 4 5] 4]' '(let c ["hello" "world" [3 4 5]]) [(get c [1]) (get c [2]) (get c [2 1])]'
+test 30 '(let arr [10 20 30]) (get arr [2])'
+test 99 '(let arr [1 2 3 4 5]) (set arr [2] 99) (get arr [2])'
+test '[[1 2 3] 3 [2 3]]' '(let a [1 2]) (push a 3) (push a 4) (pop a) [a (len a) (slice a 1)]'
+test '[1 2 [3 4]]' '(let [a b & rest] [1 2 3 4]) [a b rest]'
+test 6 '(fn f [[a b] c] (+ a b c)) (f [1 2] 3)'
+test 2 '(match [1 2 3 4] [[x]] x [[x y & r]] (len r) [_] 0)'
+
+test '{"a" 1 "b" [1 2]}' '{"a" 1 "b" [1 2]}'
+test '[2 null ["a" "b"]]' '(let m {"a" 1 "b" {"c" 2}}) [(get m ["b" "c"]) (get m "x") (keys m)]'
+test '{"z" 2}' '(let m {"a" 1}) (assoc m "z" 2 "a" 3) (dissoc m "a") m'
+test 7 '(match {"type" "user" "id" 7} [{"type" "user" "id" id}] id [_] 0)'
+test 6 '(let {"a" a "b" [x y]} {"a" 1 "b" [2 3]}) (+ a x y)'
+
+test '[1 [true null] {"a" "b"}]' '[1 (json/parse "[true, null]") (json/parse (json/stringify {"a" "b"}))]'
+test 2 '(get (json/parse (json/stringify {"a" [1 2]} (< 0 1))) ["a" 1])'
+
+test '[3 4 null]' '(let b [1 2 [3 4]]) [(get b [2 0]) (get-in b [-1 -1]) (get-in? {} ["a" "b"])]'
+test '{"a" {"b" 2}}' '(fn inc [x] (+ x 1)) (let m {}) (assoc-in m ["a" "b"] 1) (update-in m ["a" "b"] inc)'
+test '{"a" [2]}' '(dissoc-in {"a" [1 2]} ["a" 0])'
+
+test_query '[1,2]' '{"a": [1, 2]}' -c '(get $ "a")'
+test_query 'x' '{"name": "x"}' --raw-output '(get it "name")'
+test_query '"x"' '{"name": "x"}' '(get it "name")'
+test_query $'1\n2' $'{"n": 1}\n{"n": 2}' --ndjson '(get it "n")'
+test_query '3' $'{"n": 1}\n{"n": 2}' --slurp '(+ (get-in it [0 "n"]) (get-in it [1 "n"]))'
+
+test_query $'{"n":1}\n{"n":[2]}\n3' $'1\n\n[2]\n3\n' --ndjson --compact '(if (== it 1) {"n" it} (if (== it 3) it {"n" it}))'
+
+test '[2 4 6]' '(fn double [x] (* x 2)) (map double [1 2 3])'
+test 10 '(fn add [a b] (+ a b)) (reduce add 0 (range 5))'
+test '[[1 "a"] [2 "b"]]' '(zip (take 2 (drop 1 [0 1 2 3])) (distinct ["a" "b" "a"]))'
+test '[24 120]' '(map x_fac (sort [5 4]))'
+
+test '"foobar"' '(let s1 "foo") (let s2 "bar") (+ s1 s2)'
+test '"A-B-C"' '(join (split (upper "a,b,c") ",") "-")'
+test '["x=1" 2.5]' '[(format "x={}" 1) (+ 2 (parse-float "0.5"))]'
+
+test '["2024" "01" "02"]' '(drop 1 (re/match "(\d+)-(\d+)-(\d+)" "on 2024-01-02"))'
+test '"a_b_c"' '(re/replace "\s+" "a  b c" "_")'
+
+test '"2024-01-03T00:00:00Z"' '(time/format (time/truncate (+ (time/parse "2024-01-02T20:00:00Z") (time/hours 5)) "day"))'
+test 90000 '(time/millis (- (time/parse "2024-01-01T00:01:30Z") (time/parse "2024-01-01T00:00:00.000Z")))'
+
+
+test 2432902008176640000 '(x_fac 20)'
+
+test '"x_fac: 21! overflows I64"' '(try (x_fac 21) (catch e (join [(get e "name") (get e "message")] ": ")))'
+
+
+mkdir -p /tmp/e2e_lib
+printf "%s" '(fn sq [x] (* x x)) (export sq)' > /tmp/e2e_lib/util.jisp
+test 49 '(import "e2e_lib/util.jisp" [sq]) (sq 7)'
+rm -r /tmp/e2e_lib
+
+test '[4.0 3]' '(import "std/math") (import "std/math" [max]) [(math/sqrt 16) (max 1 3)]'
+
+
+test '"hi"' '(fs/write "/tmp/e2e_io.txt" "hi") (fs/read "/tmp/e2e_io.txt")'
+rm /tmp/e2e_io.txt
+
+
+test 'x 1
+[1:22] 2
+3' '(println "x" 1) (+ 1 (dbg 2))'
+
+
+test '[#<fn fac> len "a\"b"]' '(fn fac [n] n) [fac len "a\"b"]'
+test '[null true -1.5 (time/ms 5) (re "a+")]' '[null true -1.5 (time/ms 5) (re "a+")]'
+
 cleanup
\ No newline at end of file